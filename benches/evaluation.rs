@@ -0,0 +1,57 @@
+extern crate expression_solver;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use expression_solver::{EvalError, ExpressionAtom, OperationTokenTree, TokenizeError, evaluate, evaluate_oneshot};
+
+const ITERATIONS: u32 = 200_000;
+
+/// The system allocator, counting how many allocations are made through it
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs the evaluator over the expression many times and reports the average time and number of
+/// allocations per call
+fn bench<T>(name: &str, expression: &str, evaluator: fn(&str) -> T) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(evaluator(black_box(expression)));
+    }
+    let elapsed = start.elapsed() / ITERATIONS;
+    let allocated = (ALLOCATIONS.load(Ordering::Relaxed) - allocations) as f64 / f64::from(ITERATIONS);
+    println!("{:<12} {:<24} {:?} {:.1} allocations", name, expression, elapsed, allocated);
+}
+
+fn tokenize(expression: &str) -> Result<Vec<ExpressionAtom>, TokenizeError> {
+    OperationTokenTree::try_evaluate_tokens(expression)
+}
+
+fn main() {
+    for expression in &["1+2*3-4", "12*34+56/7-8^2", "1+2+3+4+5+6+7+8+9"] {
+        bench("tokenize", expression, tokenize);
+        bench::<Result<u32, EvalError>>("evaluate", expression, evaluate);
+        bench::<Result<u32, EvalError>>("oneshot", expression, evaluate_oneshot);
+    }
+    for expression in &["3^20", "2^31", "1^4000000000", "0^4294967295"] {
+        bench::<Result<u32, EvalError>>("evaluate", expression, evaluate);
+    }
+}
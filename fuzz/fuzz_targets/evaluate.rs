@@ -0,0 +1,15 @@
+#![no_main]
+
+use expression_solver::{evaluate, evaluate_oneshot, evaluate_in, evaluate_signed, Tokenizer};
+use libfuzzer_sys::fuzz_target;
+
+// Every entry point reports bad input through its Result, so any panic here is a bug
+fuzz_target!(|data: &[u8]| {
+    if let Ok(expression) = std::str::from_utf8(data) {
+        let _ = evaluate(expression);
+        let _ = evaluate_oneshot(expression);
+        let _ = evaluate_signed(expression);
+        let _ = evaluate_in::<f64>(expression);
+        for _ in Tokenizer::new(expression) {}
+    }
+});
@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+use std::ops::Neg;
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, Function, EvalError, OperationTokenTree, Field, turn_into_character};
+use {precedence, is_right_associative, render_tokens_preserving_implicit};
+use evaluator::{to_rpn, Condition};
+
+/// An expression as a tree, where each operation holds the subexpressions it is calculated on.
+///     1+2*3 is Binary(Add, 1, Binary(Multiply, 2, 3))
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+
+    /// e.g: 1, 5
+    Number(u32),
+
+    /// e.g: 0.5
+    Real(f64),
+
+    /// e.g: 1+2
+    Binary(Operations, Box<Expr>, Box<Expr>),
+
+    /// e.g: clamp(15, 0, 10)
+    Call(Function, Vec<Expr>),
+
+    /// e.g: -(1+2)
+    Neg(Box<Expr>),
+
+    /// e.g: x
+    Var(char)
+}
+
+impl Expr {
+    pub fn num(value: u32) -> Expr {
+        Expr::Number(value)
+    }
+
+    pub fn binary(operation: Operations, left: Expr, right: Expr) -> Expr {
+        Expr::Binary(operation, Box::new(left), Box::new(right))
+    }
+
+    /// Compares two trees, treating numbers as equal when they are within epsilon of each other.
+    /// Whole numbers and reals can match each other, so 2 and 2.0000001 are approximately equal.
+    pub fn approx_eq(&self, other: &Expr, epsilon: f64) -> bool {
+        match (self, other) {
+            (&Expr::Binary(op, ref left, ref right), &Expr::Binary(other_op, ref other_left, ref other_right)) =>
+                op == other_op && left.approx_eq(other_left, epsilon) && right.approx_eq(other_right, epsilon),
+            (Expr::Neg(inner), Expr::Neg(other_inner)) => Expr::approx_eq(inner, other_inner, epsilon),
+            (&Expr::Var(name), &Expr::Var(other_name)) => name == other_name,
+            (&Expr::Call(function, ref arguments), &Expr::Call(other_function, ref other_arguments)) =>
+                function == other_function
+                    && arguments.len() == other_arguments.len()
+                    && arguments.iter().zip(other_arguments).all(|(a, b)| a.approx_eq(b, epsilon)),
+            _ => match (self.leaf_value(), other.leaf_value()) {
+                (Some(a), Some(b)) => (a - b).abs() <= epsilon,
+                _ => false
+            }
+        }
+    }
+
+    /// Applies f to every whole number in the tree, keeping its shape. Reals are left alone.
+    ///     map_numbers on 1+2 with |n| n*10 gives 10+20
+    pub fn map_numbers<F: Fn(u32) -> u32>(&self, f: F) -> Expr {
+        self.map_numbers_by(&f)
+    }
+
+    fn map_numbers_by<F: Fn(u32) -> u32>(&self, f: &F) -> Expr {
+        match *self {
+            Expr::Number(value) => Expr::Number(f(value)),
+            Expr::Real(value) => Expr::Real(value),
+            Expr::Binary(op, ref left, ref right) => Expr::binary(op, left.map_numbers_by(f), right.map_numbers_by(f)),
+            Expr::Call(function, ref arguments) =>
+                Expr::Call(function, arguments.iter().map(|argument| argument.map_numbers_by(f)).collect()),
+            Expr::Neg(ref inner) => Expr::Neg(Box::new(inner.map_numbers_by(f))),
+            Expr::Var(name) => Expr::Var(name)
+        }
+    }
+
+    /// Removes negations by pushing them into the tree: double negations cancel, and a negated
+    /// subtraction swaps its sides.
+    ///     -(-(5)) becomes 5, -(3-7) becomes 7-3
+    pub fn simplify_negations(&self) -> Expr {
+        match *self {
+            Expr::Neg(ref inner) => match inner.simplify_negations() {
+                Expr::Neg(twice) => *twice,
+                Expr::Binary(Operations::Subtract, left, right) => Expr::Binary(Operations::Subtract, right, left),
+                simplified => Expr::Neg(Box::new(simplified))
+            },
+            Expr::Binary(op, ref left, ref right) =>
+                Expr::binary(op, left.simplify_negations(), right.simplify_negations()),
+            Expr::Call(function, ref arguments) =>
+                Expr::Call(function, arguments.iter().map(Expr::simplify_negations).collect()),
+            _ => self.clone()
+        }
+    }
+
+    /// Rewrites every subtraction as adding a negation, so a chain of additions and subtractions
+    /// becomes a single chain of additions. Only meaningful for signed fields, like i64.
+    ///     5-3-1 becomes (5 + -3) + -1
+    pub fn normalize_subtraction_to_addition(&self) -> Expr {
+        match *self {
+            Expr::Binary(Operations::Subtract, ref left, ref right) => Expr::binary(Operations::Add,
+                left.normalize_subtraction_to_addition(),
+                Expr::Neg(Box::new(right.normalize_subtraction_to_addition()))),
+            Expr::Binary(op, ref left, ref right) =>
+                Expr::binary(op, left.normalize_subtraction_to_addition(), right.normalize_subtraction_to_addition()),
+            Expr::Call(function, ref arguments) =>
+                Expr::Call(function, arguments.iter().map(Expr::normalize_subtraction_to_addition).collect()),
+            Expr::Neg(ref inner) => Expr::Neg(Box::new(inner.normalize_subtraction_to_addition())),
+            _ => self.clone()
+        }
+    }
+
+    /// Calculates the tree's value over any Field
+    pub fn evaluate_in<F: Field>(&self) -> Result<F, EvalError> {
+        self.evaluate_by(&|name| Err(EvalError::UnboundVariable(name)))
+    }
+
+    /// Calculates the tree's value with the given values for its variables. A variable without a
+    /// value is UnboundVariable.
+    ///     2*x+1 with x as 3 is 7
+    pub fn evaluate_with<F: Field>(&self, variables: &HashMap<char, F>) -> Result<F, EvalError> {
+        self.evaluate_by(&|name| variables.get(&name).copied().ok_or(EvalError::UnboundVariable(name)))
+    }
+
+    fn evaluate_by<F: Field, V: Fn(char) -> Result<F, EvalError>>(&self, variable: &V) -> Result<F, EvalError> {
+        match *self {
+            Expr::Number(value) => Ok(F::from_u32(value)),
+            Expr::Real(value) => F::from_real(value),
+            Expr::Binary(op, ref left, ref right) => left.evaluate_by(variable)?.calculate(right.evaluate_by(variable)?, op),
+            // Only the branch that is picked is calculated
+            Expr::Call(Function::If, ref arguments) if arguments.len() == 3 =>
+                if arguments[0].evaluate_by(variable)?.is_true() { arguments[1].evaluate_by(variable) } else { arguments[2].evaluate_by(variable) },
+            Expr::Call(function, ref arguments) => {
+                let values = arguments.iter().map(|argument| argument.evaluate_by(variable)).collect::<Result<Vec<F>, EvalError>>()?;
+                F::call(function, &values)
+            },
+            Expr::Neg(ref inner) => F::from_u32(0).calculate(inner.evaluate_by(variable)?, Operations::Subtract),
+            Expr::Var(name) => variable(name)
+        }
+    }
+
+    /// Writes the tree back out as infix tokens, with parentheses only where they are needed, so
+    /// parse builds the same tree from them.
+    ///     Binary(Multiply, Binary(Add, 1, 2), 3) gives (, 1, +, 2, ), *, 3
+    /// Reals are written with at most 9 decimal places, and a negative one comes back as the
+    /// negation of a positive one.
+    pub fn to_tokens(&self) -> Vec<ExpressionAtom> {
+        let mut tokens: Vec<ExpressionAtom> = vec![];
+        self.push_tokens(Grouping::Needed, &mut tokens);
+        tokens
+    }
+
+    /// Writes the tree back out as text, from the tokens it writes with the given grouping. Every
+    /// multiplication is written out.
+    ///     Binary(Add, 1, Binary(Multiply, 2, 3)) is "1+2*3", or "1+(2*3)" grouped explicitly
+    pub(crate) fn to_text(&self, grouping: Grouping) -> String {
+        let mut tokens: Vec<ExpressionAtom> = vec![];
+        self.push_tokens(grouping, &mut tokens);
+        render_tokens_preserving_implicit(&tokens)
+    }
+
+    fn push_tokens(&self, grouping: Grouping, tokens: &mut Vec<ExpressionAtom>) {
+        match *self {
+            Expr::Number(value) => tokens.push(ExpressionAtom::Number(value)),
+            Expr::Real(value) if value < 0.0 => Expr::Neg(Box::new(Expr::Real(-value))).push_tokens(grouping, tokens),
+            Expr::Real(value) => tokens.push(decimal_atom(value)),
+            Expr::Var(name) => tokens.push(ExpressionAtom::Variable(name)),
+            Expr::Binary(op, ref left, ref right) => {
+                left.push_operand_tokens(op, is_right_associative(op), grouping, tokens);
+                tokens.push(ExpressionAtom::Operation(op));
+                right.push_operand_tokens(op, !is_right_associative(op), grouping, tokens);
+            },
+            Expr::Call(function, ref arguments) => {
+                tokens.push(ExpressionAtom::Function(function));
+                tokens.push(ExpressionAtom::LeftParenthesis);
+                for (index, argument) in arguments.iter().enumerate() {
+                    if index > 0 {
+                        tokens.push(ExpressionAtom::Comma);
+                    }
+                    argument.push_tokens(grouping, tokens);
+                }
+                tokens.push(ExpressionAtom::RightParenthesis);
+            },
+            Expr::Neg(ref inner) => {
+                tokens.push(ExpressionAtom::Negate);
+                match **inner {
+                    Expr::Number(_) | Expr::Var(_) | Expr::Call(..) => inner.push_tokens(grouping, tokens),
+                    Expr::Real(value) if value >= 0.0 => inner.push_tokens(grouping, tokens),
+                    _ => inner.push_grouped_tokens(grouping, tokens)
+                }
+            }
+        }
+    }
+
+    /// Writes one side of an operation, in parentheses when it would otherwise group differently,
+    /// or for Grouping::Explicit, whenever it is an operation that does not continue the chain.
+    /// Negations always get them, so -2^2 is not mistaken for (-2)^2.
+    fn push_operand_tokens(&self, parent: Operations, groups_away: bool, grouping: Grouping, tokens: &mut Vec<ExpressionAtom>) {
+        match *self {
+            Expr::Binary(op, ..) if precedence(op) < precedence(parent) || (groups_away && precedence(op) == precedence(parent)) =>
+                self.push_grouped_tokens(grouping, tokens),
+            Expr::Binary(op, ..) if grouping == Grouping::Explicit && precedence(op) != precedence(parent) =>
+                self.push_grouped_tokens(grouping, tokens),
+            Expr::Neg(_) => self.push_grouped_tokens(grouping, tokens),
+            Expr::Real(value) if value < 0.0 => self.push_grouped_tokens(grouping, tokens),
+            _ => self.push_tokens(grouping, tokens)
+        }
+    }
+
+    fn push_grouped_tokens(&self, grouping: Grouping, tokens: &mut Vec<ExpressionAtom>) {
+        tokens.push(ExpressionAtom::LeftParenthesis);
+        self.push_tokens(grouping, tokens);
+        tokens.push(ExpressionAtom::RightParenthesis);
+    }
+
+    /// The value of a number, if this is one
+    fn leaf_value(&self) -> Option<f64> {
+        match *self {
+            Expr::Number(value) => Some(f64::from(value)),
+            Expr::Real(value) => Some(value),
+            _ => None
+        }
+    }
+}
+
+/// Which parentheses Expr::to_text writes
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Grouping {
+
+    /// Only the ones the order of operations needs, like 1+2*3
+    Needed,
+
+    /// Also ones around operations that only bind because of precedence, like 1+(2*3)
+    Explicit
+}
+
+/// A tree with every subexpression's value worked out, for showing how an expression was calculated.
+/// A node whose calculation failed holds the error; its children still hold their values.
+///     1+2*3 is "+" = 7, with children "1" = 1 and "*" = 6
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalTree {
+    /// The number, operation character, or function name at this node. Negations are "neg".
+    pub label: String,
+    pub value: Result<u32, EvalError>,
+    pub children: Vec<EvalTree>
+}
+
+impl Expr {
+    /// Calculates every subexpression of the tree with u32 arithmetic, keeping each value
+    pub fn eval_tree(&self) -> EvalTree {
+        let (label, children) = match *self {
+            Expr::Number(value) => (value.to_string(), vec![]),
+            Expr::Real(value) => (value.to_string(), vec![]),
+            Expr::Binary(op, ref left, ref right) => (turn_into_character(op).to_string(), vec![left.eval_tree(), right.eval_tree()]),
+            Expr::Call(function, ref arguments) => (function.name().to_string(), arguments.iter().map(Expr::eval_tree).collect()),
+            Expr::Neg(ref inner) => ("neg".to_string(), vec![inner.eval_tree()]),
+            Expr::Var(name) => (name.to_string(), vec![])
+        };
+        let values = children.iter().map(|child| child.value).collect::<Result<Vec<u32>, EvalError>>();
+        let value = match *self {
+            // The branch that is not picked keeps its own value, but does not affect the if's
+            Expr::Call(Function::If, _) if children.len() == 3 =>
+                children[0].value.and_then(|condition| if condition != 0 { children[1].value } else { children[2].value }),
+            _ => values.and_then(|values| match *self {
+                Expr::Number(value) => Ok(value),
+                Expr::Real(value) => u32::from_real(value),
+                Expr::Binary(op, ..) => u32::calculate(values[0], values[1], op),
+                Expr::Call(function, _) => u32::call(function, &values),
+                Expr::Neg(_) => u32::calculate(0, values[0], Operations::Subtract),
+                Expr::Var(name) => Err(EvalError::UnboundVariable(name))
+            })
+        };
+        EvalTree { label, value, children }
+    }
+}
+
+/// Wraps the tree in a negation, so -Expr::num(5) is Neg(5)
+impl Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        Expr::Neg(Box::new(self))
+    }
+}
+
+/// The Decimal token for a real, with the fewest decimal places that give back the same value
+fn decimal_atom(value: f64) -> ExpressionAtom {
+    let mut closest = ExpressionAtom::Decimal { mantissa: 0, scale: 0 };
+    for scale in 0..10u32 {
+        let mantissa = (value * 10f64.powi(scale as i32)).round();
+        if mantissa > f64::from(u32::MAX) {
+            break;
+        }
+        closest = ExpressionAtom::Decimal { mantissa: mantissa as u32, scale };
+        if mantissa / 10f64.powi(scale as i32) == value {
+            break;
+        }
+    }
+    closest
+}
+
+/// Builds a tree from infix tokens
+pub fn parse(tokens: &[ExpressionAtom]) -> Result<Expr, EvalError> {
+    let mut stack: Vec<Expr> = vec![];
+    for token in to_rpn(tokens)? {
+        push_rpn(&mut stack, token)?;
+    }
+    finish_rpn(stack)
+}
+
+/// Takes the next reverse polish token, replacing the subexpressions it works on with its node
+pub(crate) fn push_rpn(stack: &mut Vec<Expr>, token: ExpressionAtom) -> Result<(), EvalError> {
+    match token {
+        ExpressionAtom::Number(num) => stack.push(Expr::Number(num)),
+        ExpressionAtom::Variable(name) => stack.push(Expr::Var(name)),
+        ExpressionAtom::Decimal { mantissa, scale } =>
+            stack.push(Expr::Real(f64::from(mantissa) / 10f64.powi(scale as i32))),
+        ExpressionAtom::Operation(op) => {
+            let right = stack.pop().ok_or(EvalError::MissingOperand)?;
+            let left = stack.pop().ok_or(EvalError::MissingOperand)?;
+            stack.push(Expr::binary(op, left, right));
+        },
+        ExpressionAtom::Custom(symbol) => return Err(EvalError::UnknownOperator(symbol)),
+        ExpressionAtom::Negate => {
+            let inner = stack.pop().ok_or(EvalError::MissingOperand)?;
+            stack.push(Expr::Neg(Box::new(inner)));
+        },
+        ExpressionAtom::Function(function) => {
+            if stack.len() < function.arity() {
+                return Err(EvalError::MissingOperand);
+            }
+            let arguments = stack.split_off(stack.len() - function.arity());
+            stack.push(Expr::Call(function, arguments));
+        },
+        _ => return Err(EvalError::MismatchedParentheses)
+    }
+    Ok(())
+}
+
+/// The finished tree, once every token has been pushed
+pub(crate) fn finish_rpn(mut stack: Vec<Expr>) -> Result<Expr, EvalError> {
+    match stack.len() {
+        0 => Err(EvalError::EmptyExpression),
+        1 => Ok(stack.remove(0)),
+        _ => Err(EvalError::MissingOperator)
+    }
+}
+
+/// Tokenizes an expression and builds a tree from it
+pub fn parse_expression(expression: &str) -> Result<Expr, EvalError> {
+    parse(&OperationTokenTree::try_evaluate_tokens(expression)?)
+}
+
+/// The operation that would be calculated last, at the root of the expression's tree, or None if
+/// the root is a number or function call.
+///     "1+2*3" is Some(Add), "(1+2)*3" is Some(Multiply)
+pub fn root_operator(expression: &str) -> Result<Option<Operations>, EvalError> {
+    match parse_expression(expression)? {
+        Expr::Binary(op, _, _) => Ok(Some(op)),
+        _ => Ok(None)
+    }
+}
+
+/// Whether two expressions parse to the same tree. Parentheses that do not change the grouping make
+/// no difference, but the order of operands does, even where it would not change the result.
+///     "(1+2)" is structurally equal to "1+2", but "1+2" is not to "2+1"
+pub fn structurally_equal(a: &str, b: &str) -> Result<bool, EvalError> {
+    Ok(parse_expression(a)? == parse_expression(b)?)
+}
+
+/// Recognizes expressions that are always 0 or always 1 from their shape alone, treating every
+/// other subexpression as unknown. Nothing is calculated, so an unknown operand that would fail,
+/// like the 1/0 in (1/0)*0, is not noticed. Sides that call rand are never the same, so
+/// rand(1, 9)-rand(1, 9) is not 0.
+///     5*0 and (2+3)-(2+3) are Some(0), 7^0 is Some(1), 5+0 is None
+pub fn trivial_value(tokens: &[ExpressionAtom]) -> Option<u32> {
+    parse(tokens).ok().as_ref().and_then(trivial)
+}
+
+/// The value of an expression, if it is 0 or 1 whatever its unknown parts are
+fn trivial(expr: &Expr) -> Option<u32> {
+    let (op, left, right) = match *expr {
+        Expr::Number(value) if value <= 1 => return Some(value),
+        Expr::Neg(ref inner) => return trivial(inner).filter(|&value| value == 0),
+        Expr::Binary(op, ref left, ref right) => (op, left, right),
+        _ => return None
+    };
+
+    let (known_left, known_right) = (trivial(left), trivial(right));
+    match op {
+        Operations::Multiply | Operations::And if known_left == Some(0) || known_right == Some(0) => Some(0),
+        Operations::Multiply | Operations::And if known_left == Some(1) && known_right == Some(1) => Some(1),
+        Operations::Or if known_left == Some(1) || known_right == Some(1) => Some(1),
+        Operations::Or if known_left == Some(0) && known_right == Some(0) => Some(0),
+        Operations::Power if known_right == Some(0) || known_left == Some(1) => Some(1),
+        Operations::Add if known_left == Some(0) => known_right,
+        Operations::Add if known_right == Some(0) => known_left,
+        Operations::Subtract | Operations::Greater | Operations::Less if left == right && is_pure(left) => Some(0),
+        Operations::Subtract if known_right == Some(0) => known_left,
+        Operations::Divide if known_right == Some(1) => known_left,
+        Operations::Equal if left == right && is_pure(left) => Some(1),
+        _ => None
+    }
+}
+
+/// Whether an expression gives the same value every time, because it calls no function that may not
+fn is_pure(expr: &Expr) -> bool {
+    match *expr {
+        Expr::Number(_) | Expr::Real(_) | Expr::Var(_) => true,
+        Expr::Binary(_, ref left, ref right) => is_pure(left) && is_pure(right),
+        Expr::Call(function, ref arguments) => function.is_pure() && arguments.iter().all(is_pure),
+        Expr::Neg(ref inner) => is_pure(inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evaluate;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_expression("1+2*3"), Ok(Expr::binary(Operations::Add, Expr::num(1),
+            Expr::binary(Operations::Multiply, Expr::num(2), Expr::num(3)))));
+        assert_eq!(parse_expression("clamp(1,2,3)"),
+            Ok(Expr::Call(Function::Clamp, vec![Expr::num(1), Expr::num(2), Expr::num(3)])));
+        assert_eq!(parse_expression("1+"), Err(EvalError::MissingOperand));
+    }
+
+    #[test]
+    fn test_to_tokens() {
+        let tree = Expr::binary(Operations::Multiply, Expr::binary(Operations::Add, Expr::num(1), Expr::num(2)), Expr::num(3));
+        assert_eq!(tree.to_tokens(), OperationTokenTree::evaluate_tokens("(1+2)*3"));
+        assert_eq!(parse_expression("1+(2*3)").unwrap().to_tokens(), OperationTokenTree::evaluate_tokens("1+2*3"));
+
+        for expression in &["1+2*3", "(1+2)*3", "1-(2-3)", "1-2-3", "2^3^2", "(2^3)^2", "-(1+2)*x", "-2^2",
+            "(-2)^2", "2*-3", "clamp(1+2, 0, x)*4", "if(1>2, 0.25, -y)", "1.5+0.125", "sum(-(3), 2)/(7-z)", "abs(-x)"] {
+            let tree = parse_expression(expression).unwrap();
+            assert_eq!(parse(&tree.to_tokens()), Ok(tree));
+        }
+
+        let built = vec![
+            Expr::binary(Operations::Subtract, Expr::num(1), Expr::binary(Operations::Add, Expr::num(2), Expr::Var('x'))),
+            Expr::binary(Operations::Divide, Expr::Neg(Box::new(Expr::Neg(Box::new(Expr::num(4))))), Expr::num(2)),
+            Expr::binary(Operations::Power, Expr::Neg(Box::new(Expr::Real(0.5))), Expr::Real(1.75)),
+            Expr::binary(Operations::Or, Expr::binary(Operations::And, Expr::num(1), Expr::num(0)), Expr::num(1)),
+            Expr::binary(Operations::And, Expr::num(1), Expr::binary(Operations::Or, Expr::num(0), Expr::num(1)))
+        ];
+        for tree in built {
+            assert_eq!(parse(&tree.to_tokens()), Ok(tree));
+        }
+    }
+
+    #[test]
+    fn test_to_text() {
+        for &(expression, needed, explicit) in &[("1+2*3", "1+2*3", "1+(2*3)"), ("(1+2)*3", "(1+2)*3", "(1+2)*3"),
+            ("2(3)-4-5", "2*3-4-5", "(2*3)-4-5"), ("2*-3", "2*(-3)", "2*(-3)"), ("-2^2", "-(2^2)", "-(2^2)")] {
+            let tree = parse_expression(expression).unwrap();
+            assert_eq!(tree.to_text(Grouping::Needed), needed);
+            assert_eq!(tree.to_text(Grouping::Explicit), explicit);
+            assert_eq!(parse_expression(needed), Ok(tree.clone()));
+            assert_eq!(::evaluate_signed(&tree.to_text(Grouping::Explicit)), ::evaluate_signed(expression));
+        }
+    }
+
+    #[test]
+    fn test_simplify_negations() {
+        let double = parse_expression("-(-(5))").unwrap();
+        assert_eq!(double, Expr::Neg(Box::new(Expr::Neg(Box::new(Expr::num(5))))));
+        assert_eq!(double.simplify_negations(), Expr::num(5));
+
+        let difference = parse_expression("-(3-7)").unwrap().simplify_negations();
+        assert_eq!(Ok(difference.clone()), parse_expression("7-3"));
+        assert_eq!(difference.evaluate_in::<i64>(), Ok(4));
+
+        let nested = parse_expression("1+-(-(-(2-9)))").unwrap();
+        assert_eq!(nested.evaluate_in::<i64>(), Ok(8));
+        assert_eq!(Ok(nested.simplify_negations()), parse_expression("1+(9-2)"));
+        assert_eq!(parse_expression("-5").unwrap().simplify_negations(), Expr::Neg(Box::new(Expr::num(5))));
+    }
+
+    #[test]
+    fn test_neg_operator() {
+        let literal = -Expr::num(5);
+        assert_eq!(literal, Expr::Neg(Box::new(Expr::num(5))));
+        assert_eq!(literal.evaluate_in::<i64>(), Ok(-5));
+        assert_eq!(literal.evaluate_in::<u32>(), Err(EvalError::Underflow));
+
+        let difference = -Expr::binary(Operations::Subtract, Expr::num(3), Expr::num(7));
+        assert_eq!(Ok(difference.clone()), parse_expression("-(3-7)"));
+        assert_eq!(difference.evaluate_in::<i64>(), Ok(4));
+        assert_eq!((-(-Expr::num(2))).simplify_negations(), Expr::num(2));
+    }
+
+    #[test]
+    fn test_normalize_subtraction_to_addition() {
+        let chain = parse_expression("5-3-1").unwrap().normalize_subtraction_to_addition();
+        let negative = |value| Expr::Neg(Box::new(Expr::num(value)));
+        assert_eq!(chain, Expr::binary(Operations::Add,
+            Expr::binary(Operations::Add, Expr::num(5), negative(3)), negative(1)));
+        assert_eq!(chain.evaluate_in::<i64>(), Ok(1));
+
+        let nested = parse_expression("10-(4-clamp(9-1, 0, 5))").unwrap();
+        assert_eq!(nested.normalize_subtraction_to_addition().evaluate_in::<i64>(), nested.evaluate_in::<i64>());
+    }
+
+    #[test]
+    fn test_eval_tree() {
+        let tree = parse_expression("1+2*3").unwrap().eval_tree();
+        assert_eq!(tree.value, ::evaluate("1+2*3"));
+        assert_eq!(tree.label, "+");
+        assert_eq!((tree.children[0].label.as_str(), tree.children[0].value), ("1", Ok(1)));
+        assert_eq!((tree.children[1].label.as_str(), tree.children[1].value), ("*", Ok(6)));
+        let leaves: Vec<Result<u32, EvalError>> = tree.children[1].children.iter().map(|leaf| leaf.value).collect();
+        assert_eq!(leaves, vec![Ok(2), Ok(3)]);
+
+        let failing = parse_expression("clamp(9, 0, 5)+(1/0)").unwrap().eval_tree();
+        assert_eq!(failing.value, Err(EvalError::DivideByZero));
+        assert_eq!(failing.children[0].value, Ok(5));
+        assert_eq!(failing.children[1].children[0].value, Ok(1));
+
+        let branch = parse_expression("if(0, 1/0, 7)").unwrap();
+        assert_eq!(branch.evaluate_in::<u32>(), Ok(7));
+        let tree = branch.eval_tree();
+        assert_eq!(tree.value, Ok(7));
+        assert_eq!(tree.children[1].value, Err(EvalError::DivideByZero));
+    }
+
+    #[test]
+    fn test_trivial_value() {
+        let trivial = |expression| trivial_value(&OperationTokenTree::evaluate_tokens(expression));
+        assert_eq!(trivial("5*0"), Some(0));
+        assert_eq!(trivial("(2+3^4)*0"), Some(0));
+        assert_eq!(trivial("7^0"), Some(1));
+        assert_eq!(trivial("1^(9*9)"), Some(1));
+        assert_eq!(trivial("(2+3)-(2+3)"), Some(0));
+        assert_eq!(trivial("4=4"), Some(1));
+        assert_eq!(trivial("6*0+1"), Some(1));
+        assert_eq!(trivial("-(5*0)"), Some(0));
+
+        assert_eq!(trivial("5+x"), None);
+        assert_eq!(trivial("5+0"), None);
+        assert_eq!(trivial("2*3"), None);
+        assert_eq!(trivial("0/5"), None);
+        assert_eq!(trivial(""), None);
+        assert_eq!(trivial("rand(1, 9)-rand(1, 9)"), None);
+        assert_eq!(trivial("(2*rand(1, 9))=(2*rand(1, 9))"), None);
+        assert_eq!(trivial("clamp(x, 0, 9)-clamp(x, 0, 9)"), Some(0));
+        assert_eq!(trivial("rand(1, 9)*0"), Some(0));
+
+        for expression in &["5*0", "7^0", "(2+3)-(2+3)", "6*0+1", "1^(9*9)"] {
+            assert_eq!(trivial(expression), evaluate(expression).ok());
+        }
+    }
+
+    #[test]
+    fn test_structurally_equal() {
+        assert_eq!(structurally_equal("(1+2)", "1+2"), Ok(true));
+        assert_eq!(structurally_equal("((1))+[2*3]", "1+2*3"), Ok(true));
+        assert_eq!(structurally_equal("(1+2)+3", "1+2+3"), Ok(true));
+        assert_eq!(structurally_equal("clamp((5), 0, (1+1))", "clamp(5,0,1+1)"), Ok(true));
+
+        assert_eq!(structurally_equal("1+2", "2+1"), Ok(false));
+        assert_eq!(structurally_equal("1+(2+3)", "1+2+3"), Ok(false));
+        assert_eq!(structurally_equal("(1+2)*3", "1+2*3"), Ok(false));
+        assert_eq!(structurally_equal("1+2", "3"), Ok(false));
+
+        assert_eq!(structurally_equal("1+", "1"), Err(EvalError::MissingOperand));
+    }
+
+    #[test]
+    fn test_root_operator() {
+        assert_eq!(root_operator("1+2*3"), Ok(Some(Operations::Add)));
+        assert_eq!(root_operator("(1+2)*3"), Ok(Some(Operations::Multiply)));
+        assert_eq!(root_operator("2^3^2"), Ok(Some(Operations::Power)));
+        assert_eq!(root_operator("1+2>2"), Ok(Some(Operations::Greater)));
+        assert_eq!(root_operator("((42))"), Ok(None));
+        assert_eq!(root_operator("clamp(1+1, 0, 1)"), Ok(None));
+        assert_eq!(root_operator("1+"), Err(EvalError::MissingOperand));
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let a = Expr::binary(Operations::Add, Expr::Real(0.1 + 0.2), Expr::num(1));
+        let b = Expr::binary(Operations::Add, Expr::Real(0.3), Expr::Real(1.0000001));
+        assert!(a != b);
+        assert!(a.approx_eq(&b, 1e-6));
+        assert!(!a.approx_eq(&b, 1e-9));
+
+        let c = Expr::binary(Operations::Subtract, Expr::Real(0.3), Expr::num(1));
+        assert!(!a.approx_eq(&c, 1e-6));
+        assert!(!Expr::num(1).approx_eq(&a, 1e-6));
+    }
+
+    #[test]
+    fn test_map_numbers() {
+        let scaled = parse_expression("1+2").unwrap().map_numbers(|n| n * 10);
+        assert_eq!(Ok(scaled), parse_expression("10+20"));
+
+        let nested = parse_expression("(1+2)*clamp(3,4,5)").unwrap();
+        assert_eq!(Ok(nested.map_numbers(|n| n + 1)), parse_expression("(2+3)*clamp(4,5,6)"));
+        assert_eq!(nested.map_numbers(|n| n), nested);
+    }
+}
@@ -0,0 +1,154 @@
+use std::convert::TryFrom;
+use std::vec::Vec;
+
+use num_bigint::BigUint;
+
+use {Operations, Function, EvalError, OperationTokenTree, ExpressionAtom};
+use {validate_parentheses, normalize_operators, turn_into_operation};
+use function::clamp;
+use evaluator::{to_rpn, reduce_rpn, Condition};
+
+/// The most bits a result may have, so something like 9^999999999 is an Overflow instead of
+/// running out of memory
+const MAX_BITS: u64 = 1 << 20;
+
+impl Condition for BigUint {
+    fn is_true(&self) -> bool {
+        *self != BigUint::default()
+    }
+}
+
+/// base^exp, or None if the result would have more than MAX_BITS bits
+fn pow(base: &BigUint, exp: &BigUint) -> Option<BigUint> {
+    if base.bits() <= 1 || !exp.is_true() {
+        return Some(if exp.is_true() { base.clone() } else { BigUint::from(1u32) });
+    }
+    let exp = u32::try_from(exp).ok()?;
+    if (base.bits() - 1).checked_mul(u64::from(exp))? > MAX_BITS {
+        return None;
+    }
+    Some(base.pow(exp))
+}
+
+/// Calculates two numbers of any size with the Operations enum. Comparisons give 0 or 1.
+pub fn calculate_big(lval: &BigUint, rval: &BigUint, operation: Operations) -> Result<BigUint, EvalError> {
+    let truth = |value: bool| BigUint::from(value);
+    match operation {
+        Operations::Add => Ok(lval + rval),
+        Operations::Subtract if lval < rval => Err(EvalError::Underflow),
+        Operations::Subtract => Ok(lval - rval),
+        Operations::Multiply => Ok(lval * rval),
+        Operations::Divide if !rval.is_true() => Err(EvalError::DivideByZero),
+        Operations::Divide => Ok(lval / rval),
+        Operations::Power => pow(lval, rval).ok_or(EvalError::Overflow),
+        Operations::Greater => Ok(truth(lval > rval)),
+        Operations::Less => Ok(truth(lval < rval)),
+        Operations::Equal => Ok(truth(lval == rval)),
+        Operations::And => Ok(truth(lval.is_true() && rval.is_true())),
+        Operations::Or => Ok(truth(lval.is_true() || rval.is_true()))
+    }
+}
+
+/// Tokenizes and evaluates an expression with numbers of any size, which may also be written in it.
+///     "2^200" and "99999999999999999999+1" work, where evaluate would overflow
+/// Numbers must be whole; decimals are Inexact.
+pub fn evaluate_big(expression: &str) -> Result<BigUint, EvalError> {
+    validate_parentheses(expression)?;
+
+    // Literals are read here, and each is left as its index for the tokenizer. Looking them up by
+    // index keeps them right when an if skips the numbers of the branch it does not take.
+    let (literals, placeholder) = extract_literals(&normalize_operators(expression));
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(&placeholder)?)?;
+    if rpn.iter().any(|atom| matches!(atom, ExpressionAtom::Decimal { .. })) {
+        return Err(EvalError::Inexact);
+    }
+    // Only happens when something the tokenizer skips, like an unknown word, joins two numbers
+    if rpn.iter().filter(|atom| matches!(atom, ExpressionAtom::Number(_))).count() != literals.len() {
+        return Err(EvalError::MissingOperator);
+    }
+
+    reduce_rpn(&rpn, |index, _| literals.get(index as usize).cloned().ok_or(EvalError::MissingOperand), |lval, rval, op| calculate_big(&lval, &rval, op), |function, arguments| match function {
+        Function::Clamp => Ok(clamp(arguments[0].clone(), arguments[1].clone(), arguments[2].clone())),
+        Function::Abs | Function::Floor | Function::Ceil | Function::Round => Ok(arguments[0].clone()),
+        Function::If => Ok(if arguments[0].is_true() { arguments[1].clone() } else { arguments[2].clone() }),
+        Function::Rand | Function::Sum | Function::Product | Function::Pct(_) => Err(EvalError::UnsupportedFunction(function))
+    })
+}
+
+/// Pulls every number out of an expression, replacing each with its index in the list. Characters the tokenizer
+/// skips, like spaces, do not split a number, so 12 34 is one number as it is everywhere else.
+fn extract_literals(expression: &str) -> (Vec<BigUint>, String) {
+    let mut literals: Vec<BigUint> = vec![];
+    let mut placeholder = String::new();
+    let mut digits = String::new();
+    // The ) on the end makes sure the last number is finished, and is taken off again after
+    for character in expression.chars().chain(Some(')')) {
+        let skipped = !character.is_alphabetic() && !character.is_ascii_digit()
+            && turn_into_operation(character).is_none() && !"()[]{},.".contains(character);
+        if character.is_ascii_digit() || (skipped && !digits.is_empty()) {
+            if character.is_ascii_digit() {
+                digits.push(character);
+            }
+            continue;
+        }
+        if let Ok(literal) = digits.parse::<BigUint>() {
+            placeholder.push_str(&literals.len().to_string());
+            literals.push(literal);
+        }
+        digits.clear();
+        placeholder.push(character);
+    }
+    placeholder.pop();
+    (literals, placeholder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn big(digits: &str) -> BigUint {
+        digits.parse().unwrap()
+    }
+
+    #[test]
+    fn test_big_arithmetic() {
+        assert_eq!(evaluate_big("2^200"), Ok(big("1606938044258990275541962092341162602522202993782792835301376")));
+        assert_eq!(evaluate_big("99999999999999999999+1").map(|value| value.to_string()), Ok("100000000000000000000".to_string()));
+        assert_eq!(evaluate_big("2^200/2^199"), Ok(BigUint::from(2u32)));
+        assert_eq!(evaluate_big("(2^64+5)-(2^64)"), Ok(BigUint::from(5u32)));
+        assert_eq!(evaluate_big("10^30/7").map(|value| value.to_string()), Ok("142857142857142857142857142857".to_string()));
+        assert_eq!(evaluate_big("2^100>2^99"), Ok(BigUint::from(1u32)));
+        assert_eq!(evaluate_big("clamp(2^70, 0, 12 34)"), Ok(BigUint::from(1234u32)));
+        assert_eq!(evaluate_big("1+2*3"), Ok(BigUint::from(7u32)));
+    }
+
+    #[test]
+    fn test_big_if() {
+        assert_eq!(evaluate_big("if(0, 5, 7)"), Ok(BigUint::from(7u32)));
+        assert_eq!(evaluate_big("if(1, 5, 7)+100"), Ok(BigUint::from(105u32)));
+        assert_eq!(evaluate_big("if(2^100>1, 2^70, 3)/2^69+if(0, 1, 10)"), Ok(BigUint::from(12u32)));
+        assert_eq!(evaluate_big("1 2 3 4 5 6 7 8 9 10 11 12+1"), Ok(big("123456789101113")));
+    }
+
+    #[test]
+    fn test_big_factorial() {
+        let product = (1..=100).map(|n: u32| n.to_string()).collect::<Vec<String>>().join("*");
+        let factorial = evaluate_big(&product).unwrap();
+        assert_eq!(factorial.to_string(), "93326215443944152681699238856266700490715968264381621468592963895217599993229915608941463976156518286253697920827223758251185210916864000000000000000000000000");
+
+        let divided = (1..=100u32).fold(factorial, |value, n| calculate_big(&value, &BigUint::from(n), Operations::Divide).unwrap());
+        assert_eq!(divided, BigUint::from(1u32));
+    }
+
+    #[test]
+    fn test_big_errors() {
+        assert_eq!(evaluate_big("1-2^100"), Err(EvalError::Underflow));
+        assert_eq!(evaluate_big("2^100/0"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_big("9^999999999"), Err(EvalError::Overflow));
+        assert_eq!(evaluate_big("1.5"), Err(EvalError::Inexact));
+        assert_eq!(evaluate_big("(1"), Err(EvalError::MismatchedParentheses));
+        assert_eq!(evaluate_big(""), Err(EvalError::EmptyExpression));
+        assert_eq!(BigUint::default().to_string(), "0");
+        assert_eq!(evaluate_big("1^99999999999999999999"), Ok(BigUint::from(1u32)));
+    }
+}
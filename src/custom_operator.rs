@@ -0,0 +1,117 @@
+use std::vec::Vec;
+
+use {EvalError, TableError, OperationTokenTree, Field, NumberAdjacencyPolicy, validate_parentheses};
+use evaluator::{to_rpn_with, reduce_rpn_with};
+use operator_table::{OperatorTable, Associativity};
+
+/// How a custom operator calculates its two values
+type Implementation = Box<dyn Fn(u32, u32) -> Result<u32, EvalError>>;
+
+/// Evaluates expressions that may use operators registered at runtime, on top of the usual ones.
+///     With @ registered as averaging, "4@6" evaluates to 5
+pub struct Evaluator {
+    table: OperatorTable,
+    implementations: Vec<(char, Implementation)>
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator { table: OperatorTable::default(), implementations: vec![] }
+    }
+
+    /// Adds an operator written as symbol, which binds with the given precedence and associativity
+    /// and is calculated by implementation. The usual operations have precedences 0 (|) to 5 (^).
+    /// A symbol that is already registered, or that OperatorTable::set_custom reserves, is an error
+    /// and leaves the evaluator as it was.
+    pub fn register_operator<F>(&mut self, symbol: char, precedence: u8, associativity: Associativity, implementation: F)
+        -> Result<(), TableError>
+        where F: Fn(u32, u32) -> Result<u32, EvalError> + 'static {
+        self.table.set_custom(symbol, precedence, associativity)?;
+        self.implementations.push((symbol, Box::new(implementation)));
+        Ok(())
+    }
+
+    /// The order of operations, including the registered operators
+    pub fn table(&self) -> &OperatorTable {
+        &self.table
+    }
+
+    /// Tokenizes and evaluates an expression, respecting order of operations and parentheses
+    pub fn evaluate(&self, expression: &str) -> Result<u32, EvalError> {
+        validate_parentheses(expression)?;
+        let tokens = OperationTokenTree::try_evaluate_tokens_with(expression, &self.table.custom_symbols(), NumberAdjacencyPolicy::default())?;
+        reduce_rpn_with(&to_rpn_with(&tokens, &self.table)?, u32::from_decimal, u32::calculate, u32::call,
+            |symbol, left, right| match self.implementations.iter().find(|&&(registered, _)| registered == symbol) {
+                Some((_, implementation)) => implementation(left, right),
+                None => Err(EvalError::UnknownOperator(symbol))
+            }, |name| Err(EvalError::UnboundVariable(name)))
+    }
+}
+
+impl Default for Evaluator {
+    fn default() -> Evaluator {
+        Evaluator::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {evaluate, debug_rpn, evaluate_rpn, ExpressionAtom};
+
+    fn with_average() -> Evaluator {
+        let mut evaluator = Evaluator::new();
+        evaluator.register_operator('@', 4, Associativity::Left, |left, right| {
+            left.checked_add(right).map(|sum| sum / 2).ok_or(EvalError::Overflow)
+        }).unwrap();
+        evaluator
+    }
+
+    #[test]
+    fn test_register_operator() {
+        let evaluator = with_average();
+        assert_eq!(evaluator.evaluate("4@6"), Ok(5));
+        assert_eq!(evaluator.evaluate("1+4@6"), Ok(6));
+        assert_eq!(evaluator.evaluate("2*4@6"), Ok(7));
+        assert_eq!(evaluator.evaluate("(1+3)@(10)"), Ok(7));
+        assert_eq!(evaluator.evaluate("4294967295@1"), Err(EvalError::Overflow));
+        assert_eq!(evaluator.evaluate("1+2*3"), evaluate("1+2*3"));
+    }
+
+    #[test]
+    fn test_associativity() {
+        let mut evaluator = Evaluator::new();
+        let subtract = |left: u32, right: u32| left.checked_sub(right).ok_or(EvalError::Underflow);
+        assert_eq!(evaluator.register_operator('~', 5, Associativity::Right, subtract), Ok(()));
+        assert_eq!(evaluator.evaluate("8~3~1"), Ok(6));
+        assert_eq!(evaluator.evaluate("2*8~3"), Ok(10));
+    }
+
+    #[test]
+    fn test_register_conflicts() {
+        let mut evaluator = with_average();
+        let first = |left: u32, _: u32| Ok(left);
+        assert_eq!(evaluator.register_operator('@', 5, Associativity::Right, first), Err(TableError::DuplicateSymbol('@')));
+        assert_eq!(evaluator.evaluate("4@6"), Ok(5));
+        assert_eq!(evaluator.table().validate(), Ok(()));
+
+        assert_eq!(evaluator.register_operator('*', 5, Associativity::Left, first),
+            Err(TableError::ShadowsOperation { symbol: '*', operation: ::Operations::Multiply }));
+        assert_eq!(evaluator.register_operator('3', 5, Associativity::Left, first), Err(TableError::ReservedSymbol('3')));
+        assert_eq!(evaluator.register_operator(')', 5, Associativity::Left, first), Err(TableError::ReservedSymbol(')')));
+        assert_eq!(evaluator.evaluate("3*4@6"), Ok(9));
+    }
+
+    #[test]
+    fn test_unregistered_operator() {
+        // Unknown characters are skipped, like everywhere else
+        assert_eq!(Evaluator::new().evaluate("4@6"), Ok(46));
+        assert_eq!(debug_rpn("4@6"), "46");
+
+        let rpn = to_rpn_with(&OperationTokenTree::try_evaluate_tokens_with("4#6", &['#'], NumberAdjacencyPolicy::default()).unwrap(), &OperatorTable::default());
+        assert_eq!(with_average().evaluate("4#6"), Ok(46));
+        assert_eq!(rpn.map(|rpn| rpn[2]), Ok(ExpressionAtom::Custom('#')));
+        assert_eq!(evaluate_rpn(&[ExpressionAtom::Number(4), ExpressionAtom::Number(6), ExpressionAtom::Custom('#')]),
+            Err(EvalError::UnknownOperator('#')));
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::vec::Vec;
+
+use {Expr, Operations, Function};
+
+/// A node of a Dag. Children are indexes into the Dag's nodes, which always come before the node
+/// that uses them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DagNode {
+    Number(u32),
+
+    /// The bits of the f64, so identical reals can be matched up
+    Real(u64),
+
+    Binary(Operations, usize, usize),
+
+    Call(Function, Vec<usize>),
+
+    Neg(usize),
+
+    Var(char)
+}
+
+/// An expression where identical subexpressions are stored once and shared by everything that uses
+/// them, the way a compiler eliminates common subexpressions.
+///     (1+2)*(1+2) has the nodes 1, 2, 1+2, and a product that uses 1+2 twice
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dag {
+    nodes: Vec<DagNode>,
+    root: usize
+}
+
+impl Dag {
+    /// Every distinct subexpression, with each one after its children
+    pub fn nodes(&self) -> &[DagNode] {
+        &self.nodes
+    }
+
+    /// The index of the node for the whole expression
+    pub fn root(&self) -> usize {
+        self.root
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl Expr {
+    /// Stores the tree with every repeated subexpression shared
+    pub fn to_dag(&self) -> Dag {
+        let mut nodes: Vec<DagNode> = vec![];
+        let mut seen: HashMap<DagNode, usize> = HashMap::new();
+        let root = intern(self, &mut nodes, &mut seen);
+        Dag { nodes, root }
+    }
+}
+
+/// The index of the node for expr, adding it and its children unless they were already seen
+fn intern(expr: &Expr, nodes: &mut Vec<DagNode>, seen: &mut HashMap<DagNode, usize>) -> usize {
+    let node = match *expr {
+        Expr::Number(value) => DagNode::Number(value),
+        Expr::Real(value) => DagNode::Real(value.to_bits()),
+        Expr::Binary(op, ref left, ref right) => DagNode::Binary(op, intern(left, nodes, seen), intern(right, nodes, seen)),
+        Expr::Call(function, ref arguments) =>
+            DagNode::Call(function, arguments.iter().map(|argument| intern(argument, nodes, seen)).collect()),
+        Expr::Neg(ref inner) => DagNode::Neg(intern(inner, nodes, seen)),
+        Expr::Var(name) => DagNode::Var(name)
+    };
+    *seen.entry(node.clone()).or_insert_with(|| {
+        nodes.push(node);
+        nodes.len() - 1
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parse_expression;
+
+    #[test]
+    fn test_to_dag_shares_subexpressions() {
+        let dag = parse_expression("(1+2)*(1+2)").unwrap().to_dag();
+        assert_eq!(dag.nodes(), &[
+            DagNode::Number(1),
+            DagNode::Number(2),
+            DagNode::Binary(Operations::Add, 0, 1),
+            DagNode::Binary(Operations::Multiply, 2, 2)
+        ][..]);
+        assert_eq!(dag.root(), 3);
+    }
+
+    #[test]
+    fn test_to_dag() {
+        let dag = parse_expression("clamp(2, 2, 2) + -2").unwrap().to_dag();
+        assert_eq!(dag.len(), 4);
+        assert_eq!(dag.nodes()[dag.root()], DagNode::Binary(Operations::Add, 1, 2));
+
+        assert_eq!(parse_expression("1+2*3").unwrap().to_dag().len(), 5);
+        assert_eq!(parse_expression("0.5+0.5").unwrap().to_dag().len(), 2);
+        assert_eq!(parse_expression("7").unwrap().to_dag().root(), 0);
+    }
+}
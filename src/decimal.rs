@@ -0,0 +1,158 @@
+use std::fmt::{Display};
+
+use {Operations, EvalError, Field};
+use field::evaluate_in;
+use square_and_multiply;
+
+/// A fixed-point number with PLACES digits after the decimal point, so tenths and hundredths
+/// are stored exactly. PLACES can be at most 19, the most a u64 has room for; more does not compile.
+///     0.10+0.20 as a Decimal<2> is exactly 0.30
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal<const PLACES: u32> {
+    units: u64
+}
+
+impl<const PLACES: u32> Decimal<PLACES> {
+    // How many units make up one whole
+    const SCALE: u64 = {
+        assert!(PLACES <= 19, "Decimal can have at most 19 places");
+        10u64.pow(PLACES)
+    };
+
+    /// How many units make up one whole
+    fn scale() -> u64 {
+        Self::SCALE
+    }
+
+    /// Builds a decimal out of the smallest units it can hold.
+    ///     Decimal::<2>::from_units(30) is 0.30
+    pub fn from_units(units: u64) -> Decimal<PLACES> {
+        Decimal { units }
+    }
+
+    /// The number of the smallest units this decimal holds
+    pub fn units(&self) -> u64 {
+        self.units
+    }
+
+    /// Divides, rounding exact halves to the even neighbour like bankers do
+    fn divide_rounded(numerator: u128, denominator: u128) -> Result<Decimal<PLACES>, EvalError> {
+        if denominator == 0 {
+            return Err(EvalError::DivideByZero);
+        }
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        let rounded = if remainder * 2 > denominator || (remainder * 2 == denominator && quotient % 2 == 1) {
+            quotient + 1
+        } else {
+            quotient
+        };
+        if rounded > u128::from(u64::MAX) {
+            return Err(EvalError::Overflow);
+        }
+        Ok(Decimal::from_units(rounded as u64))
+    }
+}
+
+impl<const PLACES: u32> Display for Decimal<PLACES> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let whole = self.units / Decimal::<PLACES>::scale();
+        let fraction = self.units % Decimal::<PLACES>::scale();
+        if PLACES == 0 {
+            write!(f, "{}", whole)
+        } else {
+            write!(f, "{}.{:0width$}", whole, fraction, width = PLACES as usize)
+        }
+    }
+}
+
+/// Addition and subtraction are exact. Multiplication and division round half-to-even to PLACES.
+/// Powers need a whole exponent.
+impl<const PLACES: u32> Field for Decimal<PLACES> {
+    fn from_u32(value: u32) -> Self {
+        Decimal::from_units(u64::from(value).saturating_mul(Decimal::<PLACES>::scale()))
+    }
+
+    fn from_decimal(mantissa: u32, scale: u32) -> Result<Self, EvalError> {
+        if scale <= PLACES {
+            let units = u64::from(mantissa).checked_mul(10u64.pow(PLACES - scale)).ok_or(EvalError::Overflow)?;
+            return Ok(Decimal::from_units(units));
+        }
+        match 10u64.checked_pow(scale - PLACES) {
+            Some(divisor) if u64::from(mantissa).is_multiple_of(divisor) => Ok(Decimal::from_units(u64::from(mantissa) / divisor)),
+            _ => Err(EvalError::Inexact)
+        }
+    }
+
+    fn add(self, rval: Self) -> Result<Self, EvalError> {
+        self.units.checked_add(rval.units).map(Decimal::from_units).ok_or(EvalError::Overflow)
+    }
+
+    fn sub(self, rval: Self) -> Result<Self, EvalError> {
+        self.units.checked_sub(rval.units).map(Decimal::from_units).ok_or(EvalError::Underflow)
+    }
+
+    fn mul(self, rval: Self) -> Result<Self, EvalError> {
+        Decimal::divide_rounded(u128::from(self.units) * u128::from(rval.units), u128::from(Decimal::<PLACES>::scale()))
+    }
+
+    fn div(self, rval: Self) -> Result<Self, EvalError> {
+        Decimal::divide_rounded(u128::from(self.units) * u128::from(Decimal::<PLACES>::scale()), u128::from(rval.units))
+    }
+
+    fn pow(self, rval: Self) -> Result<Self, EvalError> {
+        if !rval.units.is_multiple_of(Decimal::<PLACES>::scale()) {
+            return Err(EvalError::Inexact);
+        }
+        square_and_multiply(self, rval.units / Decimal::<PLACES>::scale(), Decimal::<PLACES>::from_u32(1), Decimal::mul, None)
+    }
+}
+
+/// Calculates two decimals with the Operations enum
+pub fn calculate_decimal<const PLACES: u32>(lval: Decimal<PLACES>, rval: Decimal<PLACES>, operation: Operations)
+    -> Result<Decimal<PLACES>, EvalError> {
+    lval.calculate(rval, operation)
+}
+
+/// Tokenizes and evaluates an expression with fixed-point decimals.
+///     evaluate_decimal::<2>("0.10+0.20") is exactly 0.30
+pub fn evaluate_decimal<const PLACES: u32>(expression: &str) -> Result<Decimal<PLACES>, EvalError> {
+    evaluate_in(expression)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decimal_exactness() {
+        assert_eq!(evaluate_decimal::<2>("0.10+0.20"), Ok(Decimal::from_units(30)));
+        assert_eq!(evaluate_decimal::<2>("0.10+0.20").unwrap().to_string(), "0.30");
+        assert_eq!(evaluate_decimal::<1>("0.1+0.2").unwrap().to_string(), "0.3");
+        assert_eq!(evaluate_decimal::<2>("0.1*3-0.3"), Ok(Decimal::from_units(0)));
+        assert_eq!(evaluate_decimal::<2>("19.99*3").unwrap().to_string(), "59.97");
+        assert_eq!(evaluate_decimal::<2>("10/3").unwrap().to_string(), "3.33");
+        assert_eq!(evaluate_decimal::<2>("0.125"), Err(EvalError::Inexact));
+    }
+
+    #[test]
+    fn test_decimal_pow() {
+        assert_eq!(evaluate_decimal::<2>("1.5^2").unwrap().to_string(), "2.25");
+        assert_eq!(evaluate_decimal::<2>("2^10").unwrap().to_string(), "1024.00");
+        assert_eq!(evaluate_decimal::<2>("1^4000000000").unwrap().to_string(), "1.00");
+        assert_eq!(evaluate_decimal::<2>("0.5^0").unwrap().to_string(), "1.00");
+        assert_eq!(evaluate_decimal::<2>("10^30"), Err(EvalError::Overflow));
+        assert_eq!(evaluate_decimal::<2>("4^0.5"), Err(EvalError::Inexact));
+        assert_eq!(evaluate_decimal::<19>("0.5+0.25").unwrap().units(), 7500000000000000000);
+    }
+
+    #[test]
+    fn test_calculate_decimal() {
+        let tenth = Decimal::<2>::from_units(10);
+        let fifth = Decimal::<2>::from_units(20);
+        assert_eq!(calculate_decimal(tenth, fifth, Operations::Add), Ok(Decimal::from_units(30)));
+        assert_eq!(calculate_decimal(tenth, fifth, Operations::Subtract), Err(EvalError::Underflow));
+        assert_eq!(calculate_decimal(fifth, tenth, Operations::Divide), Ok(Decimal::from_units(200)));
+        assert_eq!(calculate_decimal(tenth, Decimal::from_units(0), Operations::Divide), Err(EvalError::DivideByZero));
+    }
+}
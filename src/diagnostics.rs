@@ -0,0 +1,192 @@
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, EvalError, TokenizeError, Tokenizer, OperationTokenTree, Field};
+use {division_is_exact, try_calculate, validate_parentheses};
+use evaluator::{to_rpn, evaluate_rpn, reduce_rpn};
+
+/// A problem found in an expression, for reporting several at once
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+
+    /// A character or number that could not be read, which was skipped
+    Tokenize(TokenizeError),
+
+    /// e.g: the ) at position 3 in 1+2), which was dropped
+    UnmatchedParenthesis { pos: usize },
+
+    /// e.g: the ( at position 0 in (1+2, which was closed at the end
+    UnclosedParenthesis { pos: usize },
+
+    /// The repaired expression still could not be evaluated
+    Eval(EvalError)
+}
+
+/// Tokenizes, checks, and evaluates an expression, collecting every problem found along the way
+/// instead of stopping at the first. Problems are repaired where possible so that a best-effort
+/// result can still be given.
+///     "1+$2)*(3" reports the $, the unmatched ), and the unclosed (, and gives 7
+pub fn evaluate_diagnostics(expression: &str) -> (Option<u32>, Vec<Diagnostic>) {
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+
+    // Read every token that can be read, remembering where each started
+    let mut tokens: Vec<(usize, ExpressionAtom)> = vec![];
+    let mut tokenizer = Tokenizer::new(expression);
+    loop {
+        let position = tokenizer.position();
+        match tokenizer.next() {
+            Some(Ok(token)) => tokens.push((position, token)),
+            Some(Err(error)) => diagnostics.push(Diagnostic::Tokenize(error)),
+            None => break
+        }
+    }
+
+    // Drop parentheses that close nothing, and close the ones left open
+    let mut repaired: Vec<ExpressionAtom> = vec![];
+    let mut open: Vec<usize> = vec![];
+    for (position, token) in tokens {
+        match token {
+            ExpressionAtom::LeftParenthesis => open.push(position),
+            ExpressionAtom::RightParenthesis if open.pop().is_none() => {
+                diagnostics.push(Diagnostic::UnmatchedParenthesis { pos: position });
+                continue;
+            },
+            _ => {}
+        }
+        repaired.push(token);
+    }
+    for position in open.into_iter().rev() {
+        diagnostics.push(Diagnostic::UnclosedParenthesis { pos: position });
+        repaired.push(ExpressionAtom::RightParenthesis);
+    }
+
+    match to_rpn(&repaired).and_then(|rpn| evaluate_rpn(&rpn)) {
+        Ok(result) => (Some(result), diagnostics),
+        Err(error) => {
+            diagnostics.push(Diagnostic::Eval(error));
+            (None, diagnostics)
+        }
+    }
+}
+
+/// Suggests a corrected expression for common mistakes, or None if none were found.
+///     "1 2" (a missing operator) suggests "1*2"
+///     "(1+2" (an unclosed parenthesis) suggests "(1+2)"
+/// The suggestion has its whitespace removed.
+pub fn suggest(expression: &str) -> Option<String> {
+    let mut suggestion = String::new();
+    let mut changed = false;
+    let mut depth: usize = 0;
+
+    // The last character that was not whitespace, and whether whitespace came after it
+    let mut previous: Option<char> = None;
+    let mut spaced = false;
+
+    for character in expression.chars() {
+        if character.is_whitespace() {
+            spaced = true;
+            continue;
+        }
+
+        let missing_operator = match previous {
+            Some(last) => (last.is_ascii_digit() && character.is_ascii_digit() && spaced)
+                || (last == ')' && character == '('),
+            None => false
+        };
+        if missing_operator {
+            suggestion.push('*');
+            changed = true;
+        }
+
+        match character {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                // A parenthesis that closes nothing is dropped
+                changed = true;
+                previous = Some(character);
+                spaced = false;
+                continue;
+            },
+            ')' => depth -= 1,
+            _ => {}
+        }
+
+        suggestion.push(character);
+        previous = Some(character);
+        spaced = false;
+    }
+
+    if depth > 0 {
+        suggestion.push_str(&")".repeat(depth));
+        changed = true;
+    }
+
+    if changed { Some(suggestion) } else { None }
+}
+
+/// Something evaluation did that may not be what was meant, though it is not an error
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+
+    /// e.g: 7/2, which is 3 with the remainder of 1 dropped
+    TruncatedDivision { lval: u32, rval: u32 }
+}
+
+/// Evaluates an expression, noting every step that quietly lost information along the way.
+///     "7/2" is 3, with a TruncatedDivision warning
+/// Warnings from steps before an error are still reported.
+pub fn evaluate_warned(expression: &str) -> (Result<u32, EvalError>, Vec<Warning>) {
+    let mut warnings: Vec<Warning> = vec![];
+    let result = validate_parentheses(expression)
+        .and_then(|_| to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?))
+        .and_then(|rpn| reduce_rpn(&rpn, u32::from_decimal, |lval, rval, op| {
+            if op == Operations::Divide && rval != 0 && !division_is_exact(lval, rval) {
+                warnings.push(Warning::TruncatedDivision { lval, rval });
+            }
+            try_calculate(lval, rval, op)
+        }, u32::call));
+    (result, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_diagnostics() {
+        let (result, diagnostics) = evaluate_diagnostics("1+$2)*(3");
+        assert_eq!(result, Some(7));
+        assert_eq!(diagnostics, vec![
+            Diagnostic::Tokenize(TokenizeError::UnexpectedChar { c: '$', pos: 2 }),
+            Diagnostic::UnmatchedParenthesis { pos: 4 },
+            Diagnostic::UnclosedParenthesis { pos: 6 }]);
+
+        let (result, diagnostics) = evaluate_diagnostics("1/0 #");
+        assert_eq!(result, None);
+        assert_eq!(diagnostics, vec![
+            Diagnostic::Tokenize(TokenizeError::UnexpectedChar { c: '#', pos: 4 }),
+            Diagnostic::Eval(EvalError::DivideByZero)]);
+
+        assert_eq!(evaluate_diagnostics("1+2*3"), (Some(7), vec![]));
+    }
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(suggest("1 2"), Some("1*2".to_string()));
+        assert_eq!(suggest("(1+2"), Some("(1+2)".to_string()));
+        assert_eq!(suggest("((1+2"), Some("((1+2))".to_string()));
+        assert_eq!(suggest("(1+2)(3)"), Some("(1+2)*(3)".to_string()));
+        assert_eq!(suggest("1+2)"), Some("1+2".to_string()));
+        assert_eq!(suggest("1 + 2"), None);
+        assert_eq!(suggest("12"), None);
+    }
+
+    #[test]
+    fn test_evaluate_warned() {
+        assert_eq!(evaluate_warned("7/2"), (Ok(3), vec![Warning::TruncatedDivision { lval: 7, rval: 2 }]));
+        assert_eq!(evaluate_warned("6/2"), (Ok(3), vec![]));
+        assert_eq!(evaluate_warned("(7/2)/2+1"), (Ok(2), vec![
+            Warning::TruncatedDivision { lval: 7, rval: 2 }, Warning::TruncatedDivision { lval: 3, rval: 2 }]));
+        assert_eq!(evaluate_warned("7/2+1/0"),
+            (Err(EvalError::DivideByZero), vec![Warning::TruncatedDivision { lval: 7, rval: 2 }]));
+    }
+}
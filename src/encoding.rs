@@ -0,0 +1,176 @@
+use std::char;
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, Function, DecodeError};
+
+// One tag byte starts every atom. Numbers and symbols follow as varints, seven bits to a byte with
+// the high bit set on every byte but the last. Operations and functions follow as their index in
+// Operations::all() and Function::all(), with pct followed by how many arguments it takes.
+const NUMBER: u8 = 0;
+const DECIMAL: u8 = 1;
+const OPERATION: u8 = 2;
+const LEFT_PARENTHESIS: u8 = 3;
+const RIGHT_PARENTHESIS: u8 = 4;
+const FUNCTION: u8 = 5;
+const COMMA: u8 = 6;
+const IMPLICIT_MULTIPLY: u8 = 7;
+const NEGATE: u8 = 8;
+const CUSTOM: u8 = 9;
+const VARIABLE: u8 = 10;
+const START: u8 = 11;
+const END: u8 = 12;
+
+/// Writes a token stream compactly, for caching parsed expressions.
+///     the tokens of 1+300 are [0, 1, 2, 0, 0, 172, 2]
+pub fn tokens_to_bytes(tokens: &[ExpressionAtom]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+    for &token in tokens {
+        match token {
+            ExpressionAtom::Number(value) => {
+                bytes.push(NUMBER);
+                write_varint(&mut bytes, value);
+            },
+            ExpressionAtom::Decimal { mantissa, scale } => {
+                bytes.push(DECIMAL);
+                write_varint(&mut bytes, mantissa);
+                write_varint(&mut bytes, scale);
+            },
+            ExpressionAtom::Operation(op) => {
+                bytes.push(OPERATION);
+                bytes.push(Operations::all().iter().position(|&other| other == op).unwrap_or(0) as u8);
+            },
+            ExpressionAtom::Function(function) => {
+                bytes.push(FUNCTION);
+                bytes.push(Function::all().iter().position(|&other| other.name() == function.name()).unwrap_or(0) as u8);
+                if let Function::Pct(arguments) = function {
+                    write_varint(&mut bytes, arguments as u32);
+                }
+            },
+            ExpressionAtom::Custom(symbol) => {
+                bytes.push(CUSTOM);
+                write_varint(&mut bytes, symbol as u32);
+            },
+            ExpressionAtom::Variable(name) => {
+                bytes.push(VARIABLE);
+                write_varint(&mut bytes, name as u32);
+            },
+            ExpressionAtom::LeftParenthesis => bytes.push(LEFT_PARENTHESIS),
+            ExpressionAtom::RightParenthesis => bytes.push(RIGHT_PARENTHESIS),
+            ExpressionAtom::Comma => bytes.push(COMMA),
+            ExpressionAtom::ImplicitMultiply => bytes.push(IMPLICIT_MULTIPLY),
+            ExpressionAtom::Negate => bytes.push(NEGATE),
+            ExpressionAtom::Start => bytes.push(START),
+            ExpressionAtom::End => bytes.push(END)
+        }
+    }
+    bytes
+}
+
+/// Reads back a token stream written by tokens_to_bytes
+pub fn tokens_from_bytes(bytes: &[u8]) -> Result<Vec<ExpressionAtom>, DecodeError> {
+    let mut tokens: Vec<ExpressionAtom> = vec![];
+    let mut pos: usize = 0;
+    while let Some(&tag) = bytes.get(pos) {
+        let tag_pos = pos;
+        pos += 1;
+        let token = match tag {
+            NUMBER => ExpressionAtom::Number(read_varint(bytes, &mut pos)?),
+            DECIMAL => {
+                let mantissa = read_varint(bytes, &mut pos)?;
+                ExpressionAtom::Decimal { mantissa, scale: read_varint(bytes, &mut pos)? }
+            },
+            OPERATION => ExpressionAtom::Operation(*read_index(bytes, &mut pos, Operations::all())?),
+            FUNCTION => match *read_index(bytes, &mut pos, Function::all())? {
+                function @ Function::Pct(_) => ExpressionAtom::Function(function.with_arguments(read_varint(bytes, &mut pos)? as usize)),
+                function => ExpressionAtom::Function(function)
+            },
+            CUSTOM => ExpressionAtom::Custom(read_char(bytes, &mut pos)?),
+            VARIABLE => ExpressionAtom::Variable(read_char(bytes, &mut pos)?),
+            LEFT_PARENTHESIS => ExpressionAtom::LeftParenthesis,
+            RIGHT_PARENTHESIS => ExpressionAtom::RightParenthesis,
+            COMMA => ExpressionAtom::Comma,
+            START => ExpressionAtom::Start,
+            END => ExpressionAtom::End,
+            IMPLICIT_MULTIPLY => ExpressionAtom::ImplicitMultiply,
+            NEGATE => ExpressionAtom::Negate,
+            _ => return Err(DecodeError::UnknownTag { tag, pos: tag_pos })
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Reads a varint starting at pos, leaving pos after it
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Result<u32, DecodeError> {
+    let start = *pos;
+    let mut value: u64 = 0;
+    for shift in (0..35).step_by(7) {
+        let byte = *bytes.get(*pos).ok_or(DecodeError::UnexpectedEnd { pos: *pos })?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 && value <= u64::from(u32::MAX) {
+            return Ok(value as u32);
+        }
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+    Err(DecodeError::InvalidPayload { pos: start })
+}
+
+/// Reads a varint starting at pos as a character
+fn read_char(bytes: &[u8], pos: &mut usize) -> Result<char, DecodeError> {
+    let start = *pos;
+    char::from_u32(read_varint(bytes, pos)?).ok_or(DecodeError::InvalidPayload { pos: start })
+}
+
+/// Reads one byte at pos as an index into items
+fn read_index<'a, T>(bytes: &[u8], pos: &mut usize, items: &'a [T]) -> Result<&'a T, DecodeError> {
+    let index = *bytes.get(*pos).ok_or(DecodeError::UnexpectedEnd { pos: *pos })?;
+    *pos += 1;
+    items.get(index as usize).ok_or(DecodeError::InvalidPayload { pos: *pos - 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use OperationTokenTree;
+
+    #[test]
+    fn test_round_trip() {
+        for expression in &["1+300", "4294967295*12345678", "2(0.125)-clamp(1, 20, 300)", "((7))^2>=3|1", "-4+-abs(9)", ""] {
+            let tokens = OperationTokenTree::evaluate_tokens(expression);
+            assert_eq!(tokens_from_bytes(&tokens_to_bytes(&tokens)), Ok(tokens));
+        }
+        let custom = vec![ExpressionAtom::Number(1), ExpressionAtom::Custom('\u{2295}'), ExpressionAtom::Variable('x'),
+            ExpressionAtom::Start, ExpressionAtom::End];
+        assert_eq!(tokens_from_bytes(&tokens_to_bytes(&custom)), Ok(custom));
+        let rpn = ::to_rpn(&OperationTokenTree::evaluate_tokens("pct(1, 2, 3)")).unwrap();
+        assert_eq!(rpn.last(), Some(&ExpressionAtom::Function(Function::Pct(3))));
+        assert_eq!(tokens_from_bytes(&tokens_to_bytes(&rpn)), Ok(rpn));
+    }
+
+    #[test]
+    fn test_encoding_is_compact() {
+        assert_eq!(tokens_to_bytes(&OperationTokenTree::evaluate_tokens("1+300")), vec![0, 1, 2, 0, 0, 172, 2]);
+        assert_eq!(tokens_to_bytes(&[ExpressionAtom::Number(u32::MAX)]).len(), 6);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(tokens_from_bytes(&[0, 1, 200]), Err(DecodeError::UnknownTag { tag: 200, pos: 2 }));
+        assert_eq!(tokens_from_bytes(&[0, 0x80]), Err(DecodeError::UnexpectedEnd { pos: 2 }));
+        assert_eq!(tokens_from_bytes(&[2, 99]), Err(DecodeError::InvalidPayload { pos: 1 }));
+        assert_eq!(tokens_from_bytes(&[0, 0xff, 0xff, 0xff, 0xff, 0x7f]), Err(DecodeError::InvalidPayload { pos: 1 }));
+        assert_eq!(tokens_from_bytes(&[9, 0x80, 0xb0, 0x03]), Err(DecodeError::InvalidPayload { pos: 1 }));
+        assert_eq!(tokens_from_bytes(&[]), Ok(vec![]));
+    }
+}
@@ -0,0 +1,409 @@
+use std::error::Error;
+use std::fmt::{Display};
+
+use {ExpressionAtom, Operations, Function};
+
+/// Everything that can go wrong while turning an expression into a number
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EvalError {
+
+    /// e.g: ""
+    EmptyExpression,
+
+    /// e.g: (1+2, 1+2)
+    MismatchedParentheses,
+
+    /// e.g: 1+, *2
+    MissingOperand,
+
+    /// e.g: 1 2
+    MissingOperator,
+
+    /// e.g: 1/0
+    DivideByZero,
+
+    /// e.g: 4294967295+1
+    Overflow,
+
+    /// e.g: 1-2
+    Underflow,
+
+    /// e.g: 2>1 over a type with no truth values
+    UnsupportedOperation(Operations),
+
+    /// e.g: clamp(1,2,3) over a type with no ordering
+    UnsupportedFunction(Function),
+
+    /// e.g: clamp(1, 2)
+    WrongArgumentCount { function: Function, expected: usize, found: usize },
+
+    /// e.g: base 1 or base 37
+    InvalidRadix(u32),
+
+    /// e.g: 0.5 as a whole number
+    Inexact,
+
+    /// e.g: {a} or {0 in a template
+    InvalidPlaceholder,
+
+    /// e.g: {2} in a template given two arguments
+    PlaceholderOutOfRange(usize),
+
+    /// e.g: "+ ; $" as an order of operations
+    InvalidPrecedenceSpec,
+
+    /// Evaluation was stopped from outside before it finished
+    Cancelled,
+
+    /// e.g: [1+2), where the bracket at pos closes the wrong kind of opener
+    MismatchedBrackets { open: char, close: char, pos: usize },
+
+    /// e.g: 2000000+1, when operands are limited to 1000000
+    OperandTooLarge(u32),
+
+    /// e.g: the @ in 4@6, when no operator was registered for it
+    UnknownOperator(char),
+
+    /// e.g: 1+2*3, when a chain of one operation, like 1+2+3, was needed
+    NotAChain,
+
+    /// e.g: the ( in 1+(2), for evaluations that only take numbers and operations
+    UnexpectedAtom(ExpressionAtom),
+
+    /// e.g: inf-inf, which has no floating point answer
+    NotANumber,
+
+    /// e.g: the x in 1+x, when no value was given for it
+    UnboundVariable(char),
+
+    /// e.g: 3m+2s, which adds a length to a time
+    IncompatibleUnits,
+
+    /// e.g: [[1,2],[3,4]]+[[1,2,3]], which adds matrices of different sizes
+    DimensionMismatch,
+
+    /// e.g: 0^0, when it is set to have no answer
+    Indeterminate,
+
+    /// e.g: a chain of 20 numbers, which has too many groupings to list
+    ChainTooLong(usize),
+
+    /// e.g: 12 34 under NumberAdjacencyPolicy::Error, where pos is where the 34 starts
+    AdjacentNumbers { pos: usize }
+}
+
+impl Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            EvalError::EmptyExpression => write!(f, "the expression is empty"),
+            EvalError::MismatchedParentheses => write!(f, "the parentheses are not balanced"),
+            EvalError::MissingOperand => write!(f, "an operation is missing a value"),
+            EvalError::MissingOperator => write!(f, "two values are not joined by an operation"),
+            EvalError::DivideByZero => write!(f, "division by zero"),
+            EvalError::Overflow => write!(f, "the result is too large"),
+            EvalError::Underflow => write!(f, "the result is below zero"),
+            EvalError::UnsupportedOperation(op) => write!(f, "{} is not supported here", op),
+            EvalError::UnsupportedFunction(function) => write!(f, "{} is not supported here", function),
+            EvalError::WrongArgumentCount { function, expected, found } =>
+                write!(f, "{} takes {} arguments but was given {}", function, expected, found),
+            EvalError::InvalidRadix(radix) => write!(f, "base {} is not between 2 and 36", radix),
+            EvalError::Inexact => write!(f, "the number cannot be represented exactly"),
+            EvalError::InvalidPlaceholder => write!(f, "a placeholder is not of the form {{N}}"),
+            EvalError::PlaceholderOutOfRange(index) => write!(f, "there is no argument {} for the placeholder", index),
+            EvalError::InvalidPrecedenceSpec => write!(f, "the order of operations could not be read"),
+            EvalError::Cancelled => write!(f, "evaluation was cancelled"),
+            EvalError::MismatchedBrackets { open, close, pos } =>
+                write!(f, "'{}' at position {} cannot close '{}'", close, pos, open),
+            EvalError::OperandTooLarge(operand) => write!(f, "{} is larger than operands are allowed to be", operand),
+            EvalError::UnknownOperator(symbol) => write!(f, "there is no operator {}", symbol),
+            EvalError::NotAChain => write!(f, "the expression is not numbers joined by a single operation"),
+            EvalError::UnexpectedAtom(atom) => write!(f, "{} cannot be used here", atom),
+            EvalError::NotANumber => write!(f, "the result is not a number"),
+            EvalError::UnboundVariable(name) => write!(f, "no value was given for {}", name),
+            EvalError::IncompatibleUnits => write!(f, "the units do not fit the operation"),
+            EvalError::DimensionMismatch => write!(f, "the matrix sizes do not fit the operation"),
+            EvalError::Indeterminate => write!(f, "the result has no agreed value"),
+            EvalError::ChainTooLong(count) => write!(f, "a chain of {} numbers has too many groupings to list", count),
+            EvalError::AdjacentNumbers { pos } => write!(f, "the number at position {} has no operation before it", pos)
+        }
+    }
+}
+
+impl Error for EvalError {}
+
+/// Everything that can go wrong while reading an expression into tokens. Each variant carries the
+/// character position it happened at.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenizeError {
+
+    /// e.g: the $ in 1$2
+    UnexpectedChar { c: char, pos: usize },
+
+    /// e.g: 4294967296
+    NumberOverflow { text: String, pos: usize },
+
+    /// e.g: the missing operand in 1+
+    UnexpectedEnd { pos: usize },
+
+    /// e.g: the 34 at 3 in 12 34, when numbers with only spaces between them are not allowed
+    AdjacentNumbers { pos: usize },
+
+    /// e.g: the ) at 4 in [1+2), which closes the wrong kind of bracket
+    MismatchedBrackets { open: char, close: char, pos: usize }
+}
+
+impl Display for TokenizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TokenizeError::UnexpectedChar { c, pos } =>
+                write!(f, "unexpected character '{}' at position {}", c, pos),
+            TokenizeError::NumberOverflow { ref text, pos } =>
+                write!(f, "the number {} at position {} is too large", text, pos),
+            TokenizeError::UnexpectedEnd { pos } =>
+                write!(f, "the expression ends unexpectedly at position {}", pos),
+            TokenizeError::AdjacentNumbers { pos } =>
+                write!(f, "the number at position {} has no operation before it", pos),
+            TokenizeError::MismatchedBrackets { open, close, pos } =>
+                write!(f, "'{}' at position {} cannot close '{}'", close, pos, open)
+        }
+    }
+}
+
+impl Error for TokenizeError {}
+
+/// Lets evaluation report tokenizing problems with ?. A number overflowing, adjacent numbers, and
+/// mismatched brackets have an exact match; the rest become the closest structural error.
+impl From<TokenizeError> for EvalError {
+    fn from(error: TokenizeError) -> EvalError {
+        match error {
+            TokenizeError::NumberOverflow { .. } => EvalError::Overflow,
+            TokenizeError::UnexpectedChar { .. } => EvalError::MissingOperator,
+            TokenizeError::AdjacentNumbers { pos } => EvalError::AdjacentNumbers { pos },
+            TokenizeError::UnexpectedEnd { .. } => EvalError::MissingOperand,
+            TokenizeError::MismatchedBrackets { open, close, pos } => EvalError::MismatchedBrackets { open, close, pos }
+        }
+    }
+}
+
+/// An expression that could not be read, before anything was calculated
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+
+    /// A character or number the tokenizer could not read
+    Tokenize(TokenizeError),
+
+    /// The tokens do not form an expression, e.g: the missing operand in 1+
+    Syntax(EvalError),
+
+    /// e.g: the * at 1 in 1*2, when only + and - are allowed
+    Disallowed { op: Operations, pos: usize }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ParseError::Tokenize(ref error) => write!(f, "{}", error),
+            ParseError::Syntax(error) => write!(f, "{}", error),
+            ParseError::Disallowed { op, pos } => write!(f, "{} at position {} is not allowed", op, pos)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Everything evaluate_calc can fail with, split by when it happened, so a caller can point at the
+/// syntax of an expression separately from problems like dividing by zero
+#[derive(Clone, Debug, PartialEq)]
+pub enum CalcError {
+
+    /// e.g: 1+ or (1
+    Parse(ParseError),
+
+    /// e.g: 1/0 or 0-1
+    Eval(EvalError)
+}
+
+impl Display for CalcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CalcError::Parse(ref error) => write!(f, "could not read the expression: {}", error),
+            CalcError::Eval(error) => write!(f, "could not evaluate the expression: {}", error)
+        }
+    }
+}
+
+impl Error for CalcError {}
+
+impl From<ParseError> for CalcError {
+    fn from(error: ParseError) -> CalcError {
+        CalcError::Parse(error)
+    }
+}
+
+/// Everything fold_tokens rejects. Positions are indexes into the token stream.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum StackError {
+
+    /// There were no tokens
+    Empty,
+
+    /// e.g: the second number in 1 2, which has no operation before it
+    MissingOperation { pos: usize },
+
+    /// e.g: the + in +1, or the end of 1+
+    MissingValue { pos: usize },
+
+    /// e.g: a parenthesis or function, which need more than one stack
+    Unsupported { atom: ExpressionAtom, pos: usize },
+
+    /// e.g: the / in 1/0
+    Calculation { error: EvalError, pos: usize }
+}
+
+impl Display for StackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            StackError::Empty => write!(f, "there are no tokens"),
+            StackError::MissingOperation { pos } => write!(f, "the value at token {} has no operation before it", pos),
+            StackError::MissingValue { pos } => write!(f, "a value is missing at token {}", pos),
+            StackError::Unsupported { atom, pos } => write!(f, "{} at token {} cannot be folded", atom, pos),
+            StackError::Calculation { error, pos } => write!(f, "{} at token {}", error, pos)
+        }
+    }
+}
+
+impl Error for StackError {}
+
+/// Everything evaluate_strict rejects. Positions are byte offsets into the expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum StrictError {
+
+    /// e.g: the ( at 1 in 2(3), which must be written 2*(3)
+    ImplicitMultiplication { pos: usize },
+
+    /// e.g: the 012 at 2 in 1+012
+    LeadingZero { pos: usize },
+
+    /// e.g: the 34 at 3 in 12 34, which would otherwise be read as 1234
+    MissingOperator { pos: usize },
+
+    /// A character or word that is not part of the grammar, or a number too large to read
+    Tokenize(TokenizeError),
+
+    /// The expression was read, but could not be evaluated
+    Eval(EvalError)
+}
+
+impl Display for StrictError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            StrictError::ImplicitMultiplication { pos } =>
+                write!(f, "a multiplication at position {} is implied rather than written", pos),
+            StrictError::LeadingZero { pos } => write!(f, "the number at position {} has a leading zero", pos),
+            StrictError::MissingOperator { pos } =>
+                write!(f, "the number at position {} is not joined to the one before it by an operation", pos),
+            StrictError::Tokenize(ref error) => write!(f, "{}", error),
+            StrictError::Eval(error) => write!(f, "{}", error)
+        }
+    }
+}
+
+impl Error for StrictError {}
+
+/// A conflict in an OperatorTable, found by OperatorTable::validate
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TableError {
+
+    /// e.g: @ added as a custom operator twice
+    DuplicateSymbol(char),
+
+    /// e.g: a custom operator written +, which is already Add
+    ShadowsOperation { symbol: char, operation: Operations },
+
+    /// e.g: a custom operator written 7 or (, which already mean something in an expression
+    ReservedSymbol(char),
+
+    /// e.g: a right associative operator at the same precedence as +, so 1+2@3 could group either way
+    MixedAssociativity { precedence: u8 }
+}
+
+impl Display for TableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            TableError::DuplicateSymbol(symbol) => write!(f, "the operator {} is defined more than once", symbol),
+            TableError::ShadowsOperation { symbol, operation } =>
+                write!(f, "the operator {} hides the operation {}", symbol, operation),
+            TableError::ReservedSymbol(symbol) => write!(f, "{:?} cannot be used as an operator", symbol),
+            TableError::MixedAssociativity { precedence } =>
+                write!(f, "operators at precedence {} do not all group the same way", precedence)
+        }
+    }
+}
+
+impl Error for TableError {}
+
+/// Bytes that are not an encoded token stream, from tokens_from_bytes. Each variant carries the
+/// byte offset it happened at.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+
+    /// e.g: a tag of 200, which no atom uses
+    UnknownTag { tag: u8, pos: usize },
+
+    /// e.g: an operation number past the last operation
+    InvalidPayload { pos: usize },
+
+    /// e.g: a number tag with nothing after it
+    UnexpectedEnd { pos: usize }
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            DecodeError::UnknownTag { tag, pos } => write!(f, "unknown tag {} at byte {}", tag, pos),
+            DecodeError::InvalidPayload { pos } => write!(f, "the value at byte {} is out of range", pos),
+            DecodeError::UnexpectedEnd { pos } => write!(f, "the bytes end unexpectedly at byte {}", pos)
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Text that does not name an atom, from parsing an ExpressionAtom or Operations out of a string
+///     "Number(x)" and "PLUS" are not atoms
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseAtomError {
+    pub text: String
+}
+
+impl Display for ParseAtomError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "'{}' is not an atom", self.text)
+    }
+}
+
+impl Error for ParseAtomError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_error_display() {
+        let unexpected = TokenizeError::UnexpectedChar { c: '$', pos: 1 };
+        assert!(unexpected.to_string().contains("position 1"));
+        assert!(unexpected.to_string().contains('$'));
+
+        let overflow = TokenizeError::NumberOverflow { text: "4294967296".to_string(), pos: 3 };
+        assert!(overflow.to_string().contains("position 3"));
+        assert!(overflow.to_string().contains("4294967296"));
+
+        let end = TokenizeError::UnexpectedEnd { pos: 2 };
+        assert!(end.to_string().contains("position 2"));
+
+        let brackets = TokenizeError::MismatchedBrackets { open: '[', close: ')', pos: 4 };
+        assert_eq!(EvalError::from(brackets.clone()), EvalError::MismatchedBrackets { open: '[', close: ')', pos: 4 });
+        assert_eq!(EvalError::from(TokenizeError::AdjacentNumbers { pos: 3 }), EvalError::AdjacentNumbers { pos: 3 });
+        assert_eq!(brackets.to_string(), EvalError::MismatchedBrackets { open: '[', close: ')', pos: 4 }.to_string());
+    }
+}
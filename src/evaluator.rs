@@ -0,0 +1,927 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::vec::Vec;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+#[cfg(feature = "timing")]
+use std::time::{Duration, Instant};
+
+use {ExpressionAtom, ExpressionStack, Operations, Function, OperationTokenTree, EvalError, ParseError, CalcError};
+use {precedence, is_right_associative, turn_into_operation};
+use {try_calculate, validate_parentheses, square_and_multiply};
+use field::{Field, evaluate_rpn_in};
+use operator_table::{OperatorTable, Associativity};
+use tokenizer::{Tokenizer, parse_number_run};
+use tokens::{strip_outer_parens, ExprStats, expression_stats};
+
+/// Reorders infix tokens into reverse polish notation using the shunting-yard algorithm.
+///     [1, +, 2, *, 3] becomes [1, 2, 3, *, +]
+/// Parentheses are consumed; the result only contains numbers and operations.
+pub fn to_rpn(tokens: &[ExpressionAtom]) -> Result<Vec<ExpressionAtom>, EvalError> {
+    to_rpn_with(tokens, &OperatorTable::default())
+}
+
+/// Reorders infix tokens into reverse polish notation, using the table's order of operations
+pub fn to_rpn_with(tokens: &[ExpressionAtom], table: &OperatorTable) -> Result<Vec<ExpressionAtom>, EvalError> {
+    let mut output: Vec<ExpressionAtom> = vec![];
+    let mut yard = ShuntingYard::new();
+    for &token in tokens {
+        yard.push(token, table, &mut output)?;
+    }
+    yard.finish(&mut output)?;
+    Ok(output)
+}
+
+/// The operator stack of the shunting-yard algorithm, fed one infix token at a time. Whatever is
+/// ready to be calculated is moved to the output in reverse polish order.
+#[derive(Clone, Debug)]
+pub(crate) struct ShuntingYard {
+    operators: Vec<ExpressionAtom>,
+
+    // How many arguments have been seen inside each open parenthesis
+    argument_counts: Vec<usize>,
+
+    // A - where a value should be, like at the start or after another operation, is a negation
+    expecting_operand: bool
+}
+
+impl ShuntingYard {
+    pub(crate) fn new() -> ShuntingYard {
+        ShuntingYard { operators: vec![], argument_counts: vec![], expecting_operand: true }
+    }
+
+    /// Takes the next infix token, moving anything it lets go of to the output
+    pub(crate) fn push(&mut self, token: ExpressionAtom, table: &OperatorTable, output: &mut Vec<ExpressionAtom>)
+        -> Result<(), EvalError> {
+        let operators = &mut self.operators;
+        match token {
+            ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. } | ExpressionAtom::Variable(_) =>
+                output.push(token),
+            ExpressionAtom::Operation(Operations::Subtract) | ExpressionAtom::Negate if self.expecting_operand =>
+                operators.push(ExpressionAtom::Negate),
+            ExpressionAtom::Operation(_) | ExpressionAtom::ImplicitMultiply | ExpressionAtom::Negate
+            | ExpressionAtom::Custom(_) => {
+                let incoming = match token {
+                    ExpressionAtom::Custom(_) => token,
+                    _ => ExpressionAtom::Operation(token.as_operation().ok_or(EvalError::UnexpectedAtom(token))?)
+                };
+                let (precedence, associativity) = table.binding(incoming);
+                loop {
+                    let binds_tighter = match operators.last() {
+                        Some(&top @ ExpressionAtom::Operation(_)) | Some(&top @ ExpressionAtom::Custom(_)) => {
+                            let (top_precedence, _) = table.binding(top);
+                            top_precedence > precedence
+                                || (top_precedence == precedence && associativity == Associativity::Left)
+                        },
+                        // Negation binds tighter than everything but powers, so -2^2 is -(2^2)
+                        Some(&ExpressionAtom::Negate) => incoming != ExpressionAtom::Operation(Operations::Power),
+                        _ => false
+                    };
+                    if !binds_tighter {
+                        break;
+                    }
+                    output.extend(operators.pop());
+                }
+                operators.push(incoming);
+            },
+            ExpressionAtom::Function(_) => operators.push(token),
+            // Boundaries are already implied by where the tokens begin and end
+            ExpressionAtom::Start | ExpressionAtom::End => {},
+            ExpressionAtom::LeftParenthesis => {
+                operators.push(token);
+                self.argument_counts.push(1);
+            },
+            ExpressionAtom::Comma => {
+                pop_until_parenthesis(operators, output)?;
+                match self.argument_counts.last_mut() {
+                    Some(count) => *count += 1,
+                    None => return Err(EvalError::MismatchedParentheses)
+                }
+            },
+            ExpressionAtom::RightParenthesis => {
+                pop_until_parenthesis(operators, output)?;
+                operators.pop();
+                let arguments = self.argument_counts.pop().unwrap_or(1);
+
+                if let Some(&ExpressionAtom::Function(function)) = operators.last() {
+                    let function = function.with_arguments(arguments);
+                    if arguments != function.arity() {
+                        return Err(EvalError::WrongArgumentCount {
+                            function, expected: function.arity(), found: arguments
+                        });
+                    }
+                    output.push(ExpressionAtom::Function(function));
+                    operators.pop();
+                } else if arguments > 1 {
+                    return Err(EvalError::MissingOperator);
+                }
+            }
+        }
+
+        self.expecting_operand = !matches!(token, ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. }
+            | ExpressionAtom::Variable(_) | ExpressionAtom::RightParenthesis);
+        Ok(())
+    }
+
+    /// Moves the operations still waiting to the output, once there are no more tokens
+    pub(crate) fn finish(self, output: &mut Vec<ExpressionAtom>) -> Result<(), EvalError> {
+        for atom in self.operators.into_iter().rev() {
+            match atom {
+                ExpressionAtom::LeftParenthesis | ExpressionAtom::Function(_) =>
+                    return Err(EvalError::MismatchedParentheses),
+                _ => output.push(atom)
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Moves operations to the output until the innermost open parenthesis, which is left in place
+fn pop_until_parenthesis(operators: &mut Vec<ExpressionAtom>, output: &mut Vec<ExpressionAtom>)
+    -> Result<(), EvalError> {
+    loop {
+        match operators.last() {
+            Some(&ExpressionAtom::LeftParenthesis) => return Ok(()),
+            Some(&atom) => {
+                output.push(atom);
+                operators.pop();
+            },
+            None => return Err(EvalError::MismatchedParentheses)
+        }
+    }
+}
+
+/// Runs a reverse polish token stream through a value stack. The closures decide what a value is:
+/// how numbers are read (as mantissa / 10^scale), how operations are calculated, and how functions
+/// are called. Custom operators are reported as UnknownOperator, and variables as UnboundVariable.
+/// Only the branch an if picks is reduced, so the closures are not called for anything in the other
+/// one. Nothing should be matched up with literals by the order number is called in; look them up
+/// by what they are, like a placeholder variable, instead.
+pub(crate) fn reduce_rpn<T, N, O, C>(tokens: &[ExpressionAtom], number: N, operate: O, call: C)
+    -> Result<T, EvalError>
+    where T: Condition,
+          N: FnMut(u32, u32) -> Result<T, EvalError>,
+          O: FnMut(T, T, Operations) -> Result<T, EvalError>,
+          C: FnMut(Function, &[T]) -> Result<T, EvalError> {
+    reduce_rpn_with(tokens, number, operate, call, |symbol, _, _| Err(EvalError::UnknownOperator(symbol)),
+        |name| Err(EvalError::UnboundVariable(name)))
+}
+
+/// Values an if can branch on. Anything other than zero is true.
+pub(crate) trait Condition {
+    fn is_true(&self) -> bool;
+}
+
+impl<F: Field> Condition for F {
+    fn is_true(&self) -> bool {
+        *self != F::from_u32(0)
+    }
+}
+
+/// Checking structure without calculating has no values, so every if takes its else branch
+impl Condition for () {
+    fn is_true(&self) -> bool {
+        false
+    }
+}
+
+/// Runs a reverse polish token stream through a value stack like reduce_rpn, calculating custom
+/// operators with the custom closure and looking variables up with the variable closure
+pub(crate) fn reduce_rpn_with<T, N, O, C, U, V>(tokens: &[ExpressionAtom], number: N, operate: O, call: C,
+                                                custom: U, variable: V) -> Result<T, EvalError>
+    where T: Condition,
+          N: FnMut(u32, u32) -> Result<T, EvalError>,
+          O: FnMut(T, T, Operations) -> Result<T, EvalError>,
+          C: FnMut(Function, &[T]) -> Result<T, EvalError>,
+          U: FnMut(char, T, T) -> Result<T, EvalError>,
+          V: FnMut(char) -> Result<T, EvalError> {
+    let mut reducer = Reducer { tokens, branches: if_branches(tokens), number, operate, call, custom, variable };
+    let mut values: Vec<T> = vec![];
+    reducer.reduce(0, tokens.len(), &mut values)?;
+
+    match values.len() {
+        0 => Err(EvalError::EmptyExpression),
+        1 => Ok(values.remove(0)),
+        _ => Err(EvalError::MissingOperator)
+    }
+}
+
+/// The closures reduce_rpn_with calculates with, kept together so if branches can be reduced on
+/// their own
+struct Reducer<'a, N, O, C, U, V> {
+    tokens: &'a [ExpressionAtom],
+    /// Where each if's then branch starts, with where its else branch starts and where the if is
+    branches: HashMap<usize, (usize, usize)>,
+    number: N,
+    operate: O,
+    call: C,
+    custom: U,
+    variable: V
+}
+
+impl<'a, N, O, C, U, V> Reducer<'a, N, O, C, U, V> {
+    /// Reduces tokens[start..end] onto the value stack. The first error stops reduction, so nothing
+    /// after it is read or calculated.
+    fn reduce<T>(&mut self, start: usize, end: usize, values: &mut Vec<T>) -> Result<(), EvalError>
+        where T: Condition,
+              N: FnMut(u32, u32) -> Result<T, EvalError>,
+              O: FnMut(T, T, Operations) -> Result<T, EvalError>,
+              C: FnMut(Function, &[T]) -> Result<T, EvalError>,
+              U: FnMut(char, T, T) -> Result<T, EvalError>,
+              V: FnMut(char) -> Result<T, EvalError> {
+        let mut index = start;
+        while index < end {
+            // At the start of an if's then branch, its condition is on top of the stack. Only the
+            // branch it picks is reduced, so the other cannot fail, and the if itself is skipped.
+            if let Some(&(else_start, if_index)) = self.branches.get(&index).filter(|&&(_, if_index)| if_index < end) {
+                let condition = values.pop().ok_or(EvalError::MissingOperand)?;
+                if condition.is_true() {
+                    self.reduce(index, else_start, values)?;
+                } else {
+                    self.reduce(else_start, if_index, values)?;
+                }
+                index = if_index + 1;
+                continue;
+            }
+
+            let token = self.tokens[index];
+            index += 1;
+            match token {
+                ExpressionAtom::Number(num) => values.push((self.number)(num, 0)?),
+                ExpressionAtom::Decimal { mantissa, scale } => values.push((self.number)(mantissa, scale)?),
+                ExpressionAtom::Variable(name) => values.push((self.variable)(name)?),
+                ExpressionAtom::Operation(_) | ExpressionAtom::ImplicitMultiply => {
+                    let right = values.pop().ok_or(EvalError::MissingOperand)?;
+                    let left = values.pop().ok_or(EvalError::MissingOperand)?;
+                    values.push((self.operate)(left, right, token.as_operation().ok_or(EvalError::UnexpectedAtom(token))?)?);
+                },
+                // Negation is calculated as 0-value
+                ExpressionAtom::Negate => {
+                    let value = values.pop().ok_or(EvalError::MissingOperand)?;
+                    let zero = (self.number)(0, 0)?;
+                    values.push((self.operate)(zero, value, Operations::Subtract)?);
+                },
+                ExpressionAtom::Custom(symbol) => {
+                    let right = values.pop().ok_or(EvalError::MissingOperand)?;
+                    let left = values.pop().ok_or(EvalError::MissingOperand)?;
+                    values.push((self.custom)(symbol, left, right)?);
+                },
+                ExpressionAtom::Function(function) => {
+                    if values.len() < function.arity() {
+                        return Err(EvalError::MissingOperand);
+                    }
+                    let arguments = values.split_off(values.len() - function.arity());
+                    values.push((self.call)(function, &arguments)?);
+                },
+                ExpressionAtom::Start | ExpressionAtom::End => {},
+                ExpressionAtom::LeftParenthesis | ExpressionAtom::RightParenthesis | ExpressionAtom::Comma =>
+                    return Err(EvalError::MismatchedParentheses)
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Finds the branches of every if in reverse polish notation, keyed by where the then branch
+/// starts. Ifs that run short of operands are left out, and fail like any other function would.
+///     in 1 10 20 if, the then branch starts at 1, the else branch at 2, and the if is at 3
+fn if_branches(tokens: &[ExpressionAtom]) -> HashMap<usize, (usize, usize)> {
+    let mut branches = HashMap::new();
+    for (if_index, &token) in tokens.iter().enumerate() {
+        if token != ExpressionAtom::Function(Function::If) {
+            continue;
+        }
+        let else_start = operand_start(tokens, if_index);
+        let then_start = else_start.and_then(|else_start| operand_start(tokens, else_start));
+        let condition_start = then_start.and_then(|then_start| operand_start(tokens, then_start));
+        if let (Some(else_start), Some(then_start), Some(_)) = (else_start, then_start, condition_start) {
+            branches.insert(then_start, (else_start, if_index));
+        }
+    }
+    branches
+}
+
+/// Where the operand that ends just before end starts, counting back over the values each token takes
+fn operand_start(tokens: &[ExpressionAtom], end: usize) -> Option<usize> {
+    let mut needed: usize = 1;
+    let mut index = end;
+    while needed > 0 {
+        index = index.checked_sub(1)?;
+        needed = needed - 1 + operands_taken(tokens[index]);
+    }
+    Some(index)
+}
+
+/// Evaluates a token stream that is already in reverse polish notation
+pub fn evaluate_rpn(tokens: &[ExpressionAtom]) -> Result<u32, EvalError> {
+    evaluate_rpn_in(tokens)
+}
+
+/// Whether a token stream is well formed reverse polish notation: no operation or function runs
+/// short of values, and exactly one value is left at the end. Nothing is calculated, so 1 0 / is
+/// valid even though it cannot be evaluated.
+///     1 2 + is valid, 1 + and 1 2 3 + are not
+pub fn is_valid_rpn(tokens: &[ExpressionAtom]) -> bool {
+    check_rpn(tokens).is_ok()
+}
+
+/// Walks a token stream as reverse polish notation without calculating anything, returning the
+/// first structural problem
+fn check_rpn(tokens: &[ExpressionAtom]) -> Result<(), EvalError> {
+    reduce_rpn_with(tokens, |_, _| Ok(()), |_, _, _| Ok(()), |_, _| Ok(()), |_, _, _| Ok(()), |_| Ok(()))
+}
+
+/// Tokenizes and evaluates an expression, respecting order of operations and parentheses.
+///     "1+2*3" evaluates to 7
+/// Square and curly brackets group like parentheses, but must be closed by their own kind.
+pub fn evaluate(expression: &str) -> Result<u32, EvalError> {
+    validate_parentheses(expression)?;
+    let tokens = OperationTokenTree::try_evaluate_tokens(expression)?;
+    evaluate_rpn(&to_rpn(&tokens)?)
+}
+
+/// Evaluates both expressions and orders them by value, for sorting expressions by what they
+/// calculate to. The first error, checking a before b, is returned instead.
+///     "1+1" is Less than "3", and "2*3" is Equal to "6"
+pub fn compare_expressions(a: &str, b: &str) -> Result<Ordering, EvalError> {
+    Ok(evaluate(a)?.cmp(&evaluate(b)?))
+}
+
+/// Evaluates an expression like evaluate, but reports whether it failed while being read or while
+/// being calculated.
+///     "1+" is a CalcError::Parse, "1/0" is a CalcError::Eval
+pub fn evaluate_calc(expression: &str) -> Result<u32, CalcError> {
+    validate_parentheses(expression).map_err(ParseError::Syntax)?;
+    let tokens = OperationTokenTree::try_evaluate_tokens(expression).map_err(ParseError::Tokenize)?;
+    let rpn = to_rpn(&tokens).map_err(ParseError::Syntax)?;
+    check_rpn(&rpn).map_err(ParseError::Syntax)?;
+    evaluate_rpn(&rpn).map_err(CalcError::Eval)
+}
+
+/// Tokenizes an expression, converts it to reverse polish notation, and renders it space separated.
+/// Meant for eyeballing how precedence was handled.
+///     "1+2*3" renders as "1 2 3 * +"
+/// If the expression cannot be converted, the error is rendered instead.
+pub fn debug_rpn(expression: &str) -> String {
+    match OperationTokenTree::try_evaluate_tokens(expression).map_err(EvalError::from).and_then(|tokens| to_rpn(&tokens)) {
+        Ok(rpn) => rpn.iter().map(ExpressionAtom::to_string).collect::<Vec<String>>().join(" "),
+        Err(error) => error.to_string()
+    }
+}
+
+/// Evaluates reverse polish notation written out as space separated words, the way debug_rpn
+/// renders it. Each word is a number, an operation, a function name, or neg.
+///     "1 2 3 * +" evaluates to 7
+pub fn evaluate_rpn_str(input: &str) -> Result<u32, EvalError> {
+    evaluate_rpn(&read_rpn_words(input)?)
+}
+
+/// Reads space separated reverse polish words, like the ones evaluate_rpn_str takes, into tokens
+pub(crate) fn read_rpn_words(input: &str) -> Result<Vec<ExpressionAtom>, EvalError> {
+    let mut tokens: Vec<ExpressionAtom> = vec![];
+    for word in input.split_whitespace() {
+        if word == "neg" {
+            tokens.push(ExpressionAtom::Negate);
+            continue;
+        }
+        let mut atoms = Tokenizer::new(word);
+        match (atoms.next().transpose()?, atoms.next().transpose()?) {
+            (Some(atom), None) => tokens.push(atom),
+            // A word like 1+2, which is more than one token
+            (_, Some(extra)) => return Err(EvalError::UnexpectedAtom(extra)),
+            (None, None) => {}
+        }
+    }
+    Ok(tokens)
+}
+
+/// Evaluates an expression, recording an ExpressionStack for every operation as it is applied.
+/// Meant for debugging the stack machine.
+///     "1+2*3" records 2*3, then 1+6
+/// Negations are recorded as 0-value. Function calls are not recorded, since a stack only holds
+/// two values.
+pub fn evaluate_with_stack_trace(expression: &str) -> Result<(u32, Vec<ExpressionStack>), EvalError> {
+    validate_parentheses(expression)?;
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    let mut trace: Vec<ExpressionStack> = vec![];
+    let result = reduce_rpn(&rpn, u32::from_decimal, |left, right, op| {
+        trace.push(ExpressionStack { operation: Some(op), left_value: Some(left), right_value: Some(right) });
+        try_calculate(left, right, op)
+    }, u32::call)?;
+    Ok((result, trace))
+}
+
+/// Evaluates an expression, handing every binary operation to the hook once it has been calculated,
+/// along with its operands and result. Meant for logging and metrics.
+///     "1+2*3" calls the hook with (Multiply, 2, 3, 6), then (Add, 1, 6, 7)
+/// Negations are reported as 0-value. An operation that fails is not reported.
+pub fn evaluate_with_hook<H>(expression: &str, mut hook: H) -> Result<u32, EvalError>
+    where H: FnMut(Operations, u32, u32, u32) {
+    validate_parentheses(expression)?;
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    reduce_rpn(&rpn, u32::from_decimal, |left, right, op| {
+        let result = try_calculate(left, right, op)?;
+        hook(op, left, right, result);
+        Ok(result)
+    }, u32::call)
+}
+
+/// Evaluates an expression while folding every binary operation into a running state, like
+/// evaluate_with_hook, so statistics can be gathered in the same pass. The hook is given the
+/// state, then the operation, its operands, and its result.
+///     "2*3+100" with the largest result as the state gives (106, 106)
+pub fn evaluate_reduce<S, F>(expression: &str, init: S, mut on_op: F) -> Result<(u32, S), EvalError>
+    where F: FnMut(&mut S, Operations, u32, u32, u32) {
+    let mut state = init;
+    let result = evaluate_with_hook(expression, |op, left, right, result| on_op(&mut state, op, left, right, result))?;
+    Ok((result, state))
+}
+
+/// Evaluates an expression in a single pass over its characters, without building a token list.
+/// Only plain numbers and operations are handled here; anything else, like parentheses, functions,
+/// or decimals, is handed to evaluate, so the result is always the same.
+pub fn evaluate_oneshot(expression: &str) -> Result<u32, EvalError> {
+    let mut values: Vec<u32> = Vec::with_capacity(4);
+    let mut operators: Vec<Operations> = Vec::with_capacity(4);
+    let mut number: Option<u32> = None;
+
+    let mut characters = expression.chars().peekable();
+    while let Some(&character) = characters.peek() {
+        if character.is_ascii_digit() {
+            // Two numbers in a row, like "12 34"
+            if number.is_some() {
+                return evaluate(expression);
+            }
+            number = Some(parse_number_run(&mut characters).map_err(|_| EvalError::Overflow)?);
+            continue;
+        }
+        characters.next();
+        if character.is_whitespace() {
+            continue;
+        }
+
+        let op = match (turn_into_operation(character), number.take()) {
+            (Some(op), Some(value)) => {
+                values.push(value);
+                op
+            },
+            // Anything unusual, like parentheses or an operation with no value before it
+            _ => return evaluate(expression)
+        };
+        while let Some(&top) = operators.last() {
+            if precedence(top) < precedence(op) || (precedence(top) == precedence(op) && is_right_associative(op)) {
+                break;
+            }
+            operators.pop();
+            apply_top(&mut values, top)?;
+        }
+        operators.push(op);
+    }
+
+    match number {
+        Some(value) => values.push(value),
+        None => return evaluate(expression)
+    }
+    while let Some(op) = operators.pop() {
+        apply_top(&mut values, op)?;
+    }
+    values.pop().ok_or(EvalError::EmptyExpression)
+}
+
+/// Replaces the top two values with the result of calculating them
+fn apply_top(values: &mut Vec<u32>, operation: Operations) -> Result<(), EvalError> {
+    let right = values.pop().ok_or(EvalError::MissingOperand)?;
+    let left = values.pop().ok_or(EvalError::MissingOperand)?;
+    values.push(try_calculate(left, right, operation)?);
+    Ok(())
+}
+
+/// Evaluates an expression, giving up with EvalError::Cancelled as soon as the flag is set. The
+/// flag is checked before every operation and on every step of a power, so another thread can
+/// stop a long computation.
+pub fn evaluate_cancellable(expression: &str, cancel: &AtomicBool) -> Result<u32, EvalError> {
+    validate_parentheses(expression)?;
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    reduce_rpn(&rpn, u32::from_decimal, |left, right, op| {
+        if cancel.load(AtomicOrdering::Relaxed) {
+            return Err(EvalError::Cancelled);
+        }
+        match op {
+            Operations::Power => square_and_multiply(left, u64::from(right), 1,
+                |a, b| a.checked_mul(b).ok_or(EvalError::Overflow), Some(cancel)),
+            _ => try_calculate(left, right, op)
+        }
+    }, u32::call)
+}
+
+/// How many binary reductions it takes to fully evaluate an expression, which is the number of
+/// operations in it. Nothing is calculated, so this works even if evaluation would overflow.
+///     "1+2*3" takes 2
+pub fn reduction_step_count(expression: &str) -> Result<usize, EvalError> {
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+
+    let mut depth: usize = 0;
+    let mut steps: usize = 0;
+    for token in &rpn {
+        let consumed = operands_taken(*token);
+        if depth < consumed {
+            return Err(EvalError::MissingOperand);
+        }
+        depth = depth - consumed + 1;
+        if let ExpressionAtom::Operation(_) | ExpressionAtom::Negate | ExpressionAtom::Custom(_) = *token {
+            steps += 1;
+        }
+    }
+
+    match depth {
+        0 => Err(EvalError::EmptyExpression),
+        1 => Ok(steps),
+        _ => Err(EvalError::MissingOperator)
+    }
+}
+
+/// How many values a reverse polish token takes off the stack before pushing its own
+fn operands_taken(token: ExpressionAtom) -> usize {
+    match token {
+        ExpressionAtom::Operation(_) | ExpressionAtom::ImplicitMultiply | ExpressionAtom::Custom(_) => 2,
+        ExpressionAtom::Negate => 1,
+        ExpressionAtom::Function(function) => function.arity(),
+        _ => 0
+    }
+}
+
+/// Evaluates an expression and reports how deep evaluation went, for tuning limits on nesting.
+/// Evaluation does not recurse; its value stack does the same job, so the depth is the most values
+/// that were waiting on it at once.
+///     "1+2+3" goes 2 deep, "1+(2+(3+4))" goes 4 deep
+pub fn evaluate_with_depth_report(expression: &str) -> Result<(u32, usize), EvalError> {
+    validate_parentheses(expression)?;
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    let result = evaluate_rpn(&rpn)?;
+
+    let mut depth: usize = 0;
+    let mut deepest: usize = 0;
+    for &token in &rpn {
+        depth = depth - operands_taken(token) + 1;
+        deepest = deepest.max(depth);
+    }
+    Ok((result, deepest))
+}
+
+/// Evaluates an expression along with measures of how complicated it is, for showing both at once
+///     "1+2*(3+4)" is 15, with 2 Adds, 1 Multiply, 4 literals, and a depth of 1
+pub fn evaluate_with_stats(expression: &str) -> Result<(u32, ExprStats), EvalError> {
+    validate_parentheses(expression)?;
+    let tokens = OperationTokenTree::try_evaluate_tokens(expression)?;
+    let result = evaluate_rpn(&to_rpn(&tokens)?)?;
+    Ok((result, expression_stats(&tokens)))
+}
+
+/// Evaluates an expression strictly left to right, like an adding machine's paper tape, and lists
+/// the running result after every operation, starting with the first number. Precedence is ignored.
+///     "1+2*3" gives [1, 3, 9]
+/// Only numbers and operations are allowed; parentheses, functions, and decimals are UnexpectedAtom.
+/// Parentheses around the whole expression, like the ones in (42), change nothing and are allowed.
+pub fn evaluate_tape(expression: &str) -> Result<Vec<u32>, EvalError> {
+    let tokens = OperationTokenTree::try_evaluate_tokens(expression)?;
+
+    let mut tape: Vec<u32> = vec![];
+    let mut pending: Option<Operations> = None;
+    for &token in strip_outer_parens(&tokens) {
+        match (token, tape.last().cloned(), pending) {
+            (ExpressionAtom::Number(value), None, None) => tape.push(value),
+            (ExpressionAtom::Number(value), Some(total), Some(operation)) => {
+                tape.push(try_calculate(total, value, operation)?);
+                pending = None;
+            },
+            (ExpressionAtom::Number(_), Some(_), None) => return Err(EvalError::MissingOperator),
+            (ExpressionAtom::Operation(operation), Some(_), None) => pending = Some(operation),
+            (ExpressionAtom::ImplicitMultiply, Some(_), None) => pending = Some(Operations::Multiply),
+            (ExpressionAtom::Operation(_), _, _) => return Err(EvalError::MissingOperand),
+            (atom, _, _) => return Err(EvalError::UnexpectedAtom(atom))
+        }
+    }
+
+    match (tape.is_empty(), pending) {
+        (true, _) => Err(EvalError::EmptyExpression),
+        (false, Some(_)) => Err(EvalError::MissingOperand),
+        (false, None) => Ok(tape)
+    }
+}
+
+/// Evaluates an expression and measures how long it took on the wall clock, for profiling
+/// expensive inputs like deep power towers.
+#[cfg(feature = "timing")]
+pub fn evaluate_timed(expression: &str) -> (Result<u32, EvalError>, Duration) {
+    let start = Instant::now();
+    let result = evaluate(expression);
+    (result, start.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(evaluate("1+2*3"), Ok(7));
+        assert_eq!(evaluate("(1+2)*3"), Ok(9));
+        assert_eq!(evaluate("8/2*2"), Ok(8));
+        assert_eq!(evaluate("2^3^2"), Ok(512));
+    }
+
+    #[test]
+    fn test_stack_trace() {
+        let (result, trace) = evaluate_with_stack_trace("1+2").unwrap();
+        assert_eq!(result, 3);
+        assert_eq!(trace, vec![
+            ExpressionStack { operation: Some(Operations::Add), left_value: Some(1), right_value: Some(2) }]);
+        assert_eq!(trace[0].calculate(), 3);
+
+        let (_, trace) = evaluate_with_stack_trace("1+2*3").unwrap();
+        assert_eq!(trace.iter().map(ExpressionStack::calculate).collect::<Vec<u32>>(), vec![6, 7]);
+        assert_eq!(evaluate_with_stack_trace("1/0"), Err(EvalError::DivideByZero));
+    }
+
+    #[test]
+    fn test_evaluate_with_hook() {
+        let mut calls: Vec<(Operations, u32, u32, u32)> = vec![];
+        assert_eq!(evaluate_with_hook("1+2*3", |op, left, right, result| calls.push((op, left, right, result))), Ok(7));
+        assert_eq!(calls, vec![(Operations::Multiply, 2, 3, 6), (Operations::Add, 1, 6, 7)]);
+
+        let mut count = 0;
+        assert_eq!(evaluate_with_hook("4-1/0", |_, _, _, _| count += 1), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_with_hook("clamp(9, 0, 5)+1", |_, _, _, _| count += 1), Ok(6));
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_evaluate_reduce() {
+        let largest = |expression| evaluate_reduce(expression, 0, |largest: &mut u32, _, left, right, result|
+            *largest = (*largest).max(left).max(right).max(result));
+        assert_eq!(largest("2*3+100"), Ok((106, 106)));
+        assert_eq!(largest("100-2*3"), Ok((94, 100)));
+        assert_eq!(largest("7"), Ok((7, 0)));
+        assert_eq!(largest("1/0"), Err(EvalError::DivideByZero));
+
+        let counted = evaluate_reduce("1+2*3-4", Vec::new(), |ops: &mut Vec<Operations>, op, _, _, _| ops.push(op));
+        assert_eq!(counted, Ok((3, vec![Operations::Multiply, Operations::Add, Operations::Subtract])));
+    }
+
+    #[test]
+    fn test_oversized_numbers() {
+        assert_eq!(evaluate("99999999999"), Err(EvalError::Overflow));
+        assert_eq!(evaluate("1.99999999999"), Err(EvalError::Overflow));
+        assert_eq!(evaluate("4294967295"), Ok(u32::MAX));
+        assert_eq!(evaluate("\u{0663}\u{00B2}1+1"), Ok(2));
+    }
+
+    #[test]
+    fn test_negation() {
+        assert_eq!(debug_rpn("-2^2"), "2 2 ^ neg");
+        assert_eq!(debug_rpn("2*-3"), "2 3 neg *");
+        assert_eq!(debug_rpn("-(1+2)-3"), "1 2 + neg 3 -");
+        assert_eq!(evaluate("-0"), Ok(0));
+        assert_eq!(evaluate("-1"), Err(EvalError::Underflow));
+        assert_eq!(evaluate("5--2"), Err(EvalError::Underflow));
+
+        // A negation between two values is not an operation, and is not taken as one
+        let misplaced = [ExpressionAtom::Number(2), ExpressionAtom::Negate, ExpressionAtom::Number(3)];
+        assert_eq!(to_rpn(&misplaced), Err(EvalError::UnexpectedAtom(ExpressionAtom::Negate)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(evaluate(""), Err(EvalError::EmptyExpression));
+        assert_eq!(evaluate("(1+2"), Err(EvalError::MismatchedParentheses));
+        assert_eq!(evaluate("1+"), Err(EvalError::MissingOperand));
+        assert_eq!(evaluate("1/0"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate("1-2"), Err(EvalError::Underflow));
+    }
+
+    #[test]
+    fn test_evaluate_oneshot() {
+        for expression in &["1+2*3-4", "2^3^2", "8/2*2", "10-4-3", "7", " 1 + 2 ", "1+(2*3)", "2(3)", "1+", "", "1-2"] {
+            assert_eq!(evaluate_oneshot(expression), evaluate(expression), "{}", expression);
+        }
+        assert_eq!(evaluate_oneshot("1+2*3-4"), Ok(3));
+    }
+
+    #[test]
+    fn test_evaluate_cancellable() {
+        let cancel = AtomicBool::new(false);
+        assert_eq!(evaluate_cancellable("2^31+1", &cancel), Ok(2147483649));
+        assert_eq!(evaluate_cancellable("1^4000000000", &cancel), Ok(1));
+        assert_eq!(evaluate_cancellable("2^40", &cancel), Err(EvalError::Overflow));
+        for expression in &["(1+2]", "[1+2)*3", "(1+2", "1+2)"] {
+            assert_eq!(evaluate_cancellable(expression, &cancel), evaluate(expression), "{}", expression);
+        }
+        assert_eq!(evaluate_cancellable("(1+2]", &cancel), Err(EvalError::MismatchedBrackets { open: '(', close: ']', pos: 4 }));
+
+        cancel.store(true, AtomicOrdering::Relaxed);
+        assert_eq!(evaluate_cancellable("1^4000000000", &cancel), Err(EvalError::Cancelled));
+        assert_eq!(evaluate_cancellable("7", &cancel), Ok(7));
+    }
+
+    #[test]
+    fn test_debug_rpn() {
+        assert_eq!(debug_rpn("1+2*3"), "1 2 3 * +");
+        assert_eq!(debug_rpn("(1+2)*3"), "1 2 + 3 *");
+        assert_eq!(debug_rpn("2^3^2"), "2 3 2 ^ ^");
+        assert_eq!(debug_rpn("10-4-3"), "10 4 - 3 -");
+        assert_eq!(debug_rpn("(1"), "the parentheses are not balanced");
+    }
+
+    #[test]
+    fn test_is_valid_rpn() {
+        let add = ExpressionAtom::Operation(Operations::Add);
+        assert!(is_valid_rpn(&[ExpressionAtom::Number(1), ExpressionAtom::Number(2), add]));
+        assert!(!is_valid_rpn(&[ExpressionAtom::Number(1), add]));
+        assert!(!is_valid_rpn(&[ExpressionAtom::Number(1), ExpressionAtom::Number(2), ExpressionAtom::Number(3), add]));
+        assert!(!is_valid_rpn(&[]));
+        assert!(!is_valid_rpn(&[ExpressionAtom::Number(1), ExpressionAtom::LeftParenthesis]));
+
+        for expression in &["1/0", "-3+clamp(1,2,3)", "2(0.5)"] {
+            assert!(is_valid_rpn(&to_rpn(&OperationTokenTree::evaluate_tokens(expression)).unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_if_function() {
+        assert_eq!(evaluate("if(1>0, 10, 20)"), Ok(10));
+        assert_eq!(evaluate("if(0>1, 10, 20)"), Ok(20));
+        assert_eq!(evaluate("2*if(3>2, 4, 5)+1"), Ok(9));
+        assert_eq!(evaluate("if(1, if(0, 1, 2), if(1, 3, 4))"), Ok(2));
+        assert_eq!(evaluate("if(1>0, 10)"), Err(EvalError::WrongArgumentCount { function: Function::If, expected: 3, found: 2 }));
+        assert_eq!(::evaluate_in::<f64>("if(0.5, 1/4, 1/0)"), Ok(0.25));
+    }
+
+    #[test]
+    fn test_if_skips_untaken_branch() {
+        assert_eq!(evaluate("if(1>0, 10, 1/0)"), Ok(10));
+        assert_eq!(evaluate("if(0, 1/0, 5)"), Ok(5));
+        assert_eq!(evaluate("if(1, 1/0, 5)"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate("if(0, 1-2, 3)+if(2=2, 4, 2^40)"), Ok(7));
+
+        let rpn = to_rpn(&OperationTokenTree::evaluate_tokens("if(2>1, 3*4, 5-6)")).unwrap();
+        let mut calculated: Vec<Operations> = vec![];
+        let result = reduce_rpn(&rpn, u32::from_decimal, |left, right, op| {
+            calculated.push(op);
+            try_calculate(left, right, op)
+        }, u32::call);
+        assert_eq!(result, Ok(12));
+        assert_eq!(calculated, vec![Operations::Greater, Operations::Multiply]);
+    }
+
+    #[test]
+    fn test_reduce_rpn_stops_on_first_error() {
+        let rpn = to_rpn(&OperationTokenTree::evaluate_tokens("1/0+2*3")).unwrap();
+        let mut read: Vec<u32> = vec![];
+        let mut calculated: Vec<Operations> = vec![];
+        let result = reduce_rpn(&rpn, |mantissa, scale| {
+            read.push(mantissa);
+            u32::from_decimal(mantissa, scale)
+        }, |left, right, op| {
+            calculated.push(op);
+            try_calculate(left, right, op)
+        }, u32::call);
+        assert_eq!(result, Err(EvalError::DivideByZero));
+        assert_eq!(read, vec![1, 0]);
+        assert_eq!(calculated, vec![Operations::Divide]);
+
+        let rpn = to_rpn(&OperationTokenTree::evaluate_tokens("2*3+clamp(9, 0, 4)")).unwrap();
+        assert_eq!(reduce_rpn(&rpn, u32::from_decimal, try_calculate, u32::call), Ok(10));
+    }
+
+    #[test]
+    fn test_evaluate_with_stats() {
+        let (result, stats) = evaluate_with_stats("1+2*(3+4)").unwrap();
+        assert_eq!(result, 15);
+        assert_eq!(stats.operations.get(&Operations::Add), Some(&2));
+        assert_eq!(stats.operations.get(&Operations::Multiply), Some(&1));
+        assert_eq!(stats.operations.len(), 2);
+        assert_eq!(stats.literals, 4);
+        assert_eq!(stats.max_depth, 1);
+
+        assert_eq!(evaluate_with_stats("7"), Ok((7, ExprStats { literals: 1, ..ExprStats::default() })));
+        assert_eq!(evaluate_with_stats("1/0"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_with_stats("(1"), Err(EvalError::MismatchedParentheses));
+    }
+
+    #[test]
+    fn test_evaluate_with_depth_report() {
+        assert_eq!(evaluate_with_depth_report("7"), Ok((7, 1)));
+        assert_eq!(evaluate_with_depth_report("1+2+3+4"), Ok((10, 2)));
+        assert_eq!(evaluate_with_depth_report("1+(2+(3+4))"), Ok((10, 4)));
+        assert_eq!(evaluate_with_depth_report("1+2*3^2"), Ok((19, 4)));
+        assert_eq!(evaluate_with_depth_report("clamp(1, 2, 3)"), Ok((2, 3)));
+
+        let (_, flat) = evaluate_with_depth_report("1+1+1+1+1+1").unwrap();
+        let (_, nested) = evaluate_with_depth_report("1+(1+(1+(1+(1+1))))").unwrap();
+        assert!(nested > flat);
+        assert_eq!(evaluate_with_depth_report("1/0"), Err(EvalError::DivideByZero));
+    }
+
+    #[test]
+    fn test_compare_expressions() {
+        assert_eq!(compare_expressions("1+1", "3"), Ok(Ordering::Less));
+        assert_eq!(compare_expressions("2*3", "6"), Ok(Ordering::Equal));
+        assert_eq!(compare_expressions("(1+2)*3", "1+2*3"), Ok(Ordering::Greater));
+        assert_eq!(compare_expressions("1/0", "1-2"), Err(EvalError::DivideByZero));
+        assert_eq!(compare_expressions("1", "1-2"), Err(EvalError::Underflow));
+
+        let mut expressions = vec!["2^3", "1+1", "10-5"];
+        expressions.sort_by(|a, b| compare_expressions(a, b).unwrap());
+        assert_eq!(expressions, vec!["1+1", "10-5", "2^3"]);
+    }
+
+    #[test]
+    fn test_evaluate_calc() {
+        assert_eq!(evaluate_calc("1+2*3"), Ok(7));
+        assert_eq!(evaluate_calc("1+"), Err(CalcError::Parse(ParseError::Syntax(EvalError::MissingOperand))));
+        assert_eq!(evaluate_calc("(1+2"), Err(CalcError::Parse(ParseError::Syntax(EvalError::MismatchedParentheses))));
+        assert_eq!(evaluate_calc("2*+3"), Err(CalcError::Parse(ParseError::Syntax(EvalError::MissingOperand))));
+        assert_eq!(evaluate_calc(""), Err(CalcError::Parse(ParseError::Syntax(EvalError::EmptyExpression))));
+
+        assert_eq!(evaluate_calc("1/0"), Err(CalcError::Eval(EvalError::DivideByZero)));
+        assert_eq!(evaluate_calc("1-2"), Err(CalcError::Eval(EvalError::Underflow)));
+        assert_eq!(evaluate_calc("x+1"), Err(CalcError::Eval(EvalError::UnboundVariable('x'))));
+    }
+
+    #[test]
+    fn test_evaluate_rpn_str() {
+        assert_eq!(evaluate_rpn_str("1 2 3 * +"), Ok(7));
+        assert_eq!(evaluate_rpn_str("  10   4 - 2 ^ "), Ok(36));
+        assert_eq!(evaluate_rpn_str("9 0 4 clamp 5 neg +"), Err(EvalError::Underflow));
+        assert_eq!(evaluate_rpn_str("2.0 3 *"), Ok(6));
+
+        assert_eq!(evaluate_rpn_str(""), Err(EvalError::EmptyExpression));
+        assert_eq!(evaluate_rpn_str("1 +"), Err(EvalError::MissingOperand));
+        assert_eq!(evaluate_rpn_str("1 2 3 +"), Err(EvalError::MissingOperator));
+        assert_eq!(evaluate_rpn_str("1 2 ( +"), Err(EvalError::MismatchedParentheses));
+        assert_eq!(evaluate_rpn_str("1 2+3"), Err(EvalError::UnexpectedAtom(ExpressionAtom::Operation(Operations::Add))));
+        assert_eq!(evaluate_rpn_str("1 xy +"), Err(EvalError::MissingOperator));
+        assert_eq!(evaluate_rpn_str("1 x +"), Err(EvalError::UnboundVariable('x')));
+
+        for expression in &["1+2*3", "(8-3)^2/5", "clamp(2*7, 1, 10)", "6-(-2)+0"] {
+            assert_eq!(evaluate_rpn_str(&debug_rpn(expression)), evaluate(expression));
+        }
+    }
+
+    #[test]
+    fn test_evaluate_tape() {
+        assert_eq!(evaluate_tape("1+2+3"), Ok(vec![1, 3, 6]));
+        assert_eq!(evaluate_tape("1+2*3"), Ok(vec![1, 3, 9]));
+        assert_eq!(evaluate_tape("10 - 4 / 2"), Ok(vec![10, 6, 3]));
+        assert_eq!(evaluate_tape("7"), Ok(vec![7]));
+        assert_eq!(evaluate_tape("((1+2*3))"), Ok(vec![1, 3, 9]));
+
+        assert_eq!(evaluate_tape(""), Err(EvalError::EmptyExpression));
+        assert_eq!(evaluate_tape("1+"), Err(EvalError::MissingOperand));
+        assert_eq!(evaluate_tape("*2"), Err(EvalError::MissingOperand));
+        assert_eq!(evaluate_tape("1-2"), Err(EvalError::Underflow));
+        assert_eq!(evaluate_tape("1+(2)"), Err(EvalError::UnexpectedAtom(ExpressionAtom::LeftParenthesis)));
+    }
+
+    #[test]
+    fn test_lone_numbers() {
+        let never = AtomicBool::new(false);
+        for &(expression, value) in &[("42", 42), ("(42)", 42), ("((7))", 7), (" [42] ", 42)] {
+            assert_eq!(evaluate(expression), Ok(value));
+            assert_eq!(evaluate_calc(expression), Ok(value));
+            assert_eq!(evaluate_oneshot(expression), Ok(value));
+            assert_eq!(evaluate_cancellable(expression, &never), Ok(value));
+            assert_eq!(evaluate_with_stack_trace(expression), Ok((value, vec![])));
+            assert_eq!(evaluate_with_hook(expression, |_, _, _, _| panic!("nothing to calculate")), Ok(value));
+            assert_eq!(evaluate_with_depth_report(expression), Ok((value, 1)));
+            assert_eq!(evaluate_tape(expression), Ok(vec![value]));
+            assert_eq!(::evaluate_signed(expression), Ok(i64::from(value)));
+            assert_eq!(::evaluate_float(expression), Ok(f64::from(value)));
+            assert_eq!(::evaluate_value(expression), Ok(::Value::Num(value)));
+            assert_eq!(::evaluate_strict(expression), Ok(value));
+            assert_eq!(::evaluate_checked(expression), Ok(value));
+            assert_eq!(::evaluate_seeded(expression, 1), Ok(value));
+            assert_eq!(::evaluate_fraction(expression), Ok(value.to_string()));
+            assert_eq!(::evaluate_warned(expression), (Ok(value), vec![]));
+            assert_eq!(::evaluate_diagnostics(expression), (Some(value), vec![]));
+            assert_eq!(::Evaluator::new().evaluate(expression), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_reduction_step_count() {
+        assert_eq!(reduction_step_count("7"), Ok(0));
+        assert_eq!(reduction_step_count("1+2*3"), Ok(2));
+        assert_eq!(reduction_step_count("1+2+3+4"), Ok(3));
+        assert_eq!(reduction_step_count("(1+(2*(3-1)))^2"), Ok(4));
+        assert_eq!(reduction_step_count("4294967295*2"), Ok(1));
+        assert_eq!(reduction_step_count("1+"), Err(EvalError::MissingOperand));
+    }
+
+    #[cfg(feature = "timing")]
+    #[test]
+    fn test_evaluate_timed() {
+        let (result, elapsed) = evaluate_timed("2^3^2+1");
+        assert_eq!(result, Ok(513));
+        assert!(elapsed < Duration::from_secs(1));
+    }
+}
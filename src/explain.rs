@@ -0,0 +1,133 @@
+use std::vec::Vec;
+
+use {Expr, Operations, Function, EvalError, Field, parse_expression};
+use evaluator::Condition;
+
+/// Describes how an expression is calculated, one step per operation in the order they are done.
+/// A step working on the result of the step just before it calls that result "that".
+///     "1+2*3" is "Multiply 2 and 3, then add 1, giving 7."
+///     "10-2^3" is "Raise 2 to the power of 3, then subtract that from 10, giving 2."
+/// Steps are calculated with u32 arithmetic, so an expression that cannot be evaluated cannot be
+/// explained either.
+pub fn explain(expression: &str) -> Result<String, EvalError> {
+    let mut steps: Vec<String> = vec![];
+    let (value, _) = explain_into(&parse_expression(expression)?, &mut steps)?;
+    if steps.is_empty() {
+        return Ok(format!("{} needs no calculating.", value));
+    }
+
+    let mut explanation = steps.join(", then ");
+    if let Some(first) = explanation.get(..1).map(str::to_uppercase) {
+        explanation.replace_range(..1, &first);
+    }
+    Ok(format!("{}, giving {}.", explanation, value))
+}
+
+/// Adds the steps that calculate expr, giving its value and the index of the step that made it.
+/// Numbers are not made by any step.
+fn explain_into(expr: &Expr, steps: &mut Vec<String>) -> Result<(u32, Option<usize>), EvalError> {
+    let (value, step) = match *expr {
+        Expr::Number(value) => return Ok((value, None)),
+        Expr::Real(value) => return u32::from_real(value).map(|value| (value, None)),
+        Expr::Var(name) => return Err(EvalError::UnboundVariable(name)),
+        Expr::Binary(op, ref left, ref right) => {
+            let left = explain_into(left, steps)?;
+            let right = explain_into(right, steps)?;
+            let value = u32::calculate(left.0, right.0, op)?;
+            (value, describe(op, &name_of(left, steps), &name_of(right, steps)))
+        },
+        Expr::Neg(ref inner) => {
+            let inner = explain_into(inner, steps)?;
+            let value = u32::calculate(0, inner.0, Operations::Subtract)?;
+            (value, format!("negate {}", name_of(inner, steps)))
+        },
+        // Only the branch that is picked is explained
+        Expr::Call(Function::If, ref arguments) if arguments.len() == 3 => {
+            let (condition, _) = explain_into(&arguments[0], steps)?;
+            let branch = if condition.is_true() { &arguments[1] } else { &arguments[2] };
+            let (value, _) = explain_into(branch, steps)?;
+            (value, format!("pick {} since {} is {}", value, condition, condition.is_true()))
+        },
+        Expr::Call(function, ref arguments) => {
+            let mut values: Vec<u32> = vec![];
+            for argument in arguments {
+                values.push(explain_into(argument, steps)?.0);
+            }
+            let value = u32::call(function, &values)?;
+            (value, format!("take the {} of {}", function.name(), list(&values)))
+        }
+    };
+    steps.push(step);
+    Ok((value, Some(steps.len() - 1)))
+}
+
+/// How a step refers to a value: "that" when the step just before made it, or the value itself
+fn name_of(operand: (u32, Option<usize>), steps: &[String]) -> String {
+    match operand {
+        (_, Some(step)) if step + 1 == steps.len() => "that".to_string(),
+        (value, _) => value.to_string()
+    }
+}
+
+/// One step of an explanation. Adding and multiplying leave out the "that" they work on, since
+/// the order of their operands does not matter.
+fn describe(operation: Operations, left: &str, right: &str) -> String {
+    let (other, has_that) = match (left, right) {
+        ("that", other) | (other, "that") => (other, true),
+        _ => (right, false)
+    };
+    match operation {
+        Operations::Add if has_that => format!("add {}", other),
+        Operations::Add => format!("add {} and {}", left, right),
+        Operations::Multiply if has_that => format!("multiply by {}", other),
+        Operations::Multiply => format!("multiply {} and {}", left, right),
+        Operations::Subtract => format!("subtract {} from {}", right, left),
+        Operations::Divide => format!("divide {} by {}", left, right),
+        Operations::Power => format!("raise {} to the power of {}", left, right),
+        Operations::Greater => format!("check whether {} is greater than {}", left, right),
+        Operations::Less => format!("check whether {} is less than {}", left, right),
+        Operations::Equal => format!("check whether {} equals {}", left, right),
+        Operations::And => format!("check whether both {} and {} are true", left, right),
+        Operations::Or => format!("check whether either {} or {} is true", left, right)
+    }
+}
+
+/// Values written as "1, 2 and 3"
+fn list(values: &[u32]) -> String {
+    let mut names: Vec<String> = values.iter().map(u32::to_string).collect();
+    match names.pop() {
+        Some(last) if !names.is_empty() => format!("{} and {}", names.join(", "), last),
+        Some(last) => last,
+        None => "nothing".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_explain() {
+        let explanation = explain("1+2*3").unwrap();
+        assert_eq!(explanation, "Multiply 2 and 3, then add 1, giving 7.");
+        assert!(explanation.find("Multiply") < explanation.find("add"));
+
+        assert_eq!(explain("10-2^3"), Ok("Raise 2 to the power of 3, then subtract that from 10, giving 2.".to_string()));
+        assert_eq!(explain("(1+2)*(3+4)"), Ok("Add 1 and 2, then add 3 and 4, then multiply by 3, giving 21.".to_string()));
+        assert_eq!(explain("clamp(2*8, 0, 10)"), Ok("Multiply 2 and 8, then take the clamp of 16, 0 and 10, giving 10.".to_string()));
+        assert_eq!(explain("7"), Ok("7 needs no calculating.".to_string()));
+    }
+
+    #[test]
+    fn test_explain_only_the_picked_branch() {
+        assert_eq!(explain("if(2>1, 3, 4*5)"),
+            Ok("Check whether 2 is greater than 1, then pick 3 since 1 is true, giving 3.".to_string()));
+    }
+
+    #[test]
+    fn test_explain_errors() {
+        assert_eq!(explain("1/0"), Err(EvalError::DivideByZero));
+        assert_eq!(explain("1+"), Err(EvalError::MissingOperand));
+        assert_eq!(explain("x+1"), Err(EvalError::UnboundVariable('x')));
+    }
+}
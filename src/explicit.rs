@@ -0,0 +1,118 @@
+use {ExpressionAtom, Operations, EvalError, OperationTokenTree, parse_expression};
+use {turn_into_character, try_calculate};
+use ast::Grouping;
+
+/// Rewrites an expression with parentheses around every operation that only binds because of
+/// precedence, so the order of operations can be seen. Chains of the same level, like 1+2-3,
+/// are left as they are.
+///     "1+2*3" is "1+(2*3)"
+///     "(1+2)*3" is already explicit, and stays "(1+2)*3"
+pub fn explicit_form(expression: &str) -> Result<String, EvalError> {
+    Ok(parse_expression(expression)?.to_text(Grouping::Explicit))
+}
+
+/// The most numbers associativity_variants will group. The groupings grow like the Catalan
+/// numbers, so 12 numbers already have 58786 of them.
+pub const MAX_VARIANT_CHAIN: usize = 12;
+
+/// Lists every way to fully parenthesize a chain of one operation, with what each calculates to.
+/// Left grouping comes first. Groupings that cannot be calculated, like dividing by zero, are left out.
+///     "8-3-2" gives ("((8-3)-2)", 3) and ("(8-(3-2))", 7)
+/// A chain of more than MAX_VARIANT_CHAIN numbers is ChainTooLong.
+pub fn associativity_variants(expression: &str) -> Result<Vec<(String, u32)>, EvalError> {
+    let tokens = OperationTokenTree::try_evaluate_tokens(expression)?;
+    let mut numbers: Vec<u32> = vec![];
+    let mut operation: Option<Operations> = None;
+    for (index, token) in tokens.iter().enumerate() {
+        match (index % 2, *token) {
+            (0, ExpressionAtom::Number(value)) => numbers.push(value),
+            (1, ExpressionAtom::Operation(op)) if operation.is_none_or(|chain| chain == op) => operation = Some(op),
+            _ => return Err(EvalError::NotAChain)
+        }
+    }
+
+    match (operation, numbers.len()) {
+        (_, 0) => Err(EvalError::EmptyExpression),
+        (_, count) if count > MAX_VARIANT_CHAIN => Err(EvalError::ChainTooLong(count)),
+        (Some(op), count) if count == tokens.len() / 2 + 1 => Ok(groupings(&numbers, op).into_iter()
+            .filter_map(|(text, result)| result.ok().map(|value| (text, value)))
+            .collect()),
+        (None, 1) => Ok(vec![(numbers[0].to_string(), numbers[0])]),
+        _ => Err(EvalError::MissingOperand)
+    }
+}
+
+/// Every full parenthesization of numbers joined by op, with its result
+fn groupings(numbers: &[u32], op: Operations) -> Vec<(String, Result<u32, EvalError>)> {
+    if numbers.len() == 1 {
+        return vec![(numbers[0].to_string(), Ok(numbers[0]))];
+    }
+
+    // Splitting later first puts the left-grouped forms first
+    let mut variants = vec![];
+    for split in (1..numbers.len()).rev() {
+        for (left_text, left) in groupings(&numbers[..split], op) {
+            for (right_text, right) in groupings(&numbers[split..], op) {
+                let result = left.and_then(|left| right.and_then(|right| try_calculate(left, right, op)));
+                variants.push((format!("({}{}{})", left_text, turn_into_character(op), right_text), result));
+            }
+        }
+    }
+    variants
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evaluate;
+
+    fn variants(expression: &str) -> Result<Vec<(String, u32)>, EvalError> {
+        associativity_variants(expression)
+    }
+
+    #[test]
+    fn test_associativity_variants() {
+        assert_eq!(variants("8-3-2"), Ok(vec![("((8-3)-2)".to_string(), 3), ("(8-(3-2))".to_string(), 7)]));
+        assert_eq!(variants("64/8/2"), Ok(vec![("((64/8)/2)".to_string(), 4), ("(64/(8/2))".to_string(), 16)]));
+        assert_eq!(variants("8/4/8"), Ok(vec![("((8/4)/8)".to_string(), 0)]));
+        assert_eq!(variants("10-4-3-1").map(|all| all.len()), Ok(5));
+        assert_eq!(variants("10-4-3-1").map(|all| all[0].clone()), Ok(("(((10-4)-3)-1)".to_string(), 2)));
+        assert_eq!(variants("5"), Ok(vec![("5".to_string(), 5)]));
+        assert_eq!(variants("1+2*3"), Err(EvalError::NotAChain));
+        assert_eq!(variants("(1+2)+3"), Err(EvalError::NotAChain));
+        assert_eq!(variants("1+2+"), Err(EvalError::MissingOperand));
+        assert_eq!(variants(""), Err(EvalError::EmptyExpression));
+    }
+
+    #[test]
+    fn test_associativity_variants_limit() {
+        let chain = |count: usize| vec!["1"; count].join("+");
+        assert_eq!(variants(&chain(MAX_VARIANT_CHAIN)).map(|all| all.len()), Ok(58786));
+        assert_eq!(variants(&chain(MAX_VARIANT_CHAIN + 1)), Err(EvalError::ChainTooLong(MAX_VARIANT_CHAIN + 1)));
+        assert_eq!(variants(&chain(20)), Err(EvalError::ChainTooLong(20)));
+    }
+
+    #[test]
+    fn test_explicit_form() {
+        let cases = [
+            ("1+2*3", "1+(2*3)"),
+            ("2*3+4", "(2*3)+4"),
+            ("1+2*3^2", "1+(2*(3^2))"),
+            ("1+2-3", "1+2-3"),
+            ("8-(3-2)", "8-(3-2)"),
+            ("2^3^2", "2^3^2"),
+            ("(2^3)^2", "(2^3)^2"),
+            ("(1+2)*3", "(1+2)*3"),
+            ("1+(2*3)", "1+(2*3)"),
+            ("2(3+4)", "2*(3+4)"),
+            ("-2^2", "-(2^2)"),
+            ("clamp(1+2*3, 0, 5)", "clamp(1+(2*3), 0, 5)"),
+            ("1<2+3&1", "(1<(2+3))&1")
+        ];
+        for &(expression, explicit) in &cases {
+            assert_eq!(explicit_form(expression), Ok(explicit.to_string()));
+            assert_eq!(evaluate(explicit), evaluate(expression));
+        }
+        assert_eq!(explicit_form("1+"), Err(EvalError::MissingOperand));
+    }
+}
@@ -0,0 +1,352 @@
+use std::convert::TryFrom;
+
+use {ExpressionAtom, Operations, OperationTokenTree, EvalError, Function};
+use function::{clamp, range_sum, range_product};
+use {try_calculate, validate_parentheses, square_and_multiply};
+use evaluator::{to_rpn, reduce_rpn, Condition};
+
+/// A numeric type that expressions can be calculated over. Only from_u32 is needed to read the
+/// numbers in an expression; the rest are the binary operations and function calls.
+pub trait Field: Sized + Copy + PartialEq {
+    fn from_u32(value: u32) -> Self;
+    fn add(self, rval: Self) -> Result<Self, EvalError>;
+    fn sub(self, rval: Self) -> Result<Self, EvalError>;
+    fn mul(self, rval: Self) -> Result<Self, EvalError>;
+    fn div(self, rval: Self) -> Result<Self, EvalError>;
+    fn pow(self, rval: Self) -> Result<Self, EvalError>;
+
+    /// Reads a floating point number. Fields that only hold whole numbers accept whole reals, like
+    /// 2.0, and report the rest as Inexact.
+    fn from_real(value: f64) -> Result<Self, EvalError> {
+        if value.fract() == 0.0 && value >= 0.0 && value <= f64::from(u32::MAX) {
+            Ok(Self::from_u32(value as u32))
+        } else {
+            Err(EvalError::Inexact)
+        }
+    }
+
+    /// Reads mantissa / 10^scale, like 0.25 as 25 and 2. Fields that only hold whole numbers
+    /// accept decimals that happen to be whole, like 2.0, and report the rest as Inexact.
+    fn from_decimal(mantissa: u32, scale: u32) -> Result<Self, EvalError> {
+        match 10u32.checked_pow(scale) {
+            Some(divisor) if mantissa.is_multiple_of(divisor) => Ok(Self::from_u32(mantissa / divisor)),
+            None if mantissa == 0 => Ok(Self::from_u32(0)),
+            _ => Err(EvalError::Inexact)
+        }
+    }
+
+    /// Calculates two values with the Operations enum. Fields have no truth values, so comparison
+    /// and logical operations are unsupported unless a field overrides this.
+    fn calculate(self, rval: Self, operation: Operations) -> Result<Self, EvalError> {
+        self.calculate_arithmetic(rval, operation)
+    }
+
+    /// Calculates the arithmetic operations, reporting the rest as unsupported
+    fn calculate_arithmetic(self, rval: Self, operation: Operations) -> Result<Self, EvalError> {
+        match operation {
+            Operations::Add => Field::add(self, rval),
+            Operations::Subtract => Field::sub(self, rval),
+            Operations::Multiply => Field::mul(self, rval),
+            Operations::Divide => Field::div(self, rval),
+            Operations::Power => Field::pow(self, rval),
+            _ => Err(EvalError::UnsupportedOperation(operation))
+        }
+    }
+
+    /// Calls a function with its arguments in order. No functions are supported unless a field
+    /// overrides this.
+    fn call(function: Function, _arguments: &[Self]) -> Result<Self, EvalError> {
+        Err(EvalError::UnsupportedFunction(function))
+    }
+}
+
+/// The crate's native numbers. Every operation is checked.
+impl Field for u32 {
+    fn from_u32(value: u32) -> Self { value }
+    fn add(self, rval: Self) -> Result<Self, EvalError> { try_calculate(self, rval, Operations::Add) }
+    fn sub(self, rval: Self) -> Result<Self, EvalError> { try_calculate(self, rval, Operations::Subtract) }
+    fn mul(self, rval: Self) -> Result<Self, EvalError> { try_calculate(self, rval, Operations::Multiply) }
+    fn div(self, rval: Self) -> Result<Self, EvalError> { try_calculate(self, rval, Operations::Divide) }
+    fn pow(self, rval: Self) -> Result<Self, EvalError> { try_calculate(self, rval, Operations::Power) }
+
+    fn calculate(self, rval: Self, operation: Operations) -> Result<Self, EvalError> {
+        try_calculate(self, rval, operation)
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, EvalError> {
+        match function {
+            Function::Clamp => Ok(clamp(arguments[0], arguments[1], arguments[2])),
+            // Whole numbers are already rounded
+            Function::Abs | Function::Floor | Function::Ceil | Function::Round => Ok(arguments[0]),
+            Function::If => Ok(if arguments[0].is_true() { arguments[1] } else { arguments[2] }),
+            Function::Sum => range_sum(i64::from(arguments[0]), i64::from(arguments[1]))
+                .and_then(|sum| u32::try_from(sum).ok()).ok_or(EvalError::Overflow),
+            Function::Product => range_product(i64::from(arguments[0]), i64::from(arguments[1]))
+                .and_then(|product| u32::try_from(product).ok()).ok_or(EvalError::Overflow),
+            Function::Rand | Function::Pct(_) => Err(EvalError::UnsupportedFunction(function))
+        }
+    }
+}
+
+/// Signed numbers, so 3-5 is -2. Every operation is checked, and exponents cannot be negative.
+impl Field for i64 {
+    fn from_u32(value: u32) -> Self { i64::from(value) }
+    fn add(self, rval: Self) -> Result<Self, EvalError> { self.checked_add(rval).ok_or(EvalError::Overflow) }
+    fn sub(self, rval: Self) -> Result<Self, EvalError> { self.checked_sub(rval).ok_or(EvalError::Overflow) }
+    fn mul(self, rval: Self) -> Result<Self, EvalError> { self.checked_mul(rval).ok_or(EvalError::Overflow) }
+    fn div(self, rval: Self) -> Result<Self, EvalError> {
+        if rval == 0 {
+            return Err(EvalError::DivideByZero);
+        }
+        self.checked_div(rval).ok_or(EvalError::Overflow)
+    }
+    fn pow(self, rval: Self) -> Result<Self, EvalError> {
+        if rval < 0 {
+            return Err(EvalError::Inexact);
+        }
+        let exponent = u32::try_from(rval).map_err(|_| EvalError::Overflow)?;
+        self.checked_pow(exponent).ok_or(EvalError::Overflow)
+    }
+
+    fn calculate(self, rval: Self, operation: Operations) -> Result<Self, EvalError> {
+        match operation {
+            Operations::Greater => Ok((self > rval) as i64),
+            Operations::Less => Ok((self < rval) as i64),
+            Operations::Equal => Ok((self == rval) as i64),
+            Operations::And => Ok((self != 0 && rval != 0) as i64),
+            Operations::Or => Ok((self != 0 || rval != 0) as i64),
+            _ => self.calculate_arithmetic(rval, operation)
+        }
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, EvalError> {
+        match function {
+            Function::Clamp => Ok(clamp(arguments[0], arguments[1], arguments[2])),
+            Function::Abs => arguments[0].checked_abs().ok_or(EvalError::Overflow),
+            Function::Floor | Function::Ceil | Function::Round => Ok(arguments[0]),
+            Function::If => Ok(if arguments[0].is_true() { arguments[1] } else { arguments[2] }),
+            Function::Sum => range_sum(arguments[0], arguments[1]).ok_or(EvalError::Overflow),
+            Function::Product => range_product(arguments[0], arguments[1]).ok_or(EvalError::Overflow),
+            Function::Rand | Function::Pct(_) => Err(EvalError::UnsupportedFunction(function))
+        }
+    }
+}
+
+/// Floating point numbers. Nothing fails; division by zero gives infinity.
+impl Field for f64 {
+    fn from_u32(value: u32) -> Self { f64::from(value) }
+    fn from_decimal(mantissa: u32, scale: u32) -> Result<Self, EvalError> {
+        Ok(f64::from(mantissa) / 10f64.powi(scale as i32))
+    }
+    fn from_real(value: f64) -> Result<Self, EvalError> { Ok(value) }
+    fn add(self, rval: Self) -> Result<Self, EvalError> { Ok(self + rval) }
+    fn sub(self, rval: Self) -> Result<Self, EvalError> { Ok(self - rval) }
+    fn mul(self, rval: Self) -> Result<Self, EvalError> { Ok(self * rval) }
+    fn div(self, rval: Self) -> Result<Self, EvalError> { Ok(self / rval) }
+    fn pow(self, rval: Self) -> Result<Self, EvalError> { Ok(self.powf(rval)) }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, EvalError> {
+        match function {
+            Function::Clamp => Ok(clamp(arguments[0], arguments[1], arguments[2])),
+            Function::Abs => Ok(arguments[0].abs()),
+            Function::Floor => Ok(arguments[0].floor()),
+            Function::Ceil => Ok(arguments[0].ceil()),
+            Function::Round => Ok(arguments[0].round()),
+            Function::If => Ok(if arguments[0].is_true() { arguments[1] } else { arguments[2] }),
+            Function::Rand | Function::Sum | Function::Product | Function::Pct(_) => Err(EvalError::UnsupportedFunction(function))
+        }
+    }
+}
+
+/// Integers modulo M. Exponents are whole numbers rather than values modulo M, since reducing one
+/// changes the power: in ModInt<7>, 2^7 is 2 but 2^0 is 1. So an exponent has to be known exactly,
+/// and one worked out by dividing, by going below zero, or past a u32 is Inexact.
+///     In ModInt<7>, 3+5 is 1 and 1/3 is 5
+/// M has to be at least 1; ModInt<0> does not compile.
+#[derive(Copy, Clone)]
+pub struct ModInt<const M: u32> {
+    value: u32,
+
+    // The whole number this stands for, while it is known and fits in a u32
+    exact: Option<u32>
+}
+
+impl<const M: u32> ModInt<M> {
+    // Checked wherever a value is made, so a ModInt<0> fails to compile instead of dividing by zero
+    const NONZERO_MODULUS: () = assert!(M != 0, "ModInt needs a modulus of at least 1");
+
+    /// value modulo M
+    pub fn new(value: u32) -> Self {
+        let () = Self::NONZERO_MODULUS;
+        ModInt { value: value % M, exact: Some(value) }
+    }
+
+    /// The value, between 0 and M-1
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    /// value modulo M, along with the whole number it stands for if that is known
+    fn wrap(value: u64, exact: Option<u32>) -> Self {
+        let () = Self::NONZERO_MODULUS;
+        ModInt { value: (value % u64::from(M)) as u32, exact }
+    }
+
+    /// The multiplicative inverse, which only exists when the value and M share no factors
+    fn inverse(self) -> Option<Self> {
+        let (mut old_r, mut r) = (i64::from(self.value), i64::from(M));
+        let (mut old_s, mut s) = (1i64, 0i64);
+        while r != 0 {
+            let quotient = old_r / r;
+            let next_r = old_r - quotient * r;
+            old_r = r;
+            r = next_r;
+            let next_s = old_s - quotient * s;
+            old_s = s;
+            s = next_s;
+        }
+        if old_r != 1 {
+            return None;
+        }
+        Some(ModInt::wrap(old_s.rem_euclid(i64::from(M)) as u64, None))
+    }
+}
+
+/// Values are equal when they are the same modulo M, however they were worked out
+impl<const M: u32> PartialEq for ModInt<M> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<const M: u32> Eq for ModInt<M> {}
+
+impl<const M: u32> std::fmt::Debug for ModInt<M> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "ModInt({})", self.value)
+    }
+}
+
+impl<const M: u32> Field for ModInt<M> {
+    fn from_u32(value: u32) -> Self { ModInt::new(value) }
+    fn add(self, rval: Self) -> Result<Self, EvalError> {
+        let exact = self.exact.zip(rval.exact).and_then(|(left, right)| left.checked_add(right));
+        Ok(ModInt::wrap(u64::from(self.value) + u64::from(rval.value), exact))
+    }
+    fn sub(self, rval: Self) -> Result<Self, EvalError> {
+        let exact = self.exact.zip(rval.exact).and_then(|(left, right)| left.checked_sub(right));
+        Ok(ModInt::wrap(u64::from(self.value) + u64::from(M) - u64::from(rval.value), exact))
+    }
+    fn mul(self, rval: Self) -> Result<Self, EvalError> {
+        let exact = self.exact.zip(rval.exact).and_then(|(left, right)| left.checked_mul(right));
+        Ok(ModInt::wrap(u64::from(self.value) * u64::from(rval.value), exact))
+    }
+    fn div(self, rval: Self) -> Result<Self, EvalError> {
+        self.mul(rval.inverse().ok_or(EvalError::DivideByZero)?)
+    }
+    fn pow(self, rval: Self) -> Result<Self, EvalError> {
+        let exponent = rval.exact.ok_or(EvalError::Inexact)?;
+        let exact = self.exact.and_then(|base| base.checked_pow(exponent));
+        Ok(ModInt::wrap(u64::from(mod_pow(self.value, exponent, M)), exact))
+    }
+}
+
+/// Calculates base^exp modulo modulus by repeated squaring, so it takes log(exp) multiplications.
+/// Products are worked out in u64, so nothing overflows along the way.
+///     mod_pow(2, 5, 7) is 4, since 32 is 4 modulo 7
+/// Panics if modulus is 0, like the % operator.
+pub fn mod_pow(base: u32, exp: u32, modulus: u32) -> u32 {
+    let modulus = u64::from(modulus);
+    // Both sides are below modulus, so the products fit in a u64 and the multiplication never fails
+    square_and_multiply(u64::from(base) % modulus, u64::from(exp), 1 % modulus, |a, b| Ok(a * b % modulus), None)
+        .unwrap_or(0) as u32
+}
+
+/// Evaluates a token stream that is already in reverse polish notation over any Field
+pub fn evaluate_rpn_in<F: Field>(tokens: &[ExpressionAtom]) -> Result<F, EvalError> {
+    reduce_rpn(tokens, F::from_decimal, F::calculate, F::call)
+}
+
+/// Tokenizes and evaluates an expression with signed numbers, so 3-5 is -2
+pub fn evaluate_signed(expression: &str) -> Result<i64, EvalError> {
+    evaluate_in(expression)
+}
+
+/// Tokenizes and evaluates an expression over any Field.
+///     evaluate_in::<f64>("1/2") is 0.5
+///     evaluate_in::<ModInt<7>>("3+5") is ModInt::new(1)
+pub fn evaluate_in<F: Field>(expression: &str) -> Result<F, EvalError> {
+    validate_parentheses(expression)?;
+    let tokens = OperationTokenTree::try_evaluate_tokens(expression)?;
+    evaluate_rpn_in(&to_rpn(&tokens)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_in_f64() {
+        assert_eq!(evaluate_in::<f64>("1/2+3"), Ok(3.5));
+        assert_eq!(evaluate_in::<f64>("(1+2)*3/4"), Ok(2.25));
+    }
+
+    #[test]
+    fn test_evaluate_in_mod_7() {
+        assert_eq!(evaluate_in::<ModInt<7>>("3+5"), Ok(ModInt::new(1)));
+        assert_eq!(evaluate_in::<ModInt<7>>("1-3"), Ok(ModInt::new(5)));
+        assert_eq!(evaluate_in::<ModInt<7>>("1/3"), Ok(ModInt::new(5)));
+        assert_eq!(evaluate_in::<ModInt<7>>("2^5"), Ok(ModInt::new(4)));
+        assert_eq!(evaluate_in::<ModInt<7>>("1/7"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_in::<ModInt<7>>("3+5").map(|value| value.value()), Ok(1));
+    }
+
+    #[test]
+    fn test_mod_int_exponents_are_exact() {
+        let naive = |base: u64, exp: u64| (0..exp).fold(1u64, |result, _| result * base % 7) as u32;
+        for base in 0..10u64 {
+            for exp in 0..40u64 {
+                assert_eq!(evaluate_in::<ModInt<7>>(&format!("{}^{}", base, exp)), Ok(ModInt::new(naive(base, exp))),
+                    "{}^{} mod 7", base, exp);
+            }
+        }
+        assert_eq!(evaluate_in::<ModInt<7>>("2^7"), Ok(ModInt::new(2)));
+        assert_eq!(evaluate_in::<ModInt<7>>("2^8"), Ok(ModInt::new(4)));
+        assert_eq!(evaluate_in::<ModInt<7>>("2^(3+4)"), Ok(ModInt::new(2)));
+        assert_eq!(evaluate_in::<ModInt<7>>("2^(2*7)"), Ok(ModInt::new(4)));
+        assert_eq!(evaluate_in::<ModInt<7>>("3^2^2"), Ok(ModInt::new(4)));
+
+        assert_eq!(evaluate_in::<ModInt<7>>("2^(1-3)"), Err(EvalError::Inexact));
+        assert_eq!(evaluate_in::<ModInt<7>>("2^(1/3)"), Err(EvalError::Inexact));
+        assert_eq!(evaluate_in::<ModInt<7>>("2^(65536*65536)"), Err(EvalError::Inexact));
+    }
+
+    #[test]
+    fn test_mod_pow() {
+        let naive = |base: u32, exp: u32, modulus: u32| (0..exp)
+            .fold(1 % u64::from(modulus), |result, _| result * u64::from(base) % u64::from(modulus)) as u32;
+        for &modulus in &[1, 2, 7, 10, 1000, 65521, 4294967291, u32::MAX] {
+            for &base in &[0, 1, 2, 3, 10, 65535, 4294967290, u32::MAX] {
+                for &exp in &[0, 1, 2, 3, 17, 100, 1023] {
+                    assert_eq!(mod_pow(base, exp, modulus), naive(base, exp, modulus), "{}^{} mod {}", base, exp, modulus);
+                }
+            }
+        }
+        assert_eq!(mod_pow(2, 5, 7), 4);
+        assert_eq!(mod_pow(3, u32::MAX, 1000000007), 648563713);
+        assert_eq!(evaluate_in::<ModInt<1000000007>>("3^1000000000"), Ok(ModInt::new(mod_pow(3, 1000000000, 1000000007))));
+    }
+
+    #[test]
+    fn test_evaluate_signed_negation() {
+        assert_eq!(evaluate_signed("-5"), Ok(-5));
+        assert_eq!(evaluate_signed("-2^2"), Ok(-4));
+        assert_eq!(evaluate_signed("3*-(1-4)"), Ok(9));
+    }
+
+    #[test]
+    fn test_evaluate_in_unsupported() {
+        assert_eq!(evaluate_in::<f64>("2>1"), Err(EvalError::UnsupportedOperation(Operations::Greater)));
+        assert_eq!(evaluate_in::<u32>("2>1"), Ok(1));
+    }
+}
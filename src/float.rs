@@ -0,0 +1,201 @@
+use std::fmt::{Display};
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, OperationTokenTree, EvalError, TokenizeError, Field, Tokenizer};
+use evaluator::{to_rpn, reduce_rpn, reduce_rpn_with};
+
+/// How evaluate_float_with treats the edges of floating point arithmetic
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FloatMode {
+    /// Whether dividing by zero gives inf, as it does in evaluate_float, rather than DivideByZero
+    pub div_by_zero_is_inf: bool
+}
+
+impl Default for FloatMode {
+    fn default() -> FloatMode {
+        FloatMode { div_by_zero_is_inf: true }
+    }
+}
+
+/// Tokenizes and evaluates an expression with floating point numbers, so 1/2 is 0.5. Powers can
+/// be fractional too, so 4^0.5 is the square root of 4.
+pub fn evaluate_float(expression: &str) -> Result<f64, EvalError> {
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    reduce_rpn(&rpn, f64::from_decimal, f64::calculate, f64::call)
+}
+
+/// The letter inf is read as while evaluate_float_with tokenizes. A CJK ideograph, three bytes
+/// like inf itself, which nobody writes as a variable by accident.
+const INFINITY_PLACEHOLDER: char = '\u{5927}';
+
+/// Evaluates with floating point numbers like evaluate_float, but also understands the constant inf.
+/// An operation whose result is not a number, like inf-inf or 0/0, is NotANumber.
+///     "1/inf" is 0, "2inf" is inf
+pub fn evaluate_float_with(expression: &str, mode: FloatMode) -> Result<f64, EvalError> {
+    // Each inf is swapped for a letter the tokenizer reads as a variable, which is the same length
+    // so positions in errors still fit the expression
+    let mut placeholder = String::with_capacity(expression.len());
+    let mut rest = expression;
+    while let Some(start) = rest.find(char::is_alphabetic) {
+        let length = rest[start..].find(|c: char| !c.is_alphabetic()).unwrap_or(rest.len() - start);
+        placeholder.push_str(&rest[..start]);
+        if &rest[start..start + length] == "inf" {
+            placeholder.push(INFINITY_PLACEHOLDER);
+        } else {
+            placeholder.push_str(&rest[start..start + length]);
+        }
+        rest = &rest[start + length..];
+    }
+    placeholder.push_str(rest);
+
+    let tokens = Tokenizer::new(&placeholder).collect::<Result<Vec<ExpressionAtom>, TokenizeError>>()?;
+    let rpn = to_rpn(&tokens)?;
+    reduce_rpn_with(&rpn, f64::from_decimal, |left, right, op| {
+        if op == Operations::Divide && right == 0.0 && !mode.div_by_zero_is_inf {
+            return Err(EvalError::DivideByZero);
+        }
+        match left.calculate(right, op)? {
+            result if result.is_nan() => Err(EvalError::NotANumber),
+            result => Ok(result)
+        }
+    }, f64::call, |symbol, _, _| Err(EvalError::UnknownOperator(symbol)), |name| match name {
+        INFINITY_PLACEHOLDER => Ok(f64::INFINITY),
+        _ => Err(EvalError::UnboundVariable(name))
+    })
+}
+
+/// Rounds to the given number of decimal places, sending exact halves to the even neighbour.
+///     0.125 to 2 places is 0.12, 0.135 is 0.14
+pub fn round_half_even(value: f64, decimals: u32) -> f64 {
+    let factor = 10f64.powi(decimals as i32);
+    (value * factor).round_ties_even() / factor
+}
+
+/// Evaluates with floating point numbers, rounding the result of every division half-to-even to
+/// the given number of decimal places before it is used further. Other operations are not rounded.
+///     "1/3*3" to 2 places is 0.99, since 1/3 becomes 0.33 first
+pub fn evaluate_banker_rounded(expression: &str, decimals: u32) -> Result<f64, EvalError> {
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    reduce_rpn(&rpn, f64::from_decimal, |left, right, op| {
+        let result = left.calculate(right, op)?;
+        match op {
+            Operations::Divide => Ok(round_half_even(result, decimals)),
+            _ => Ok(result)
+        }
+    }, f64::call)
+}
+
+/// A result that is exact while it is small enough, and floating point once it is not
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Adaptive {
+
+    /// e.g: 1+1
+    Integer(u32),
+
+    /// e.g: 100000*100000, past the threshold
+    Float(f64)
+}
+
+impl Display for Adaptive {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Adaptive::Integer(value) => write!(f, "{}", value),
+            Adaptive::Float(value) => write!(f, "{}", value)
+        }
+    }
+}
+
+/// Evaluates with floating point numbers once, then gives the result as a whole number when it is
+/// one and no larger than int_threshold. Everything else, like a fraction, a result below zero, or
+/// one past the threshold, stays floating point. Dividing by zero is an error rather than inf.
+///     "2*3" under 1000 is Integer(6), "7/2" is Float(3.5), "100000*100000" is Float(10000000000)
+pub fn evaluate_adaptive(expression: &str, int_threshold: u32) -> Result<Adaptive, EvalError> {
+    let value = evaluate_float_with(expression, FloatMode { div_by_zero_is_inf: false })?;
+    if value.fract() == 0.0 && value >= 0.0 && value <= f64::from(int_threshold) {
+        Ok(Adaptive::Integer(value as u32))
+    } else {
+        Ok(Adaptive::Float(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_half_even() {
+        assert_eq!(round_half_even(2.5, 0), 2.0);
+        assert_eq!(round_half_even(3.5, 0), 4.0);
+        assert_eq!(round_half_even(0.125, 2), 0.12);
+        assert_eq!(round_half_even(0.375, 2), 0.38);
+    }
+
+    #[test]
+    fn test_float_fractional_powers() {
+        assert_eq!(evaluate_float("4^0.5"), Ok(2.0));
+        assert!((evaluate_float("8^(1/3)").unwrap() - 2.0).abs() < 1e-12);
+        assert_eq!(evaluate_float("2^-1"), Ok(0.5));
+        assert_eq!(evaluate_float("9^.5*2"), Ok(6.0));
+        assert_eq!(evaluate_float_with("4^0.5", FloatMode::default()), Ok(2.0));
+        assert_eq!(evaluate_float_with("(-8)^(1/3)", FloatMode::default()), Err(EvalError::NotANumber));
+    }
+
+    #[test]
+    fn test_float_mode_division_by_zero() {
+        let inf = FloatMode { div_by_zero_is_inf: true };
+        let strict = FloatMode { div_by_zero_is_inf: false };
+        assert_eq!(evaluate_float_with("1/0", inf), Ok(f64::INFINITY));
+        assert_eq!(evaluate_float_with("1/0", strict), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_float_with("1/2", strict), Ok(0.5));
+        assert_eq!(evaluate_float_with("0/0", inf), Err(EvalError::NotANumber));
+        assert_eq!(evaluate_float("1/0"), Ok(f64::INFINITY));
+    }
+
+    #[test]
+    fn test_float_inf_constant() {
+        let mode = FloatMode::default();
+        assert_eq!(evaluate_float_with("inf", mode), Ok(f64::INFINITY));
+        assert_eq!(evaluate_float_with("inf+1", mode), Ok(f64::INFINITY));
+        assert_eq!(evaluate_float_with("2inf", mode), Ok(f64::INFINITY));
+        assert_eq!(evaluate_float_with("-inf", mode), Ok(f64::NEG_INFINITY));
+        assert_eq!(evaluate_float_with("1/inf + 0.5", mode), Ok(0.5));
+        assert_eq!(evaluate_float_with("inf-inf", mode), Err(EvalError::NotANumber));
+        assert_eq!(evaluate_float_with("clamp(inf, 0, 10)", mode), Ok(10.0));
+        assert!(evaluate_float_with("infinity", mode).is_err());
+
+        assert_eq!(evaluate_float_with("if(0, 1, inf)", mode), Ok(f64::INFINITY));
+        assert_eq!(evaluate_float_with("if(0, 5, 2)+inf", mode), Ok(f64::INFINITY));
+        assert_eq!(evaluate_float_with("if(0, inf, 5)+2", mode), Ok(7.0));
+        assert_eq!(evaluate_float_with("if(inf, 3, inf)", mode), Ok(3.0));
+        assert_eq!(evaluate_float_with("inf+x", mode), Err(EvalError::UnboundVariable('x')));
+    }
+
+    #[test]
+    fn test_evaluate_banker_rounded() {
+        assert_eq!(evaluate_float("1/3*3"), Ok(1.0));
+        assert!((evaluate_banker_rounded("1/3*3", 2).unwrap() - 0.99).abs() < 1e-9);
+        assert_eq!(evaluate_banker_rounded("5/2", 0), Ok(2.0));
+        assert_eq!(evaluate_banker_rounded("7/2", 0), Ok(4.0));
+        assert_eq!(evaluate_banker_rounded("1/8/2", 2), Ok(0.06));
+        assert_eq!(evaluate_banker_rounded("1/8+1/8", 2), Ok(0.24));
+    }
+
+    #[test]
+    fn test_evaluate_adaptive() {
+        assert_eq!(evaluate_adaptive("2*3", 1000), Ok(Adaptive::Integer(6)));
+        assert_eq!(evaluate_adaptive("1000", 1000), Ok(Adaptive::Integer(1000)));
+        assert_eq!(evaluate_adaptive("999+2", 1000), Ok(Adaptive::Float(1001.0)));
+        assert_eq!(evaluate_adaptive("100000*100000", u32::MAX), Ok(Adaptive::Float(1e10)));
+        assert_eq!(evaluate_adaptive("1/0", 1000), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_adaptive("2^40", 1000).map(|result| result.to_string()), Ok("1099511627776".to_string()));
+    }
+
+    #[test]
+    fn test_evaluate_adaptive_does_not_depend_on_threshold_for_its_value() {
+        assert_eq!(evaluate_adaptive("7/2*1000", 1000), Ok(Adaptive::Float(3500.0)));
+        assert_eq!(evaluate_adaptive("7/2*1000", 5000), Ok(Adaptive::Integer(3500)));
+        assert_eq!(evaluate_adaptive("7/2", 5000), Ok(Adaptive::Float(3.5)));
+        assert_eq!(evaluate_adaptive("1-2", 5000), Ok(Adaptive::Float(-1.0)));
+        assert_eq!(evaluate_adaptive("0/0", 5000), Err(EvalError::DivideByZero));
+    }
+}
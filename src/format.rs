@@ -0,0 +1,186 @@
+use std::char;
+use std::vec::Vec;
+
+use {Expr, Operations, EvalError, evaluate, parse_expression, turn_into_character, try_calculate};
+
+/// Writes a number in any base from 2 to 36, using lowercase letters past 9.
+///     255 in base 16 is "ff"
+pub fn to_base(value: u32, radix: u32) -> Result<String, EvalError> {
+    if !(2..=36).contains(&radix) {
+        return Err(EvalError::InvalidRadix(radix));
+    }
+    if value == 0 {
+        return Ok("0".to_string());
+    }
+
+    let mut digits: Vec<char> = vec![];
+    let mut remaining = value;
+    while remaining > 0 {
+        digits.push(char::from_digit(remaining % radix, radix).unwrap_or('?'));
+        remaining /= radix;
+    }
+    Ok(digits.iter().rev().collect())
+}
+
+/// Evaluates an expression and writes the result in any base from 2 to 36.
+///     "255" in base 16 is "ff"
+pub fn evaluate_to_base(expression: &str, radix: u32) -> Result<String, EvalError> {
+    if !(2..=36).contains(&radix) {
+        return Err(EvalError::InvalidRadix(radix));
+    }
+    to_base(evaluate(expression)?, radix)
+}
+
+/// Writes a number with its digits grouped in threes by commas.
+///     1234567 is "1,234,567"
+pub fn format_result_grouped(value: u32) -> String {
+    let digits = value.to_string();
+    let mut grouped = String::new();
+    for (index, digit) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(3) {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
+}
+
+/// Writes a number in at most width characters, for fixed width displays. Numbers that are too long
+/// are abbreviated to thousands (K), millions (M), or billions (B), with as many decimals as fit.
+/// If even that is too long, the display is filled with #.
+///     1234 in 6 is "1234", 1200000 in 6 is "1.2M", 1234567 in 4 is "1.2M"
+pub fn format_fit(value: u32, width: usize) -> String {
+    let digits = value.to_string();
+    if digits.len() <= width {
+        return digits;
+    }
+
+    for (exponent, suffix) in [(3, 'K'), (6, 'M'), (9, 'B')] {
+        let scaled = f64::from(value) / 10f64.powi(exponent);
+        for decimals in (0..=3).rev() {
+            let mut text = format!("{:.*}", decimals, scaled);
+            // A number that rounds to 1000 belongs to the next suffix, so 999999 is 1M rather than
+            // 1000K, and one that rounds to 0 belongs to the one before, so 12345 is never 0.012M
+            match text.parse::<f64>() {
+                Ok(rounded) if rounded >= 1000.0 => break,
+                Ok(rounded) if rounded < 1.0 => continue,
+                _ => {}
+            }
+            if text.contains('.') {
+                text = text.trim_end_matches('0').trim_end_matches('.').to_string();
+            }
+            text.push(suffix);
+            if text.len() <= width {
+                return text;
+            }
+        }
+    }
+
+    "#".repeat(width)
+}
+
+/// Evaluates an expression and writes the result with its digits grouped by commas.
+///     "1000*1000" is "1,000,000"
+pub fn evaluate_grouped(expression: &str) -> Result<String, EvalError> {
+    evaluate(expression).map(format_result_grouped)
+}
+
+/// Lays out a chain of additions and subtractions the way it would be worked by hand: one term per
+/// line with its operation in front, the digits right aligned, then a rule and the total. Terms that
+/// are not plain numbers, like 2*3, are written as their value.
+///     "123+45+6" is
+///       123
+///     +  45
+///     +   6
+///     -----
+///       174
+pub fn format_vertical(expression: &str) -> Result<String, EvalError> {
+    let mut terms: Vec<(Option<Operations>, &Expr)> = vec![];
+    let parsed = parse_expression(expression)?;
+    let mut rest = &parsed;
+    while let Expr::Binary(op @ Operations::Add, ref left, ref right)
+        | Expr::Binary(op @ Operations::Subtract, ref left, ref right) = *rest {
+        terms.push((Some(op), right));
+        rest = left;
+    }
+    if terms.is_empty() {
+        return Err(EvalError::NotAChain);
+    }
+    terms.push((None, rest));
+    terms.reverse();
+
+    let mut values: Vec<(Option<Operations>, u32)> = vec![];
+    let mut total: u32 = 0;
+    for (op, term) in terms {
+        let value = term.evaluate_in::<u32>()?;
+        total = match op {
+            Some(op) => try_calculate(total, value, op)?,
+            None => value
+        };
+        values.push((op, value));
+    }
+
+    let width = values.iter().map(|&(_, value)| value).chain(Some(total))
+        .map(|value| value.to_string().len()).max().unwrap_or(1);
+    let mut lines: Vec<String> = values.iter().map(|&(op, value)|
+        format!("{} {:>width$}", op.map(turn_into_character).unwrap_or(' '), value, width = width)).collect();
+    lines.push("-".repeat(width + 2));
+    lines.push(format!("  {:>width$}", total, width = width));
+    Ok(lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_result_grouped() {
+        assert_eq!(format_result_grouped(0), "0");
+        assert_eq!(format_result_grouped(7), "7");
+        assert_eq!(format_result_grouped(999), "999");
+        assert_eq!(format_result_grouped(1000), "1,000");
+        assert_eq!(format_result_grouped(65536), "65,536");
+        assert_eq!(format_result_grouped(1234567), "1,234,567");
+        assert_eq!(format_result_grouped(u32::MAX), "4,294,967,295");
+        assert_eq!(evaluate_grouped("1000*1000"), Ok("1,000,000".to_string()));
+        assert_eq!(evaluate_grouped("1/0"), Err(EvalError::DivideByZero));
+    }
+
+    #[test]
+    fn test_format_vertical() {
+        assert_eq!(format_vertical("123+45+6"), Ok("  123\n+  45\n+   6\n-----\n  174".to_string()));
+        assert_eq!(format_vertical("999+1"), Ok("   999\n+    1\n------\n  1000".to_string()));
+        assert_eq!(format_vertical("50-2*3+1"), Ok("  50\n-  6\n+  1\n----\n  45".to_string()));
+        assert_eq!(format_vertical("10-(2+3)"), Ok("  10\n-  5\n----\n   5".to_string()));
+        assert_eq!(format_vertical("2*3"), Err(EvalError::NotAChain));
+        assert_eq!(format_vertical("1-2+3"), Err(EvalError::Underflow));
+        assert_eq!(format_vertical("1+2+3").map(|_| ()), evaluate("1+2+3").map(|_| ()));
+    }
+
+    #[test]
+    fn test_format_fit() {
+        assert_eq!(format_fit(42, 6), "42");
+        assert_eq!(format_fit(123456, 6), "123456");
+        assert_eq!(format_fit(1234567, 6), "1.235M");
+        assert_eq!(format_fit(1200000, 6), "1.2M");
+        assert_eq!(format_fit(1234567, 4), "1.2M");
+        assert_eq!(format_fit(999999, 5), "1M");
+        assert_eq!(format_fit(1234567, 6).len(), 6);
+        assert_eq!(format_fit(u32::MAX, 6), "4.295B");
+        assert_eq!(format_fit(u32::MAX, 3), "4B");
+        assert_eq!(format_fit(12345, 3), "12K");
+        assert_eq!(format_fit(12345, 2), "##");
+        assert_eq!(format_fit(5, 0), "");
+    }
+
+    #[test]
+    fn test_evaluate_to_base() {
+        assert_eq!(evaluate_to_base("255", 16), Ok("ff".to_string()));
+        assert_eq!(evaluate_to_base("2+3", 2), Ok("101".to_string()));
+        assert_eq!(evaluate_to_base("0", 2), Ok("0".to_string()));
+        assert_eq!(evaluate_to_base("35", 36), Ok("z".to_string()));
+        assert_eq!(evaluate_to_base("10", 10), Ok("10".to_string()));
+        assert_eq!(evaluate_to_base("10", 1), Err(EvalError::InvalidRadix(1)));
+        assert_eq!(evaluate_to_base("10", 37), Err(EvalError::InvalidRadix(37)));
+    }
+}
@@ -0,0 +1,214 @@
+use std::convert::TryFrom;
+use std::fmt::{Display};
+
+/// Named functions that can be called in an expression, like clamp(15, 0, 10)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Function {
+
+    /// clamp(value, lo, hi)
+    Clamp,
+
+    /// abs(value)
+    Abs,
+
+    /// floor(value), rounding down
+    Floor,
+
+    /// ceil(value), rounding up
+    Ceil,
+
+    /// round(value), rounding halves away from zero
+    Round,
+
+    /// rand(lo, hi), a pseudo-random whole number from lo to hi. Needs a seed; see evaluate_seeded.
+    Rand,
+
+    /// if(condition, then, else), then when the condition is not zero and else when it is. Only
+    /// the branch that is picked is evaluated.
+    If,
+
+    /// sum(start, end), the sum of the whole numbers from start to end
+    Sum,
+
+    /// product(start, end), the product of the whole numbers from start to end
+    Product,
+
+    /// pct(a, b, ...), each argument as a whole percentage of their total. Takes any number of
+    /// arguments, and holds how many it was called with.
+    Pct(usize)
+}
+
+impl Function {
+    /// Every function, for processing them all without listing them by hand
+    pub fn all() -> &'static [Function] {
+        &[Function::Clamp, Function::Abs, Function::Floor, Function::Ceil, Function::Round, Function::Rand, Function::If,
+          Function::Sum, Function::Product, Function::Pct(1)]
+    }
+
+    /// The name the function is called by in an expression
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Function::Clamp => "clamp",
+            Function::Abs => "abs",
+            Function::Floor => "floor",
+            Function::Ceil => "ceil",
+            Function::Round => "round",
+            Function::Rand => "rand",
+            Function::If => "if",
+            Function::Sum => "sum",
+            Function::Product => "product",
+            Function::Pct(_) => "pct"
+        }
+    }
+
+    /// How many arguments the function takes
+    pub fn arity(&self) -> usize {
+        match *self {
+            Function::Clamp | Function::If => 3,
+            Function::Rand | Function::Sum | Function::Product => 2,
+            Function::Abs | Function::Floor | Function::Ceil | Function::Round => 1,
+            Function::Pct(arguments) => arguments
+        }
+    }
+
+    /// Whether the function always gives the same result for the same arguments. Only rand does not.
+    pub fn is_pure(&self) -> bool {
+        *self != Function::Rand
+    }
+
+    /// The function as called with a number of arguments. Only pct takes any number of them; every
+    /// other function keeps its own arity, and is checked against it.
+    ///     pct called with 3 arguments is Pct(3)
+    pub fn with_arguments(self, arguments: usize) -> Function {
+        match self {
+            Function::Pct(_) => Function::Pct(arguments),
+            _ => self
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Given the name of a function, turn it into the Function enum.
+pub fn turn_into_function(name: &str) -> Option<Function> {
+    match name {
+        "clamp" => Some(Function::Clamp),
+        "abs" => Some(Function::Abs),
+        "floor" => Some(Function::Floor),
+        "ceil" => Some(Function::Ceil),
+        "round" => Some(Function::Round),
+        "rand" => Some(Function::Rand),
+        "if" => Some(Function::If),
+        "sum" => Some(Function::Sum),
+        "product" => Some(Function::Product),
+        "pct" => Some(Function::Pct(1)),
+        _ => None
+    }
+}
+
+/// Limits a value to the range lo..=hi. If lo is above hi, hi wins.
+pub fn clamp<T: PartialOrd>(value: T, lo: T, hi: T) -> T {
+    let raised = if value < lo { lo } else { value };
+    if raised > hi { hi } else { raised }
+}
+
+/// The sum of the whole numbers from start to end, or None if it does not fit. A range that starts
+/// past its end is empty, and sums to 0.
+///     1 to 100 sums to 5050
+pub(crate) fn range_sum(start: i64, end: i64) -> Option<i64> {
+    if start > end {
+        return Some(0);
+    }
+    let (start, end) = (i128::from(start), i128::from(end));
+    (start + end).checked_mul(end - start + 1).and_then(|doubled| i64::try_from(doubled / 2).ok())
+}
+
+/// The product of the whole numbers from start to end, or None if it does not fit. A range that
+/// starts past its end is empty, and multiplies to 1.
+///     1 to 5 multiplies to 120
+pub(crate) fn range_product(start: i64, end: i64) -> Option<i64> {
+    if start > end {
+        return Some(1);
+    }
+    // Without a zero, every number but 1 and -1 at least doubles the product, so overflow comes
+    // within a few dozen steps however long the range is
+    if start <= 0 && end >= 0 {
+        return Some(0);
+    }
+    (start..=end).try_fold(1i64, |product, number| product.checked_mul(number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {evaluate, evaluate_in, evaluate_signed, evaluate_float, EvalError};
+
+    #[test]
+    fn test_clamp() {
+        assert_eq!(evaluate("clamp(15, 0, 10)"), Ok(10));
+        assert_eq!(evaluate("clamp(5, 0, 10)"), Ok(5));
+        assert_eq!(evaluate("clamp(1, 3, 10)"), Ok(3));
+        assert_eq!(evaluate("clamp(2*8, 1+1, 3*3)+1"), Ok(10));
+        assert_eq!(evaluate("1+clamp(clamp(7,0,5), 0, 3)"), Ok(4));
+        assert_eq!(evaluate_in::<f64>("clamp(1/2, 1, 2)"), Ok(1.0));
+    }
+
+    #[test]
+    fn test_abs() {
+        assert_eq!(evaluate_signed("abs(3-5)"), Ok(2));
+        assert_eq!(evaluate_signed("abs(5-3)"), Ok(2));
+        assert_eq!(evaluate_signed("abs(0)"), Ok(0));
+        assert_eq!(evaluate_signed("abs(2*(1-4))+1"), Ok(7));
+        assert_eq!(evaluate_signed("abs(abs(1-9)-10)"), Ok(2));
+        assert_eq!(evaluate("abs(7)"), Ok(7));
+        assert_eq!(evaluate_in::<f64>("abs(1/4-1)"), Ok(0.75));
+    }
+
+    #[test]
+    fn test_rounding_functions() {
+        assert_eq!(evaluate_float("floor(2.7)"), Ok(2.0));
+        assert_eq!(evaluate_float("floor(3)"), Ok(3.0));
+        assert_eq!(evaluate_float("floor(0.999)"), Ok(0.0));
+        assert_eq!(evaluate_float("ceil(2.1)"), Ok(3.0));
+        assert_eq!(evaluate_float("ceil(3)"), Ok(3.0));
+        assert_eq!(evaluate_float("ceil(0.001)"), Ok(1.0));
+        assert_eq!(evaluate_float("round(2.5)"), Ok(3.0));
+        assert_eq!(evaluate_float("round(2.49)"), Ok(2.0));
+        assert_eq!(evaluate_float("round(0-2.5)"), Ok(-3.0));
+        assert_eq!(evaluate_float("floor(7/2)+ceil(7/2)"), Ok(7.0));
+        assert_eq!(evaluate("round(7)"), Ok(7));
+        assert_eq!(evaluate_signed("floor(2-9)"), Ok(-7));
+    }
+
+    #[test]
+    fn test_clamp_argument_count() {
+        assert_eq!(evaluate("clamp(1, 2)"),
+            Err(EvalError::WrongArgumentCount { function: Function::Clamp, expected: 3, found: 2 }));
+        assert_eq!(evaluate("5+clamp(1, 2)"),
+            Err(EvalError::WrongArgumentCount { function: Function::Clamp, expected: 3, found: 2 }));
+        assert_eq!(evaluate("(1, 2)"), Err(EvalError::MissingOperator));
+        assert_eq!(evaluate("clamp 1"), Err(EvalError::MismatchedParentheses));
+    }
+
+    #[test]
+    fn test_range_functions() {
+        assert_eq!(evaluate("sum(1, 100)"), Ok(5050));
+        assert_eq!(evaluate("product(1, 5)"), Ok(120));
+        assert_eq!(evaluate("sum(5, 5)+product(3, 4)"), Ok(17));
+        assert_eq!(evaluate("sum(2*2, 1+5)"), Ok(15));
+        assert_eq!(evaluate("sum(5, 1)"), Ok(0));
+        assert_eq!(evaluate("product(5, 1)"), Ok(1));
+        assert_eq!(evaluate("product(0, 4000000000)"), Ok(0));
+        assert_eq!(evaluate("sum(1, 4000000000)"), Err(EvalError::Overflow));
+        assert_eq!(evaluate("product(1, 13)"), Err(EvalError::Overflow));
+        assert_eq!(evaluate_signed("sum(0-3, 2)"), Ok(-3));
+        assert_eq!(evaluate_signed("product(0-3, 0-1)"), Ok(-6));
+        assert_eq!(evaluate("sum(1)"),
+            Err(EvalError::WrongArgumentCount { function: Function::Sum, expected: 2, found: 1 }));
+        assert_eq!(evaluate_float("sum(1, 2)"), Err(EvalError::UnsupportedFunction(Function::Sum)));
+    }
+}
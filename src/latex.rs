@@ -0,0 +1,81 @@
+use {Expr, Operations, Function, EvalError, parse_expression, precedence, is_right_associative};
+
+/// Writes an expression as LaTeX math, for documentation.
+///     "1/2" is \frac{1}{2}
+///     "(1+2)*3" is (1 + 2) \cdot 3
+pub fn to_latex(expression: &str) -> Result<String, EvalError> {
+    Ok(render(&parse_expression(expression)?))
+}
+
+fn render(expr: &Expr) -> String {
+    match *expr {
+        Expr::Number(value) => value.to_string(),
+        Expr::Real(value) => value.to_string(),
+        Expr::Var(name) => name.to_string(),
+        Expr::Binary(Operations::Divide, ref left, ref right) => format!("\\frac{{{}}}{{{}}}", render(left), render(right)),
+        Expr::Binary(Operations::Power, ref left, ref right) => {
+            let base = match **left {
+                Expr::Binary(..) | Expr::Neg(_) => format!("({})", render(left)),
+                _ => render(left)
+            };
+            format!("{}^{{{}}}", base, render(right))
+        },
+        Expr::Binary(op, ref left, ref right) => format!("{} {} {}",
+            render_operand(left, op, is_right_associative(op)),
+            latex_symbol(op),
+            render_operand(right, op, !is_right_associative(op))),
+        Expr::Call(Function::Abs, ref arguments) if arguments.len() == 1 =>
+            format!("\\left|{}\\right|", render(&arguments[0])),
+        Expr::Call(function, ref arguments) => format!("\\operatorname{{{}}}({})", function.name(),
+            arguments.iter().map(render).collect::<Vec<String>>().join(", ")),
+        Expr::Neg(ref inner) => match **inner {
+            Expr::Binary(Operations::Power, ..) | Expr::Binary(Operations::Divide, ..) => format!("-{}", render(inner)),
+            Expr::Binary(..) | Expr::Neg(_) => format!("-({})", render(inner)),
+            _ => format!("-{}", render(inner))
+        }
+    }
+}
+
+/// Renders one side of an infix operation, parenthesized if it would otherwise be read wrongly.
+/// A side with the same precedence only needs parentheses on the side the operation does not group from.
+fn render_operand(operand: &Expr, parent: Operations, groups_away: bool) -> String {
+    match *operand {
+        Expr::Binary(op, ..) if op != Operations::Divide && op != Operations::Power
+            && (precedence(op) < precedence(parent) || (precedence(op) == precedence(parent) && groups_away)) =>
+            format!("({})", render(operand)),
+        _ => render(operand)
+    }
+}
+
+fn latex_symbol(operation: Operations) -> &'static str {
+    match operation {
+        Operations::Add => "+",
+        Operations::Subtract => "-",
+        Operations::Multiply => "\\cdot",
+        Operations::Divide => "\\div",
+        Operations::Power => "^",
+        Operations::Greater => ">",
+        Operations::Less => "<",
+        Operations::Equal => "=",
+        Operations::And => "\\land",
+        Operations::Or => "\\lor"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_latex() {
+        assert_eq!(to_latex("1/2"), Ok("\\frac{1}{2}".to_string()));
+        assert_eq!(to_latex("2^3"), Ok("2^{3}".to_string()));
+        assert_eq!(to_latex("(1+2)*3"), Ok("(1 + 2) \\cdot 3".to_string()));
+        assert_eq!(to_latex("1-(2-3)+4"), Ok("1 - (2 - 3) + 4".to_string()));
+        assert_eq!(to_latex("(2^3)^2"), Ok("(2^{3})^{2}".to_string()));
+        assert_eq!(to_latex("(1+2)/(3*4)"), Ok("\\frac{1 + 2}{3 \\cdot 4}".to_string()));
+        assert_eq!(to_latex("-(1+2)*abs(0.5)"), Ok("-(1 + 2) \\cdot \\left|0.5\\right|".to_string()));
+        assert_eq!(to_latex("clamp(15, 0, 10)"), Ok("\\operatorname{clamp}(15, 0, 10)".to_string()));
+        assert_eq!(to_latex("1+"), Err(EvalError::MissingOperand));
+    }
+}
@@ -1,8 +1,97 @@
+use std::convert::TryFrom;
 use std::fmt::{Display};
+use std::str::FromStr;
 use std::vec::Vec;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+
+#[cfg(feature = "bigint")]
+extern crate num_bigint;
+
+mod ast;
+#[cfg(feature = "bigint")]
+mod big;
+mod custom_operator;
+mod dag;
+mod diagnostics;
+mod decimal;
+mod encoding;
+mod error;
+mod evaluator;
+mod explain;
+mod explicit;
+mod field;
+mod float;
+mod format;
+mod function;
+mod latex;
+mod matrix;
+mod operator_table;
+mod options;
+#[cfg(test)]
+mod panic_audit;
+mod preprocess;
+mod random;
+mod rational;
+mod rewrite;
+mod session;
+mod simplify;
+mod stream;
+mod strict;
+mod tokenizer;
+mod tokens;
+mod units;
+mod value;
+
+pub use ast::{Expr, EvalTree, parse, parse_expression, root_operator, trivial_value, structurally_equal};
+#[cfg(feature = "bigint")]
+pub use big::{calculate_big, evaluate_big};
+#[cfg(feature = "bigint")]
+pub use num_bigint::BigUint;
+pub use custom_operator::Evaluator;
+pub use dag::{Dag, DagNode};
+pub use decimal::{Decimal, calculate_decimal, evaluate_decimal};
+pub use encoding::{tokens_to_bytes, tokens_from_bytes};
+pub use diagnostics::{Diagnostic, Warning, suggest, evaluate_diagnostics, evaluate_warned};
+pub use error::{EvalError, TokenizeError, ParseAtomError, StrictError, TableError, DecodeError};
+pub use error::{ParseError, CalcError, StackError};
+pub use evaluator::{evaluate, evaluate_rpn, to_rpn, to_rpn_with, debug_rpn, reduction_step_count, evaluate_oneshot};
+pub use evaluator::{evaluate_cancellable, evaluate_with_stack_trace, evaluate_tape, is_valid_rpn};
+pub use evaluator::{evaluate_with_hook, evaluate_with_depth_report, evaluate_rpn_str, evaluate_calc, evaluate_reduce};
+pub use evaluator::{compare_expressions, evaluate_with_stats};
+pub use explain::explain;
+pub use explicit::{explicit_form, associativity_variants, MAX_VARIANT_CHAIN};
+pub use operator_table::{OperatorTable, OperatorEntry, CustomOperatorEntry, Associativity, evaluate_with_precedence_spec};
+pub use options::{EvalOptions, SubtractionMode, OverflowMode, NumberAdjacencyPolicy, ZeroPowZero, calculate_with_options, evaluate_with_options};
+pub use options::{evaluate_saturating, evaluate_checked, evaluate_bounded};
+pub use preprocess::{join_continued_lines, evaluate_lines, fill_template, evaluate_template};
+pub use preprocess::{apply_aliases, evaluate_with_aliases};
+pub use random::evaluate_seeded;
+pub use rational::{Rational, evaluate_fraction};
+pub use rewrite::Rule;
+pub use session::Session;
+pub use simplify::{simplify_expression, normalize, precompute};
+pub use stream::StreamParser;
+pub use strict::{evaluate_strict, validate_allowed};
+pub use float::{FloatMode, evaluate_float, evaluate_float_with, round_half_even, evaluate_banker_rounded};
+pub use float::{Adaptive, evaluate_adaptive};
+pub use format::{to_base, evaluate_to_base, format_result_grouped, evaluate_grouped, format_vertical};
+pub use format::format_fit;
+pub use function::{Function, turn_into_function};
+pub use latex::to_latex;
+pub use matrix::{calculate_value, evaluate_matrix};
+#[cfg(feature = "timing")]
+pub use evaluator::evaluate_timed;
+pub use field::{Field, ModInt, evaluate_in, evaluate_rpn_in, evaluate_signed, mod_pow};
+pub use tokenizer::{Tokenizer, SpannedToken};
+pub use tokens::{strip_outer_parens, split_top_level, token_diff, next_reducible, AtomCounts, atom_histogram, literals};
+pub use tokens::{contains_variable, find_divide_by_zero, highest_precedence_present, paren_groups};
+pub use tokens::{ExprStats, expression_stats};
+pub use tokens::{render_tokens_preserving_implicit, expression_entropy, reduce_once, animation_frames};
+pub use units::{Quantity, evaluate_units};
+pub use value::{Value, evaluate_value};
 
 /// Defines the different binary operations that could appear in an expression
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Operations {
 
     /// 1+2
@@ -18,16 +107,43 @@ pub enum Operations {
     Divide,
 
     ///1^2
-    Power
+    Power,
+
+    /// 2>1
+    Greater,
+
+    /// 1<2
+    Less,
+
+    /// 1=1
+    Equal,
+
+    /// 1&1
+    And,
+
+    /// 1|0
+    Or
+}
+
+impl Operations {
+    /// Every operation, for processing them all without listing them by hand
+    pub fn all() -> &'static [Operations] {
+        &[Operations::Add, Operations::Subtract, Operations::Multiply, Operations::Divide, Operations::Power,
+          Operations::Greater, Operations::Less, Operations::Equal, Operations::And, Operations::Or]
+    }
 }
 
 /// Represents something that can appear in a valid expression.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum ExpressionAtom {
 
     /// e.g: 1, 5
     Number(u32),
 
+    /// A number with a fractional part, stored exactly as mantissa / 10^scale
+    /// e.g: 0.25 is Decimal { mantissa: 25, scale: 2 }
+    Decimal { mantissa: u32, scale: u32 },
+
     /// e.g: *, /
     Operation(Operations),
 
@@ -35,32 +151,211 @@ pub enum ExpressionAtom {
     LeftParenthesis,
 
     /// i.e )
-    RightParenthesis
+    RightParenthesis,
+
+    /// e.g: clamp
+    Function(Function),
+
+    /// i.e ,
+    Comma,
+
+    /// The multiplication between 2 and (3) in 2(3), which is inferred rather than written
+    ImplicitMultiply,
+
+    /// The - in -5 or 2*-3, which negates one value rather than subtracting two. The tokenizers
+    /// emit a Subtract; to_rpn works out which ones are negations.
+    Negate,
+
+    /// An operator registered at runtime, like the @ in 4@6. See Evaluator::register_operator.
+    Custom(char),
+
+    /// A single letter standing for a value that is given later
+    /// e.g: x
+    Variable(char),
+
+    /// The beginning of the input, from tokenize_bounded
+    Start,
+
+    /// The end of the input, from tokenize_bounded
+    End
 }
 
+impl ExpressionAtom {
+    /// i.e Number(0)
+    pub fn zero() -> ExpressionAtom {
+        ExpressionAtom::Number(0)
+    }
+
+    /// i.e Number(1)
+    pub fn one() -> ExpressionAtom {
+        ExpressionAtom::Number(1)
+    }
+
+    /// Whether this is Number(0), the additive identity
+    pub fn is_zero(&self) -> bool {
+        *self == ExpressionAtom::Number(0)
+    }
+
+    /// Whether this is Number(1), the multiplicative identity
+    pub fn is_one(&self) -> bool {
+        *self == ExpressionAtom::Number(1)
+    }
+
+    /// The atoms that write a signed number, since Number cannot be negative. None if the number
+    /// is too large to be written in an expression.
+    ///     -6 is [Negate, Number(6)], and 6 is [Number(6)]
+    pub fn from_signed(value: i64) -> Option<Vec<ExpressionAtom>> {
+        let number = ExpressionAtom::Number(u32::try_from(value.unsigned_abs()).ok()?);
+        Some(if value < 0 { vec![ExpressionAtom::Negate, number] } else { vec![number] })
+    }
+
+    /// The operation this atom calculates, if it is one. Inferred multiplication counts.
+    pub fn as_operation(&self) -> Option<Operations> {
+        match *self {
+            ExpressionAtom::Operation(op) => Some(op),
+            ExpressionAtom::ImplicitMultiply => Some(Operations::Multiply),
+            _ => None
+        }
+    }
+}
+
+/// Writes an atom the way it looks in an expression, like "42", "+", or "(". Negate is "neg" and
+/// an ImplicitMultiply is written as the "*" it stands for. Debug shows the variant, like Number(42).
 impl Display for ExpressionAtom {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        return match self {
-            &ExpressionAtom::Number(num) => write!(f, "Number({})", num),
-            &ExpressionAtom::Operation(op) => write!(f, "Operation({})", op),
-            &ExpressionAtom::LeftParenthesis => write!(f, "LPAREN"),
-            &ExpressionAtom::RightParenthesis => write!(f, "RPAREN")
-        };
+        match *self {
+            ExpressionAtom::Number(num) => write!(f, "{}", num),
+            // The point keeps a whole Decimal, like the 7. in 7.+1, apart from a Number
+            ExpressionAtom::Decimal { mantissa, scale: 0 } => write!(f, "{}.", mantissa),
+            ExpressionAtom::Decimal { mantissa, scale } => write!(f, "{}", format_decimal(mantissa, scale)),
+            ExpressionAtom::Operation(op) => write!(f, "{}", turn_into_character(op)),
+            ExpressionAtom::LeftParenthesis => write!(f, "("),
+            ExpressionAtom::RightParenthesis => write!(f, ")"),
+            ExpressionAtom::Function(function) => write!(f, "{}", function.name()),
+            ExpressionAtom::Comma => write!(f, ","),
+            ExpressionAtom::ImplicitMultiply => write!(f, "*"),
+            ExpressionAtom::Negate => write!(f, "neg"),
+            ExpressionAtom::Custom(symbol) => write!(f, "{}", symbol),
+            ExpressionAtom::Variable(name) => write!(f, "{}", name),
+            ExpressionAtom::Start => write!(f, "start"),
+            ExpressionAtom::End => write!(f, "end")
+        }
     }
 }
 
+/// Reads an atom back from its Display form. A * is read as a Multiply, so an ImplicitMultiply
+/// comes back as the operation it stands for. The older Kind(inner) form, like "Number(5)", and bare
+/// operation names, like "ADD", are also accepted.
+///     "5" is Number(5), "(" is LeftParenthesis, "x" is Variable('x')
+impl FromStr for ExpressionAtom {
+    type Err = ParseAtomError;
+
+    fn from_str(text: &str) -> Result<ExpressionAtom, ParseAtomError> {
+        let invalid = || ParseAtomError { text: text.to_string() };
+        match text {
+            "(" | "LPAREN" => return Ok(ExpressionAtom::LeftParenthesis),
+            ")" | "RPAREN" => return Ok(ExpressionAtom::RightParenthesis),
+            "," | "COMMA" => return Ok(ExpressionAtom::Comma),
+            "IMPLICIT_MULTIPLY" => return Ok(ExpressionAtom::ImplicitMultiply),
+            "neg" | "NEGATE" => return Ok(ExpressionAtom::Negate),
+            "start" | "START" => return Ok(ExpressionAtom::Start),
+            "end" | "END" => return Ok(ExpressionAtom::End),
+            _ => {}
+        }
+        if let Ok(op) = text.parse::<Operations>() {
+            return Ok(ExpressionAtom::Operation(op));
+        }
+        if let Some(function) = turn_into_function(text) {
+            return Ok(ExpressionAtom::Function(function));
+        }
+        if text.starts_with(|c: char| c.is_ascii_digit()) {
+            return read_decimal(text).ok_or_else(invalid);
+        }
+
+        let mut characters = text.chars();
+        if let (Some(symbol), None) = (characters.next(), characters.next()) {
+            return Ok(match turn_into_operation(symbol) {
+                Some(op) => ExpressionAtom::Operation(op),
+                None if symbol.is_alphabetic() => ExpressionAtom::Variable(symbol),
+                None => ExpressionAtom::Custom(symbol)
+            });
+        }
+
+        // Everything else is written Kind(inner)
+        let (kind, inner) = text.strip_suffix(')').and_then(|call| call.split_once('(')).ok_or_else(invalid)?;
+        match kind {
+            "Number" if inner.chars().all(|c| c.is_ascii_digit()) => read_decimal(inner).ok_or_else(invalid),
+            "Decimal" if !inner.contains('.') => read_decimal(&format!("{}.", inner)).ok_or_else(invalid),
+            "Decimal" => read_decimal(inner).ok_or_else(invalid),
+            "Operation" => inner.parse::<Operations>().map(ExpressionAtom::Operation).map_err(|_| invalid()),
+            "Function" => turn_into_function(inner).map(ExpressionAtom::Function).ok_or_else(invalid),
+            "Custom" | "Variable" => {
+                let mut characters = inner.chars();
+                match (characters.next(), characters.next(), kind) {
+                    (Some(symbol), None, "Custom") => Ok(ExpressionAtom::Custom(symbol)),
+                    (Some(name), None, _) => Ok(ExpressionAtom::Variable(name)),
+                    _ => Err(invalid())
+                }
+            },
+            _ => Err(invalid())
+        }
+    }
+}
+
+/// Reads a number written the way Display writes Number and Decimal atoms. Anything with a decimal
+/// point is a Decimal, even 7. with no fraction.
+///     "42" is Number(42), "0.25" is Decimal { mantissa: 25, scale: 2 }
+fn read_decimal(text: &str) -> Option<ExpressionAtom> {
+    let (whole, fraction) = match text.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (text, None)
+    };
+    let digits = whole.to_string() + fraction.unwrap_or("");
+    if whole.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let mantissa = digits.chars().try_fold(0, |number, digit| append_digit(number, digit.to_digit(10)?))?;
+    Some(match fraction {
+        Some(fraction) => ExpressionAtom::Decimal { mantissa, scale: fraction.len() as u32 },
+        None => ExpressionAtom::Number(mantissa)
+    })
+}
+
+/// Writes mantissa / 10^scale with its decimal point.
+///     format_decimal(25, 2) is "0.25"
+pub fn format_decimal(mantissa: u32, scale: u32) -> String {
+    let digits = format!("{:0width$}", mantissa, width = scale as usize + 1);
+    let (whole, fraction) = digits.split_at(digits.len() - scale as usize);
+    if scale == 0 { whole.to_string() } else { format!("{}.{}", whole, fraction) }
+}
+
 impl Display for Operations {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        return match self {
-            &Operations::Add => write!(f, "ADD"),
-            &Operations::Subtract => write!(f, "SUBTRACT"),
-            &Operations::Multiply => write!(f, "MULTIPLY"),
-            &Operations::Divide => write!(f, "DIVIDE"),
-            &Operations::Power => write!(f, "POWER")
+        match *self {
+            Operations::Add => write!(f, "ADD"),
+            Operations::Subtract => write!(f, "SUBTRACT"),
+            Operations::Multiply => write!(f, "MULTIPLY"),
+            Operations::Divide => write!(f, "DIVIDE"),
+            Operations::Power => write!(f, "POWER"),
+            Operations::Greater => write!(f, "GREATER"),
+            Operations::Less => write!(f, "LESS"),
+            Operations::Equal => write!(f, "EQUAL"),
+            Operations::And => write!(f, "AND"),
+            Operations::Or => write!(f, "OR")
         }
     }
 }
 
+/// Reads an operation back from its Display form, like "ADD"
+impl FromStr for Operations {
+    type Err = ParseAtomError;
+
+    fn from_str(text: &str) -> Result<Operations, ParseAtomError> {
+        Operations::all().iter().cloned().find(|op| op.to_string() == text)
+            .ok_or_else(|| ParseAtomError { text: text.to_string() })
+    }
+}
+
 /*
 impl Display for Vec<ExpressionAtom> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
@@ -73,7 +368,31 @@ impl Display for Vec<ExpressionAtom> {
 */
 /// What order operations are calculated in
 pub const ORDER_OF_OPERATIONS: &[Operations] =
-    &[Operations::Power, Operations::Multiply, Operations::Divide, Operations::Add, Operations::Subtract];
+    &[Operations::Power, Operations::Multiply, Operations::Divide, Operations::Add, Operations::Subtract,
+      Operations::Greater, Operations::Less, Operations::Equal, Operations::And, Operations::Or];
+
+/// How tightly an operation binds. Higher numbers are calculated first.
+pub fn precedence(operation: Operations) -> u8 {
+    match operation {
+        Operations::Power => 5,
+        Operations::Multiply | Operations::Divide => 4,
+        Operations::Add | Operations::Subtract => 3,
+        Operations::Greater | Operations::Less | Operations::Equal => 2,
+        Operations::And => 1,
+        Operations::Or => 0
+    }
+}
+
+/// Whether a chain of this operation groups from the right, like 2^3^2 = 2^(3^2)
+pub fn is_right_associative(operation: Operations) -> bool {
+    matches!(operation, Operations::Power)
+}
+
+/// Whether the operation produces a truth value (0 or 1) rather than a number
+pub fn is_boolean_operation(operation: Operations) -> bool {
+    matches!(operation,
+        Operations::Greater | Operations::Less | Operations::Equal | Operations::And | Operations::Or)
+}
 
 /// Given a character representing a mathematical operation, turn it into the Operations enum.
 pub fn turn_into_operation(operation_character: char) -> Option<Operations> {
@@ -83,10 +402,61 @@ pub fn turn_into_operation(operation_character: char) -> Option<Operations> {
         '*' => Some(Operations::Multiply),
         '/' => Some(Operations::Divide),
         '^' => Some(Operations::Power),
+        '>' => Some(Operations::Greater),
+        '<' => Some(Operations::Less),
+        '=' => Some(Operations::Equal),
+        '&' => Some(Operations::And),
+        '|' => Some(Operations::Or),
         _ => None
     }
 }
 
+/// Every kind of bracket that can group part of an expression, as (opener, closer)
+pub const BRACKET_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Checks that every bracket is closed by the same kind of bracket, in order. All kinds group
+/// the same way, but [1+2) is a mistake.
+pub fn validate_parentheses(expression: &str) -> Result<(), EvalError> {
+    let mut open: Vec<char> = vec![];
+    for (pos, character) in expression.chars().enumerate() {
+        if open.is_empty() && BRACKET_PAIRS.iter().any(|&(_, closer)| closer == character) {
+            return Err(EvalError::MismatchedParentheses);
+        }
+        track_bracket(&mut open, character, pos)?;
+    }
+    if open.is_empty() { Ok(()) } else { Err(EvalError::MismatchedParentheses) }
+}
+
+/// Adds character to the brackets open so far if it opens one, or closes the last one if it closes
+/// one, checking it is the same kind. A closing bracket with nothing open is left for the caller.
+///     with [ open, ] closes it, but ) at 4 is MismatchedBrackets { open: '[', close: ')', pos: 4 }
+pub(crate) fn track_bracket(open: &mut Vec<char>, character: char, pos: usize) -> Result<(), TokenizeError> {
+    if BRACKET_PAIRS.iter().any(|&(opener, _)| opener == character) {
+        open.push(character);
+    } else if let Some(&(opener, _)) = BRACKET_PAIRS.iter().find(|&&(_, closer)| closer == character) {
+        match open.pop() {
+            Some(last) if last != opener => return Err(TokenizeError::MismatchedBrackets { open: last, close: character, pos }),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Alternate spellings of operations and the canonical ASCII spelling they stand for
+pub const OPERATOR_ALIASES: &[(&str, &str)] =
+    &[("**", "^"), ("\u{00D7}", "*"), ("\u{00B7}", "*"), ("\u{00F7}", "/"), ("\u{2212}", "-")];
+
+/// Rewrites every alternate operator spelling into its canonical ASCII form, so the tokenizer
+/// only ever sees one spelling per operation.
+///     "2×3" becomes "2*3", "2**3" becomes "2^3"
+pub fn normalize_operators(expression: &str) -> String {
+    let mut normalized = expression.to_string();
+    for &(alias, canonical) in OPERATOR_ALIASES {
+        normalized = normalized.replace(alias, canonical);
+    }
+    normalized
+}
+
 /// Given an operation, turns it into its character equivalent
 pub fn turn_into_character(operations: Operations) -> char {
     match operations {
@@ -94,7 +464,12 @@ pub fn turn_into_character(operations: Operations) -> char {
         Operations::Subtract => '-',
         Operations::Multiply => '*',
         Operations::Divide => '/',
-        Operations::Power => '^'
+        Operations::Power => '^',
+        Operations::Greater => '>',
+        Operations::Less => '<',
+        Operations::Equal => '=',
+        Operations::And => '&',
+        Operations::Or => '|'
     }
 }
 
@@ -105,14 +480,116 @@ pub fn calculate(lval: u32, rval: u32, operation: Operations) -> u32 {
         Operations::Subtract => lval - rval,
         Operations::Multiply => lval * rval,
         Operations::Divide => lval / rval,
-        Operations::Power => lval ^ rval
+        Operations::Power => lval.pow(rval),
+        Operations::Greater => (lval > rval) as u32,
+        Operations::Less => (lval < rval) as u32,
+        Operations::Equal => (lval == rval) as u32,
+        Operations::And => (lval != 0 && rval != 0) as u32,
+        Operations::Or => (lval != 0 || rval != 0) as u32
+    }
+}
+
+/// Calculates two numbers with the Operations enum, reporting overflow and division by zero
+/// instead of panicking
+pub fn try_calculate(lval: u32, rval: u32, operation: Operations) -> Result<u32, EvalError> {
+    match operation {
+        Operations::Add => lval.checked_add(rval).ok_or(EvalError::Overflow),
+        Operations::Subtract => lval.checked_sub(rval).ok_or(EvalError::Underflow),
+        Operations::Multiply => lval.checked_mul(rval).ok_or(EvalError::Overflow),
+        Operations::Divide => lval.checked_div(rval).ok_or(EvalError::DivideByZero),
+        Operations::Power => fast_pow(lval, rval).ok_or(EvalError::Overflow),
+        _ => Ok(calculate(lval, rval, operation))
+    }
+}
+
+/// Calculates two numbers like try_calculate, with None for anything that cannot be calculated:
+/// overflow, going below zero, and division by zero.
+///     calculate_opt(6, 3, Divide) is Some(2), calculate_opt(6, 0, Divide) is None
+pub fn calculate_opt(lval: u32, rval: u32, operation: Operations) -> Option<u32> {
+    try_calculate(lval, rval, operation).ok()
+}
+
+/// Calculates two numbers like try_calculate, along with the calculation written out for a log.
+///     2, 3 and Multiply give (6, "2 * 3 = 6")
+/// A calculation that fails is 0, with the error written in place of the result.
+///     1, 0 and Divide give (0, "1 / 0 = DivideByZero")
+pub fn calculate_with_trace(lval: u32, rval: u32, operation: Operations) -> (u32, String) {
+    let symbol = turn_into_character(operation);
+    match try_calculate(lval, rval, operation) {
+        Ok(result) => (result, format!("{} {} {} = {}", lval, symbol, rval, result)),
+        Err(error) => (0, format!("{} {} {} = {:?}", lval, symbol, rval, error))
+    }
+}
+
+/// Raises base to exp by repeated squaring, so it takes log(exp) multiplications.
+/// None if the result does not fit in a u32.
+///     fast_pow(3, 4) is Some(81)
+fn fast_pow(base: u32, exp: u32) -> Option<u32> {
+    square_and_multiply(base, u64::from(exp), 1, |a, b| a.checked_mul(b).ok_or(EvalError::Overflow), None).ok()
+}
+
+/// Raises base to exp by repeated squaring, for any type with a multiplication. one is the result
+/// of a zero exponent, and multiply works out each product. The last square is never used, so it is
+/// not worked out, and cannot overflow. When a cancel flag is given, it is checked before every
+/// step, and being set stops the calculation with Cancelled.
+///     3, 4 and 1 with checked_mul is Ok(81)
+pub(crate) fn square_and_multiply<T, M>(base: T, exp: u64, one: T, mut multiply: M, cancel: Option<&AtomicBool>)
+    -> Result<T, EvalError>
+    where T: Copy, M: FnMut(T, T) -> Result<T, EvalError> {
+    let mut result = one;
+    let mut square = base;
+    let mut remaining = exp;
+    while remaining > 0 {
+        if cancel.is_some_and(|cancel| cancel.load(AtomicOrdering::Relaxed)) {
+            return Err(EvalError::Cancelled);
+        }
+        if remaining & 1 == 1 {
+            result = multiply(result, square)?;
+        }
+        remaining >>= 1;
+        if remaining > 0 {
+            square = multiply(square, square)?;
+        }
     }
+    Ok(result)
+}
+
+/// Whether lval/rval divides evenly, so nothing is lost to truncation.
+///     6/2 is exact, 7/2 is not
+/// Dividing by zero is never exact.
+pub fn division_is_exact(lval: u32, rval: u32) -> bool {
+    lval.checked_rem(rval) == Some(0)
+}
+
+/// The smallest base and largest exponent, at least 2, that raise to value, if there are any.
+///     8 is Some((2, 3)), 16 is Some((2, 4)), and 7 is None
+/// 0 and 1 are their own squares.
+pub fn is_perfect_power(value: u32) -> Option<(u32, u32)> {
+    if value < 2 {
+        return Some((value, 2));
+    }
+    (2..32).rev().find_map(|exp| {
+        // The floating point root can be off by one either way, so its neighbours are checked too
+        let root = f64::from(value).powf(1.0 / f64::from(exp)).round() as u32;
+        (root.saturating_sub(1)..=root + 1)
+            .find(|&base| base >= 2 && fast_pow(base, exp) == Some(value))
+            .map(|base| (base, exp))
+    })
+}
+
+/// Left-folds a list of numbers with one operation.
+///     [1, 2, 3] with Add is 6
+/// Returns None for an empty list, or if any step overflows or divides by zero
+pub fn calculate_many(values: &[u32], operation: Operations) -> Option<u32> {
+    let (&first, rest) = values.split_first()?;
+    rest.iter().try_fold(first, |total, &value| try_calculate(total, value, operation).ok())
 }
 
 /// The data structure which handles the computation.
 ///     Feeding it [1, +, 2] will make the result be '3'
 ///     Feeding it [3 / 4] will make the result '.75'
 /// This data structure cannot handle order of operations
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct ExpressionStack {
     pub operation: Option<Operations>,
     pub left_value: Option<u32>,
@@ -120,6 +597,7 @@ pub struct ExpressionStack {
 }
 
 /// The stack can accept multiple types. This lets us overload 'accept'
+#[allow(dead_code)]
 trait MultiValuedStack {
     type AcceptionType;
     fn accept(&mut self, item: Self::AcceptionType);
@@ -131,12 +609,48 @@ impl ExpressionStack {
         if let Some(left) = self.left_value {
             if let Some(right) = self.right_value {
                 if let Some(operation) = self.operation {
-                    calculate(left, right, operation);
+                    return calculate(left, right, operation);
                 }
             }
         }
         panic!("Cannot calculate value without a lval, rval, and operation");
     }
+
+    /// Starts a new stack whose left value is this stack's result.
+    ///     (1+2) combined with * and 3 calculates to 9
+    pub fn combine(self, operation: Operations, other: u32) -> ExpressionStack {
+        ExpressionStack {
+            operation: Some(operation),
+            left_value: Some(self.calculate()),
+            right_value: Some(other)
+        }
+    }
+}
+
+/// Calculates a flat token stream strictly left to right with an ExpressionStack, so there is no
+/// precedence and no parentheses. Each time the stack fills, its result becomes the next left value.
+///     [1, +, 2, *, 3] folds to 9
+pub fn fold_tokens(tokens: &[ExpressionAtom]) -> Result<u32, StackError> {
+    let mut stack = ExpressionStack { operation: None, left_value: None, right_value: None };
+    for (pos, &token) in tokens.iter().enumerate() {
+        match (token, stack.left_value, stack.operation) {
+            (ExpressionAtom::Number(value), None, _) => stack.left_value = Some(value),
+            (ExpressionAtom::Number(_), Some(_), None) => return Err(StackError::MissingOperation { pos }),
+            (ExpressionAtom::Number(value), Some(left), Some(operation)) => {
+                let result = try_calculate(left, value, operation).map_err(|error| StackError::Calculation { error, pos })?;
+                stack = ExpressionStack { operation: None, left_value: Some(result), right_value: None };
+            },
+            (ExpressionAtom::Operation(operation), Some(_), None) => stack.operation = Some(operation),
+            (ExpressionAtom::Operation(_), _, _) => return Err(StackError::MissingValue { pos }),
+            (atom, _, _) => return Err(StackError::Unsupported { atom, pos })
+        }
+    }
+
+    match (stack.left_value, stack.operation) {
+        (Some(result), None) => Ok(result),
+        (Some(_), Some(_)) => Err(StackError::MissingValue { pos: tokens.len() }),
+        (None, _) => Err(StackError::Empty)
+    }
 }
 /*
 /// Allows the stack to take a number for the left and right position
@@ -161,14 +675,637 @@ impl MultiValuedStack for ExpressionStack {
     }
 }
 */
+/// Appends tokens to the end of the tree, so it can be built up from several sources
+impl Extend<ExpressionAtom> for OperationTokenTree {
+    fn extend<T: IntoIterator<Item = ExpressionAtom>>(&mut self, iter: T) {
+        self.tokens.extend(iter);
+    }
+}
+
+/// Lets a tree be handed straight to functions that take a token slice, like to_rpn
+impl AsRef<[ExpressionAtom]> for OperationTokenTree {
+    fn as_ref(&self) -> &[ExpressionAtom] {
+        &self.tokens
+    }
+}
+
+/// Compares the tree's tokens against an expression's, for concise tests.
+///     A tree holding 1+2 equals "1 + 2", but not "1+3"
+/// An expression that cannot be tokenized equals nothing.
+impl<'a> PartialEq<&'a str> for OperationTokenTree {
+    fn eq(&self, expression: &&'a str) -> bool {
+        OperationTokenTree::try_evaluate_tokens(expression).is_ok_and(|tokens| tokens == self.tokens)
+    }
+}
+
+/// Inserts an ImplicitMultiply wherever a value is directly followed by another value, like the
+/// 2 and (3) in 2(3), or (1) and clamp in (1)clamp(2,3,4)
+fn mark_implicit_multiplication(tokens: Vec<ExpressionAtom>) -> Vec<ExpressionAtom> {
+    let mut marked: Vec<ExpressionAtom> = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let ends_value = matches!(marked.last(),
+            Some(&ExpressionAtom::Number(_)) | Some(&ExpressionAtom::Decimal { .. })
+            | Some(&ExpressionAtom::Variable(_)) | Some(&ExpressionAtom::RightParenthesis));
+        let starts_value = matches!(token,
+            ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. } | ExpressionAtom::Variable(_)
+            | ExpressionAtom::LeftParenthesis | ExpressionAtom::Function(_));
+        if ends_value && starts_value {
+            marked.push(ExpressionAtom::ImplicitMultiply);
+        }
+        marked.push(token);
+    }
+    marked
+}
+
 /// Represents something that can be passed through the stack. This also doesn't account for
 /// PEMDAS or parenthesis
+#[derive(Clone, Debug, Default)]
 pub struct OperationTokenTree {
-    tokens: Vec<ExpressionAtom>
+    tokens: Vec<ExpressionAtom>,
+
+    // Whether the tokens are already in reverse polish order, rather than infix
+    postfix: bool
 }
 
 impl OperationTokenTree {
+    pub fn new() -> OperationTokenTree {
+        OperationTokenTree { tokens: vec![], postfix: false }
+    }
+
+    /// Builds a tree from reverse polish notation written out as space separated words, the way
+    /// evaluate_rpn_str takes it. The tree remembers that its tokens are postfix.
+    ///     "1 2 +" holds [Number(1), Number(2), Operation(Add)]
+    pub fn from_postfix_str(input: &str) -> Result<OperationTokenTree, EvalError> {
+        Ok(OperationTokenTree { tokens: evaluator::read_rpn_words(input)?, postfix: true })
+    }
+
+    /// Whether the tokens are in reverse polish order, as they are when built by from_postfix_str
+    pub fn is_postfix(&self) -> bool {
+        self.postfix
+    }
+
+    /// Evaluates the tokens held by the tree, putting them in reverse polish order first unless
+    /// they already are
+    pub fn evaluate(&self) -> Result<u32, EvalError> {
+        if self.postfix {
+            return evaluate_rpn(&self.tokens);
+        }
+        evaluate_rpn(&to_rpn(&self.tokens)?)
+    }
+
+    /// The tokens held by the tree, in order
+    pub fn tokens(&self) -> &[ExpressionAtom] {
+        &self.tokens
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Tokenizes an expression. Characters that are not understood are skipped.
+    /// Panics if a number does not fit in a u32, or on anything else try_evaluate_tokens reports.
     pub fn evaluate_tokens(expression: &str) -> Vec<ExpressionAtom> {
+        OperationTokenTree::try_evaluate_tokens(expression).unwrap()
+    }
+
+    /// Tokenizes an expression, reporting numbers that do not fit in a u32 as NumberOverflow and
+    /// brackets closed by the wrong kind, like [1+2), as MismatchedBrackets.
+    /// Positions count characters after operator aliases, like **, are rewritten.
+    pub fn try_evaluate_tokens(expression: &str) -> Result<Vec<ExpressionAtom>, TokenizeError> {
+        OperationTokenTree::try_evaluate_tokens_with(expression, &[], NumberAdjacencyPolicy::default())
+    }
+
+    /// Tokenizes an expression like try_evaluate_tokens, deciding what numbers separated only by
+    /// spaces mean with the policy.
+    ///     "12 34" is [Number(1234)], an AdjacentNumbers error, or [Number(12), ImplicitMultiply, Number(34)]
+    pub fn try_evaluate_tokens_with_policy(expression: &str, adjacency: NumberAdjacencyPolicy)
+        -> Result<Vec<ExpressionAtom>, TokenizeError> {
+        OperationTokenTree::try_evaluate_tokens_with(expression, &[], adjacency)
+    }
+
+    /// Tokenizes an expression like try_evaluate_tokens_with_policy, reading the custom symbols as
+    /// Custom atoms
+    pub(crate) fn try_evaluate_tokens_with(expression: &str, custom_symbols: &[char], adjacency: NumberAdjacencyPolicy)
+        -> Result<Vec<ExpressionAtom>, TokenizeError> {
+
+        /*
+        EXAMPLES:
+            * 1 + 2 = N(1), O(Add), N(2)
+            * 1 + (1 + 2) = N(1), O(Add), LP, N(1), O(Add), N(2), RP
+            * 1 + (1 + (1 + 2)) = N(1), O(Add), LP, N(1), O(Add), LP, N(1), O(Add), N(2), RP, RP
+        */
+
+        // Letters are gathered into words, which become functions if they name one
+        let mut word = String::new();
+
+        let normalized = normalize_operators(expression);
+        let characters: Vec<char> = normalized.chars().collect();
+        let mut condenser = Condenser::new(&characters, adjacency);
+        for (position, &character) in characters.iter().enumerate() {
+            if character.is_alphabetic() {
+                word.push(character);
+                continue;
+            }
+            if let Some(atom) = word_atom(&word) {
+                condenser.push(atom, position - word.chars().count())?;
+            }
+            word.clear();
+
+            if let Some(digit) = character.to_digit(10) {
+                condenser.push(ExpressionAtom::Number(digit), position)?;
+            }
+            if custom_symbols.contains(&character) {
+                condenser.push(ExpressionAtom::Custom(character), position)?;
+            } else if let Some(op) = turn_into_operation(character) {
+                condenser.push(ExpressionAtom::Operation(op), position)?;
+            }
+            if character.eq(&'(') || character.eq(&'[') || character.eq(&'{') {
+                condenser.push(ExpressionAtom::LeftParenthesis, position)?;
+            }
+            if character.eq(&')') || character.eq(&']') || character.eq(&'}') {
+                condenser.push(ExpressionAtom::RightParenthesis, position)?;
+            }
+            if character.eq(&',') {
+                condenser.push(ExpressionAtom::Comma, position)?;
+            }
+            if character.eq(&'.') {
+                condenser.push(ExpressionAtom::Decimal { mantissa: 0, scale: 0 }, position)?;
+            }
+        }
+        if let Some(atom) = word_atom(&word) {
+            condenser.push(atom, characters.len() - word.chars().count())?;
+        }
+
+        Ok(mark_implicit_multiplication(condenser.finish()?))
+    }
+}
+
+/// Builds the finished tokens as the tokenizer finds single-character ones, joining runs of digits
+/// and decimal points into numbers as they arrive. So, '12 + 4' arrives as N(1), N(2), O(Add), N(4)
+/// and leaves as N(12), O(Add), N(4).
+struct Condenser<'a> {
+    characters: &'a [char],
+    adjacency: NumberAdjacencyPolicy,
+    tokens: Vec<ExpressionAtom>,
+    building_number: Option<ExpressionAtom>,
+    number_start: usize,
+    /// Whether the number being built has any digits yet, since a point alone is not a number
+    number_has_digits: bool,
+    previous_position: Option<usize>,
+    /// The brackets open so far, so each closing one can be checked against its kind
+    brackets: Vec<char>
+}
+
+impl<'a> Condenser<'a> {
+    fn new(characters: &'a [char], adjacency: NumberAdjacencyPolicy) -> Condenser<'a> {
+        Condenser {
+            characters,
+            adjacency,
+            tokens: Vec::with_capacity(characters.len()),
+            building_number: None,
+            number_start: 0,
+            number_has_digits: false,
+            previous_position: None,
+            brackets: vec![]
+        }
+    }
+
+    /// Takes the token that starts at position in the expression
+    fn push(&mut self, token: ExpressionAtom, position: usize) -> Result<(), TokenizeError> {
+        // A digit that does not directly follow the number being built, like the 3 in 12 34
+        let separated = self.building_number.is_some()
+            && self.previous_position.is_some_and(|previous| position > previous + 1);
+        self.previous_position = Some(position);
+
+        match token {
+            ExpressionAtom::Number(digit) => {
+                if separated {
+                    match self.adjacency {
+                        NumberAdjacencyPolicy::Concatenate => {},
+                        NumberAdjacencyPolicy::Error => return Err(TokenizeError::AdjacentNumbers { pos: position }),
+                        NumberAdjacencyPolicy::ImplicitMultiply => {
+                            let number = self.take_number()?;
+                            self.tokens.extend(number);
+                            self.tokens.push(ExpressionAtom::ImplicitMultiply);
+                        }
+                    }
+                }
+                self.building_number = Some(match self.building_number {
+                    Some(ExpressionAtom::Number(old_number)) =>
+                        ExpressionAtom::Number(append_digit(old_number, digit).ok_or_else(|| self.overflow())?),
+                    Some(ExpressionAtom::Decimal { mantissa, scale }) => ExpressionAtom::Decimal {
+                        mantissa: append_digit(mantissa, digit).ok_or_else(|| self.overflow())?,
+                        scale: scale + 1
+                    },
+                    _ => {
+                        self.number_start = position;
+                        token
+                    }
+                });
+                self.number_has_digits = true;
+            },
+            // A decimal point turns the number being built into a decimal. A second point in the
+            // same number, like the last one in 1.2.3, is a mistake.
+            ExpressionAtom::Decimal { .. } => {
+                self.building_number = Some(match self.building_number {
+                    Some(ExpressionAtom::Number(whole)) => ExpressionAtom::Decimal { mantissa: whole, scale: 0 },
+                    Some(_) if !separated => return Err(TokenizeError::UnexpectedChar { c: '.', pos: position }),
+                    _ => {
+                        let number = self.take_number()?;
+                        self.tokens.extend(number);
+                        self.number_start = position;
+                        self.number_has_digits = false;
+                        token
+                    }
+                });
+            },
+            _ => {
+                let number = self.take_number()?;
+                self.tokens.extend(number);
+                if matches!(token, ExpressionAtom::LeftParenthesis | ExpressionAtom::RightParenthesis) {
+                    track_bracket(&mut self.brackets, self.characters[position], position)?;
+                }
+                self.tokens.push(token);
+            }
+        }
+        Ok(())
+    }
+
+    /// Ends the number being built, if there is one. A point with no digits around it is not a number.
+    fn take_number(&mut self) -> Result<Option<ExpressionAtom>, TokenizeError> {
+        if self.building_number.is_some() && !self.number_has_digits {
+            return Err(TokenizeError::UnexpectedChar { c: '.', pos: self.number_start });
+        }
+        Ok(self.building_number.take())
+    }
+
+    /// The error for the number being built growing too large
+    fn overflow(&self) -> TokenizeError {
+        TokenizeError::NumberOverflow {
+            text: self.characters[self.number_start..].iter().take_while(|c| c.is_ascii_digit() || **c == '.').collect(),
+            pos: self.number_start
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<ExpressionAtom>, TokenizeError> {
+        let number = self.take_number()?;
+        self.tokens.extend(number);
+        Ok(self.tokens)
+    }
+}
+
+/// The largest number that can be written in an expression
+pub const MAX_LITERAL: u32 = u32::MAX;
+
+/// Whether text is a run of digits small enough to be written in an expression, for checking
+/// input before evaluating it.
+///     "4294967295" fits, "4294967296" does not
+pub fn literal_fits(text: &str) -> bool {
+    let mut characters = text.chars().peekable();
+    // Literals are u32s, so anything that parses is at most MAX_LITERAL
+    tokenizer::parse_number_run(&mut characters).is_ok() && characters.next().is_none()
+}
+
+/// Builds a number atom from its decimal digits, most significant first. None if there are no
+/// digits, a digit is larger than 9, or the number is too large to be written in an expression.
+///     [1, 2, 3] is Number(123)
+pub fn number_from_digits(digits: &[u32]) -> Option<ExpressionAtom> {
+    if digits.is_empty() || digits.iter().any(|&digit| digit > 9) {
+        return None;
+    }
+    digits.iter().try_fold(0, |number, &digit| append_digit(number, digit)).map(ExpressionAtom::Number)
+}
+
+/// Joins each run of adjacent Number atoms into one number, the way the tokenizer joins digits, for
+/// tokens that arrive with their numbers split up. A number that would become too large to be
+/// written in an expression is not joined to the ones after it.
+///     [Number(1), Number(2), Operation(Add), Number(3)] becomes [Number(12), Operation(Add), Number(3)]
+pub fn merge_numbers<I: Iterator<Item = ExpressionAtom>>(iter: I) -> impl Iterator<Item = ExpressionAtom> {
+    let mut atoms = iter.peekable();
+    std::iter::from_fn(move || {
+        let mut atom = atoms.next()?;
+        while let (ExpressionAtom::Number(left), Some(&ExpressionAtom::Number(right))) = (atom, atoms.peek()) {
+            let joined = right.to_string().chars()
+                .try_fold(left, |number, digit| append_digit(number, digit.to_digit(10)?));
+            match joined {
+                Some(joined) => atom = ExpressionAtom::Number(joined),
+                None => break
+            }
+            atoms.next();
+        }
+        Some(atom)
+    })
+}
+
+/// Tokenizes an expression between a Start and an End sentinel, so every token has a neighbour on
+/// both sides. Each - that starts an operand, directly after Start, an operation, a parenthesis, or
+/// a comma, is marked as a Negate.
+///     "-5" is [Start, Negate, Number(5), End]
+pub fn tokenize_bounded(expression: &str) -> Result<Vec<ExpressionAtom>, TokenizeError> {
+    let mut bounded: Vec<ExpressionAtom> = vec![ExpressionAtom::Start];
+    for token in OperationTokenTree::try_evaluate_tokens(expression)? {
+        let unary = token == ExpressionAtom::Operation(Operations::Subtract) && matches!(bounded.last(),
+            Some(&ExpressionAtom::Start) | Some(&ExpressionAtom::Operation(_)) | Some(&ExpressionAtom::Negate)
+            | Some(&ExpressionAtom::Custom(_)) | Some(&ExpressionAtom::LeftParenthesis) | Some(&ExpressionAtom::Comma));
+        bounded.push(if unary { ExpressionAtom::Negate } else { token });
+    }
+    bounded.push(ExpressionAtom::End);
+    Ok(bounded)
+}
+
+/// Tokenizes an expression, also giving it back written out in a normal form: each token in its
+/// usual spelling, with one space between tokens. The nth word of the text is the nth token, except
+/// that multiplications the tokenizer inferred take up no text.
+///     "1+  2×3" is ("1 + 2 * 3", [Number(1), Operation(Add), Number(2), Operation(Multiply), Number(3)])
+///     "2(x)" is ("2 ( x )", [Number(2), ImplicitMultiply, LeftParenthesis, Variable('x'), RightParenthesis])
+pub fn lex(expression: &str) -> Result<(String, Vec<ExpressionAtom>), TokenizeError> {
+    let tokens = OperationTokenTree::try_evaluate_tokens(expression)?;
+    let words: Vec<String> = tokens.iter()
+        .map(|token| tokens::render_tokens_preserving_implicit(std::slice::from_ref(token)).trim().to_string())
+        .filter(|word| !word.is_empty())
+        .collect();
+    Ok((words.join(" "), tokens))
+}
+
+/// The atom a word of letters stands for: a function if it names one, or a variable if it is a
+/// single letter. Other words are not understood.
+fn word_atom(word: &str) -> Option<ExpressionAtom> {
+    let mut letters = word.chars();
+    match (turn_into_function(word), letters.next(), letters.next()) {
+        (Some(function), _, _) => Some(ExpressionAtom::Function(function)),
+        (None, Some(letter), None) => Some(ExpressionAtom::Variable(letter)),
+        _ => None
+    }
+}
+
+/// Writes digit on the end of number, so 12 and 3 make 123. None if the result is too large. Every
+/// place that builds a number out of its digits goes through this.
+pub(crate) fn append_digit(number: u32, digit: u32) -> Option<u32> {
+    number.checked_mul(10).and_then(|shifted| shifted.checked_add(digit))
+}
+
+#[cfg(test)]
+mod tests {
+    use self::super::*;
+    use OperationTokenTree;
+    #[test]
+    fn test_tokenizer() {
+        let _t_tree = OperationTokenTree{tokens: vec![], postfix: false};
+
+        let token_stream: Vec<ExpressionAtom> = OperationTokenTree::evaluate_tokens(
+            "1+(1+(2+4+5666))"
+        );
+        println!("{:?}", token_stream);
+    }
+
+    #[test]
+    fn test_operation_character_round_trip() {
+        for &op in Operations::all() {
+            assert_eq!(turn_into_operation(turn_into_character(op)), Some(op));
+        }
+    }
+
+    #[test]
+    fn test_operations_all() {
+        // This match stops compiling when a variant is added, as a reminder to update all()
+        let variant_index = |op: Operations| match op {
+            Operations::Add => 0, Operations::Subtract => 1, Operations::Multiply => 2,
+            Operations::Divide => 3, Operations::Power => 4, Operations::Greater => 5,
+            Operations::Less => 6, Operations::Equal => 7, Operations::And => 8, Operations::Or => 9
+        };
+        let mut seen = [0; 10];
+        for &op in Operations::all() {
+            seen[variant_index(op)] += 1;
+        }
+        assert_eq!(Operations::all().len(), seen.len());
+        assert!(seen.iter().all(|&count| count == 1));
+    }
+
+    #[test]
+    fn test_decimal_tokens() {
+        assert_eq!(OperationTokenTree::evaluate_tokens("0.25+1.5"), vec![
+            ExpressionAtom::Decimal { mantissa: 25, scale: 2 }, ExpressionAtom::Operation(Operations::Add),
+            ExpressionAtom::Decimal { mantissa: 15, scale: 1 }]);
+        assert_eq!(OperationTokenTree::evaluate_tokens(".5"), vec![ExpressionAtom::Decimal { mantissa: 5, scale: 1 }]);
+        assert_eq!(ExpressionAtom::Decimal { mantissa: 5, scale: 2 }.to_string(), "0.05");
+        assert_eq!(format_decimal(1200, 0), "1200");
+    }
+
+    #[test]
+    fn test_malformed_decimals() {
+        assert_eq!(OperationTokenTree::try_evaluate_tokens("1+."), Err(TokenizeError::UnexpectedChar { c: '.', pos: 2 }));
+        assert_eq!(OperationTokenTree::try_evaluate_tokens("1.2.3"), Err(TokenizeError::UnexpectedChar { c: '.', pos: 3 }));
+        assert!(evaluate(".").is_err());
+        assert!(evaluate("1+.").is_err());
+        assert!(evaluate("(.)*3").is_err());
+        assert!(evaluate_float("1.2.3").is_err());
+        assert!(evaluate_float("1..2").is_err());
+        assert_eq!(evaluate_float("2.+.5"), Ok(2.5));
+    }
+
+    #[test]
+    fn test_tree_equals_str() {
+        let mut tree = OperationTokenTree::new();
+        tree.extend(OperationTokenTree::evaluate_tokens("1+2"));
+        assert_eq!(tree, "1+2");
+        assert_eq!(tree, "1 + 2");
+        assert_ne!(tree, "1+3");
+        assert_ne!(tree, "1+2+");
+        assert_ne!(tree, "4294967296");
+        assert_eq!(OperationTokenTree::new(), "");
+    }
+
+    #[test]
+    fn test_tree_as_ref() {
+        let mut tree = OperationTokenTree::new();
+        tree.extend(OperationTokenTree::evaluate_tokens("2(3+4)"));
+        assert_eq!(evaluate_rpn(&to_rpn(tree.as_ref()).unwrap()), Ok(14));
+        assert_eq!(render_tokens_preserving_implicit(tree.as_ref()), "2(3+4)");
+        assert_eq!(tree.as_ref(), tree.tokens());
+    }
+
+    #[test]
+    fn test_tree_from_postfix_str() {
+        let tree = OperationTokenTree::from_postfix_str("1 2 +").unwrap();
+        assert!(tree.is_postfix());
+        assert_eq!(tree.tokens(), &[ExpressionAtom::Number(1), ExpressionAtom::Number(2), ExpressionAtom::Operation(Operations::Add)]);
+        assert_eq!(tree.evaluate(), Ok(3));
+
+        assert_eq!(OperationTokenTree::from_postfix_str("1 2 3 * +").and_then(|tree| tree.evaluate()), Ok(7));
+        assert_eq!(OperationTokenTree::from_postfix_str("5 neg 2 +").and_then(|tree| tree.evaluate()), Err(EvalError::Underflow));
+        assert_eq!(OperationTokenTree::from_postfix_str("1 +").and_then(|tree| tree.evaluate()), Err(EvalError::MissingOperand));
+        assert!(OperationTokenTree::from_postfix_str("1+2 3").is_err());
+
+        let mut infix = OperationTokenTree::new();
+        infix.extend(OperationTokenTree::evaluate_tokens("1+2*3"));
+        assert!(!infix.is_postfix());
+        assert_eq!(infix.evaluate(), Ok(7));
+    }
+
+    #[test]
+    fn test_variables() {
+        assert_eq!(OperationTokenTree::evaluate_tokens("2x+y"), vec![
+            ExpressionAtom::Number(2), ExpressionAtom::ImplicitMultiply, ExpressionAtom::Variable('x'),
+            ExpressionAtom::Operation(Operations::Add), ExpressionAtom::Variable('y')]);
+        assert_eq!(OperationTokenTree::evaluate_tokens("abs(x)")[0], ExpressionAtom::Function(Function::Abs));
+        // Longer words that are not functions are still skipped
+        assert_eq!(OperationTokenTree::evaluate_tokens("1+xy"), OperationTokenTree::evaluate_tokens("1+"));
+        assert_eq!(evaluate("1+x"), Err(EvalError::UnboundVariable('x')));
+    }
+
+    #[test]
+    fn test_extend() {
+        let mut tree = OperationTokenTree::new();
+        assert!(tree.is_empty());
+
+        tree.extend(OperationTokenTree::evaluate_tokens("1+2"));
+        tree.extend(vec![ExpressionAtom::Operation(Operations::Multiply)]);
+        tree.extend(OperationTokenTree::evaluate_tokens("(3+4)"));
+        assert_eq!(tree.len(), 9);
+        assert_eq!(tree.tokens(), &OperationTokenTree::evaluate_tokens("1+2*(3+4)")[..]);
+    }
+
+    #[test]
+    fn test_zero_and_one() {
+        assert!(ExpressionAtom::zero().is_zero());
+        assert!(ExpressionAtom::one().is_one());
+        assert!(!ExpressionAtom::zero().is_one());
+        assert!(!ExpressionAtom::one().is_zero());
+        assert!(!ExpressionAtom::Number(10).is_zero());
+        assert!(!ExpressionAtom::Number(10).is_one());
+        assert!(!ExpressionAtom::Operation(Operations::Multiply).is_one());
+        assert!(!ExpressionAtom::LeftParenthesis.is_zero());
+        assert!(!ExpressionAtom::Comma.is_zero());
+    }
+
+    #[test]
+    fn test_from_signed() {
+        assert_eq!(ExpressionAtom::from_signed(-6), Some(vec![ExpressionAtom::Negate, ExpressionAtom::Number(6)]));
+        assert_eq!(ExpressionAtom::from_signed(6), Some(vec![ExpressionAtom::Number(6)]));
+        assert_eq!(ExpressionAtom::from_signed(-4294967296), None);
+
+        let mut tokens = OperationTokenTree::evaluate_tokens("1-");
+        tokens.extend(ExpressionAtom::from_signed(evaluate_signed("3*-2").unwrap()).unwrap());
+        let rendered = render_tokens_preserving_implicit(&tokens);
+        assert_eq!(rendered, "1-(-6)");
+        assert_eq!(evaluate_signed(&rendered), Ok(7));
+    }
+
+    #[test]
+    fn test_implicit_multiply() {
+        let tokens = OperationTokenTree::evaluate_tokens("2(3)");
+        assert_eq!(tokens, vec![ExpressionAtom::Number(2), ExpressionAtom::ImplicitMultiply,
+            ExpressionAtom::LeftParenthesis, ExpressionAtom::Number(3), ExpressionAtom::RightParenthesis]);
+        assert!(!OperationTokenTree::evaluate_tokens("2*(3)").contains(&ExpressionAtom::ImplicitMultiply));
+        assert!(OperationTokenTree::evaluate_tokens("(1)(2)").contains(&ExpressionAtom::ImplicitMultiply));
+
+        assert_eq!(evaluate("2(3)"), Ok(6));
+        assert_eq!(evaluate("(1+1)(2+1)4"), Ok(24));
+        assert_eq!(evaluate("1+2(3)"), Ok(7));
+    }
+
+    #[test]
+    fn test_stack_combine() {
+        let sum = ExpressionStack { operation: Some(Operations::Add), left_value: Some(1), right_value: Some(2) };
+        assert_eq!(sum.calculate(), 3);
+
+        let product = sum.combine(Operations::Multiply, 3);
+        assert_eq!(product.left_value, Some(3));
+        assert_eq!(product.calculate(), 9);
+        assert_eq!(product.combine(Operations::Subtract, 4).calculate(), 5);
+    }
+
+    #[test]
+    fn test_fold_tokens() {
+        assert_eq!(fold_tokens(&OperationTokenTree::evaluate_tokens("1+2+3")), Ok(6));
+        assert_eq!(fold_tokens(&OperationTokenTree::evaluate_tokens("1+2*3")), Ok(9));
+        assert_eq!(fold_tokens(&OperationTokenTree::evaluate_tokens("42")), Ok(42));
+
+        assert_eq!(fold_tokens(&[]), Err(StackError::Empty));
+        assert_eq!(fold_tokens(&OperationTokenTree::evaluate_tokens("1+")), Err(StackError::MissingValue { pos: 2 }));
+        assert_eq!(fold_tokens(&OperationTokenTree::evaluate_tokens("1+*2")), Err(StackError::MissingValue { pos: 2 }));
+        assert_eq!(fold_tokens(&[ExpressionAtom::Number(1), ExpressionAtom::Number(2)]),
+            Err(StackError::MissingOperation { pos: 1 }));
+        assert_eq!(fold_tokens(&OperationTokenTree::evaluate_tokens("4/0+1")),
+            Err(StackError::Calculation { error: EvalError::DivideByZero, pos: 2 }));
+        assert_eq!(fold_tokens(&OperationTokenTree::evaluate_tokens("(1+2)")),
+            Err(StackError::Unsupported { atom: ExpressionAtom::LeftParenthesis, pos: 0 }));
+    }
+
+    #[test]
+    fn test_calculate_opt() {
+        assert_eq!(calculate_opt(2, 3, Operations::Add), Some(5));
+        assert_eq!(calculate_opt(6, 3, Operations::Divide), Some(2));
+        assert_eq!(calculate_opt(2, 10, Operations::Power), Some(1024));
+        assert_eq!(calculate_opt(3, 2, Operations::Greater), Some(1));
+
+        assert_eq!(calculate_opt(u32::MAX, 1, Operations::Add), None);
+        assert_eq!(calculate_opt(65536, 65536, Operations::Multiply), None);
+        assert_eq!(calculate_opt(2, 32, Operations::Power), None);
+        assert_eq!(calculate_opt(6, 0, Operations::Divide), None);
+        assert_eq!(calculate_opt(1, 2, Operations::Subtract), None);
+    }
+
+    #[test]
+    fn test_calculate_with_trace() {
+        assert_eq!(calculate_with_trace(2, 3, Operations::Add), (5, "2 + 3 = 5".to_string()));
+        assert_eq!(calculate_with_trace(7, 3, Operations::Subtract), (4, "7 - 3 = 4".to_string()));
+        assert_eq!(calculate_with_trace(2, 3, Operations::Multiply), (6, "2 * 3 = 6".to_string()));
+        assert_eq!(calculate_with_trace(7, 2, Operations::Divide), (3, "7 / 2 = 3".to_string()));
+        assert_eq!(calculate_with_trace(2, 10, Operations::Power), (1024, "2 ^ 10 = 1024".to_string()));
+        assert_eq!(calculate_with_trace(3, 2, Operations::Greater), (1, "3 > 2 = 1".to_string()));
+        assert_eq!(calculate_with_trace(3, 2, Operations::Less), (0, "3 < 2 = 0".to_string()));
+        assert_eq!(calculate_with_trace(4, 4, Operations::Equal), (1, "4 = 4 = 1".to_string()));
+        assert_eq!(calculate_with_trace(1, 0, Operations::And), (0, "1 & 0 = 0".to_string()));
+        assert_eq!(calculate_with_trace(1, 0, Operations::Or), (1, "1 | 0 = 1".to_string()));
+    }
+
+    #[test]
+    fn test_calculate_with_trace_errors() {
+        assert_eq!(calculate_with_trace(1, 0, Operations::Divide), (0, "1 / 0 = DivideByZero".to_string()));
+        assert_eq!(calculate_with_trace(1, 2, Operations::Subtract), (0, "1 - 2 = Underflow".to_string()));
+        assert_eq!(calculate_with_trace(u32::MAX, 2, Operations::Multiply), (0, "4294967295 * 2 = Overflow".to_string()));
+    }
+
+    #[test]
+    fn test_calculate_many() {
+        assert_eq!(calculate_many(&[1, 2, 3], Operations::Add), Some(6));
+        assert_eq!(calculate_many(&[2, 3, 4], Operations::Multiply), Some(24));
+        assert_eq!(calculate_many(&[7], Operations::Subtract), Some(7));
+        assert_eq!(calculate_many(&[], Operations::Add), None);
+    }
+
+    #[test]
+    fn test_fast_pow() {
+        for base in 0..12u32 {
+            for exp in 0..12u32 {
+                assert_eq!(fast_pow(base, exp), base.checked_pow(exp));
+            }
+        }
+        assert_eq!(fast_pow(2, 31), Some(2u32.pow(31)));
+        assert_eq!(fast_pow(2, 32), None);
+        assert_eq!(fast_pow(65535, 2), Some(65535u32.pow(2)));
+        assert_eq!(fast_pow(1, u32::MAX), Some(1));
+        assert_eq!(fast_pow(0, u32::MAX), Some(0));
+    }
+
+    #[test]
+    fn test_try_evaluate_tokens_overflow() {
+        assert_eq!(OperationTokenTree::try_evaluate_tokens("4294967296"),
+            Err(TokenizeError::NumberOverflow { text: "4294967296".to_string(), pos: 0 }));
+        assert_eq!(OperationTokenTree::try_evaluate_tokens("1+0.99999999999"),
+            Err(TokenizeError::NumberOverflow { text: "0.99999999999".to_string(), pos: 2 }));
+        assert_eq!(OperationTokenTree::try_evaluate_tokens("4294967295"), Ok(vec![ExpressionAtom::Number(u32::MAX)]));
+    }
+
+    /// The tokenizer as it was before it became a single pass: single-character tokens were gathered
+    /// into a Vec first, and condensed into numbers afterwards
+    fn two_pass_tokens(expression: &str, custom_symbols: &[char], adjacency: NumberAdjacencyPolicy)
+        -> Result<Vec<ExpressionAtom>, TokenizeError> {
 
         // This will read as-is and do no processing
         /*
@@ -182,70 +1319,366 @@ impl OperationTokenTree {
         let mut tokens: Vec<ExpressionAtom> = vec![];
 
         // Initial tokens. So, '12 + 4' will be N(1), N(2), O(Add), N(4)'. The second pass makes the 12
-        let mut initial_tokenization: Vec<ExpressionAtom> = vec![];
+        // Each is kept with the position of the character it came from
+        let mut initial_tokenization: Vec<(ExpressionAtom, usize)> = vec![];
 
-        let expression_characters = expression.chars();
-        for character in expression_characters {
-            if character.is_numeric() {
-                initial_tokenization.push(ExpressionAtom::Number(character.to_digit(10).unwrap()));
+        // Letters are gathered into words, which become functions if they name one
+        let mut word = String::new();
+
+        let normalized = normalize_operators(expression);
+        let characters: Vec<char> = normalized.chars().collect();
+        for (position, &character) in characters.iter().enumerate() {
+            if character.is_alphabetic() {
+                word.push(character);
+                continue;
+            }
+            if let Some(atom) = word_atom(&word) {
+                initial_tokenization.push((atom, position - word.chars().count()));
+            }
+            word.clear();
+
+            if let Some(digit) = character.to_digit(10) {
+                initial_tokenization.push((ExpressionAtom::Number(digit), position));
+            }
+            if custom_symbols.contains(&character) {
+                initial_tokenization.push((ExpressionAtom::Custom(character), position));
+            } else if let Some(op) = turn_into_operation(character) {
+                initial_tokenization.push((ExpressionAtom::Operation(op), position));
+            }
+            if character.eq(&'(') || character.eq(&'[') || character.eq(&'{') {
+                initial_tokenization.push((ExpressionAtom::LeftParenthesis, position));
             }
-            if let Some(op) = turn_into_operation(character) {
-                initial_tokenization.push(ExpressionAtom::Operation(op));
+            if character.eq(&')') || character.eq(&']') || character.eq(&'}') {
+                initial_tokenization.push((ExpressionAtom::RightParenthesis, position));
             }
-            if character.eq(&'(') {
-                initial_tokenization.push(ExpressionAtom::LeftParenthesis);
+            if character.eq(&',') {
+                initial_tokenization.push((ExpressionAtom::Comma, position));
             }
-            if character.eq(&')') {
-                initial_tokenization.push(ExpressionAtom::RightParenthesis);
+            if character.eq(&'.') {
+                initial_tokenization.push((ExpressionAtom::Decimal { mantissa: 0, scale: 0 }, position));
             }
         }
+        if let Some(atom) = word_atom(&word) {
+            initial_tokenization.push((atom, characters.len() - word.chars().count()));
+        }
 
         // Condenses the initial tokenization into the proper form
         let mut building_number: Option<ExpressionAtom> = None;
-        for token in initial_tokenization {
+        let mut number_start: usize = 0;
+        let mut has_digits = false;
+        let mut brackets: Vec<char> = vec![];
+        let mut previous_position: Option<usize> = None;
+        for (token, position) in initial_tokenization {
+            // A digit that does not directly follow the number being built, like the 3 in 12 34
+            let separated = building_number.is_some() && previous_position.is_some_and(|previous| position > previous + 1);
+            previous_position = Some(position);
+            let overflow = || TokenizeError::NumberOverflow {
+                text: characters[number_start..].iter().take_while(|c| c.is_ascii_digit() || **c == '.').collect(),
+                pos: number_start
+            };
+            let lone_point = TokenizeError::UnexpectedChar { c: '.', pos: number_start };
             match token {
-                ExpressionAtom::RightParenthesis | ExpressionAtom::LeftParenthesis | ExpressionAtom::Operation(_)
-                    => {
-                    if let Some(number) = building_number {
-                        tokens.push(number);
-                        building_number = None;
+                ExpressionAtom::Number(digit) => {
+                    if separated {
+                        match adjacency {
+                            NumberAdjacencyPolicy::Concatenate => {},
+                            NumberAdjacencyPolicy::Error => return Err(TokenizeError::AdjacentNumbers { pos: position }),
+                            NumberAdjacencyPolicy::ImplicitMultiply => {
+                                if !has_digits {
+                                    return Err(lone_point);
+                                }
+                                tokens.extend(building_number.take());
+                                tokens.push(ExpressionAtom::ImplicitMultiply);
+                            }
+                        }
                     }
-                        tokens.push(token);
+                    building_number = Some(match building_number {
+                        Some(ExpressionAtom::Number(old_number)) =>
+                            ExpressionAtom::Number(append_digit(old_number, digit).ok_or_else(overflow)?),
+                        Some(ExpressionAtom::Decimal { mantissa, scale }) => ExpressionAtom::Decimal {
+                            mantissa: append_digit(mantissa, digit).ok_or_else(overflow)?,
+                            scale: scale + 1
+                        },
+                        _ => {
+                            number_start = position;
+                            token
+                        }
+                    });
+                    has_digits = true;
+                },
+                // A decimal point turns the number being built into a decimal
+                ExpressionAtom::Decimal { .. } => {
+                    building_number = Some(match building_number {
+                        Some(ExpressionAtom::Number(whole)) => ExpressionAtom::Decimal { mantissa: whole, scale: 0 },
+                        Some(_) if !separated => return Err(TokenizeError::UnexpectedChar { c: '.', pos: position }),
+                        Some(_) if !has_digits => return Err(lone_point),
+                        previous => {
+                            tokens.extend(previous);
+                            number_start = position;
+                            has_digits = false;
+                            token
+                        }
+                    });
                 },
                 _ => {
                     if let Some(number) = building_number {
-                        if let ExpressionAtom::Number(old_number) = number {
-                            if let ExpressionAtom::Number(new_number) = token {
-                                building_number =
-                                    Some(ExpressionAtom::Number((old_number.to_string() + &new_number.to_string()).parse::<u32>().unwrap()));
-                            }
+                        if !has_digits {
+                            return Err(lone_point);
                         }
-                    } else {
-                        building_number = Some(token);
+                        tokens.push(number);
+                        building_number = None;
                     }
+                    if matches!(token, ExpressionAtom::LeftParenthesis | ExpressionAtom::RightParenthesis) {
+                        track_bracket(&mut brackets, characters[position], position)?;
+                    }
+                    tokens.push(token);
                 }
             }
         }
 
         if let Some(number) = building_number {
+            if !has_digits {
+                return Err(TokenizeError::UnexpectedChar { c: '.', pos: number_start });
+            }
             tokens.push(number);
         }
-        return tokens;
+        Ok(mark_implicit_multiplication(tokens))
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use self::super::*;
-    use OperationTokenTree;
-    use std::vec::*;
     #[test]
-    fn test_tokenizer() {
-        let t_tree = OperationTokenTree{tokens: vec![]};
+    fn test_single_pass_matches_two_pass() {
+        // Every string of up to four pieces, so digits, points, words, and separators meet in every order
+        let pieces = ["1", "9", "0", ".", " ", "+", "-", "*", "(", ")", "[", "]", ",", "x", "ab", "max", "#", "$"];
+        let mut expressions = vec![String::new()];
+        let mut generation = vec![String::new()];
+        for _ in 0..4 {
+            generation = generation.iter()
+                .flat_map(|expression| pieces.iter().map(move |piece| format!("{}{}", expression, piece)))
+                .collect();
+            expressions.extend(generation.iter().cloned());
+        }
+        expressions.extend(["4294967296", "1.99999999999", "12 34.5 6", "2(3)4", "1..2.3", "clamp(1,2,3)x"]
+            .iter().map(|expression| expression.to_string()));
 
-        let token_stream: Vec<ExpressionAtom> = OperationTokenTree::evaluate_tokens(
-            "1+(1+(2+4+5666))"
-        );
-        println!("{:?}", token_stream);
+        for expression in &expressions {
+            for &adjacency in &[NumberAdjacencyPolicy::Concatenate, NumberAdjacencyPolicy::Error, NumberAdjacencyPolicy::ImplicitMultiply] {
+                for custom_symbols in &[&[][..], &['#'][..]] {
+                    assert_eq!(OperationTokenTree::try_evaluate_tokens_with(expression, custom_symbols, adjacency),
+                        two_pass_tokens(expression, custom_symbols, adjacency), "tokenizing {:?}", expression);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_number_adjacency_policy() {
+        let tokens = |policy| OperationTokenTree::try_evaluate_tokens_with_policy("12 34", policy);
+        assert_eq!(tokens(NumberAdjacencyPolicy::Concatenate), Ok(vec![ExpressionAtom::Number(1234)]));
+        assert_eq!(tokens(NumberAdjacencyPolicy::Error), Err(TokenizeError::AdjacentNumbers { pos: 3 }));
+        assert_eq!(tokens(NumberAdjacencyPolicy::ImplicitMultiply),
+            Ok(vec![ExpressionAtom::Number(12), ExpressionAtom::ImplicitMultiply, ExpressionAtom::Number(34)]));
+
+        assert_eq!(OperationTokenTree::try_evaluate_tokens_with_policy("1.5 2", NumberAdjacencyPolicy::Error),
+            Err(TokenizeError::AdjacentNumbers { pos: 4 }));
+        assert_eq!(OperationTokenTree::try_evaluate_tokens_with_policy("12+34", NumberAdjacencyPolicy::Error),
+            OperationTokenTree::try_evaluate_tokens("12+34"));
+    }
+
+    #[test]
+    fn test_atom_display() {
+        let displayed = |atom: ExpressionAtom| (atom.to_string(), format!("{:?}", atom));
+        assert_eq!(displayed(ExpressionAtom::Number(42)), ("42".to_string(), "Number(42)".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Decimal { mantissa: 25, scale: 2 }),
+            ("0.25".to_string(), "Decimal { mantissa: 25, scale: 2 }".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Decimal { mantissa: 7, scale: 0 }),
+            ("7.".to_string(), "Decimal { mantissa: 7, scale: 0 }".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Operation(Operations::Add)), ("+".to_string(), "Operation(Add)".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Operation(Operations::Power)), ("^".to_string(), "Operation(Power)".to_string()));
+        assert_eq!(displayed(ExpressionAtom::LeftParenthesis), ("(".to_string(), "LeftParenthesis".to_string()));
+        assert_eq!(displayed(ExpressionAtom::RightParenthesis), (")".to_string(), "RightParenthesis".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Function(Function::Clamp)), ("clamp".to_string(), "Function(Clamp)".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Comma), (",".to_string(), "Comma".to_string()));
+        assert_eq!(displayed(ExpressionAtom::ImplicitMultiply), ("*".to_string(), "ImplicitMultiply".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Negate), ("neg".to_string(), "Negate".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Custom('@')), ("@".to_string(), "Custom('@')".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Variable('x')), ("x".to_string(), "Variable('x')".to_string()));
+        assert_eq!(displayed(ExpressionAtom::Start), ("start".to_string(), "Start".to_string()));
+        assert_eq!(displayed(ExpressionAtom::End), ("end".to_string(), "End".to_string()));
+    }
+
+    #[test]
+    fn test_atom_display_round_trip() {
+        let mut atoms = vec![
+            ExpressionAtom::Number(0), ExpressionAtom::Number(u32::MAX),
+            ExpressionAtom::Decimal { mantissa: 25, scale: 2 }, ExpressionAtom::Decimal { mantissa: 150, scale: 1 },
+            ExpressionAtom::Decimal { mantissa: 7, scale: 0 },
+            ExpressionAtom::LeftParenthesis, ExpressionAtom::RightParenthesis, ExpressionAtom::Comma,
+            ExpressionAtom::ImplicitMultiply, ExpressionAtom::Negate, ExpressionAtom::Custom('@'),
+            ExpressionAtom::Variable('x'), ExpressionAtom::Function(Function::Clamp), ExpressionAtom::Function(Function::Abs),
+            ExpressionAtom::Start, ExpressionAtom::End];
+        atoms.extend(Operations::all().iter().map(|&op| ExpressionAtom::Operation(op)));
+        for atom in atoms {
+            let expected = match atom {
+                ExpressionAtom::ImplicitMultiply => ExpressionAtom::Operation(Operations::Multiply),
+                _ => atom
+            };
+            assert_eq!(atom.to_string().parse::<ExpressionAtom>(), Ok(expected));
+        }
+
+        assert_eq!("ADD".parse::<ExpressionAtom>(), Ok(ExpressionAtom::Operation(Operations::Add)));
+        assert_eq!("Number(5)".parse::<ExpressionAtom>(), Ok(ExpressionAtom::Number(5)));
+        assert_eq!("Decimal(0.25)".parse::<ExpressionAtom>(), Ok(ExpressionAtom::Decimal { mantissa: 25, scale: 2 }));
+        assert_eq!("Decimal(7)".parse::<ExpressionAtom>(), Ok(ExpressionAtom::Decimal { mantissa: 7, scale: 0 }));
+        assert_eq!("Operation(MULTIPLY)".parse::<ExpressionAtom>(), Ok(ExpressionAtom::Operation(Operations::Multiply)));
+        assert_eq!("LPAREN".parse::<ExpressionAtom>(), Ok(ExpressionAtom::LeftParenthesis));
+        assert_eq!("IMPLICIT_MULTIPLY".parse::<ExpressionAtom>(), Ok(ExpressionAtom::ImplicitMultiply));
+        for text in &["", "PLUS", "Number(x)", "Number(-1)", "Number(4294967296)", "Decimal(.5)", "Number(5", "Function(sin)",
+            "4294967296", ".5", "1.2.3", "12a", "sin", "xy"] {
+            assert_eq!(text.parse::<ExpressionAtom>(), Err(ParseAtomError { text: text.to_string() }));
+        }
+    }
+
+    #[test]
+    fn test_literal_fits() {
+        assert!(literal_fits("4294967295"));
+        assert!(literal_fits("0042"));
+        assert!(!literal_fits("4294967296"));
+        assert!(!literal_fits("99999999999999999999"));
+        assert!(!literal_fits(""));
+        assert!(!literal_fits("12a"));
+        assert!(!literal_fits("-1"));
+    }
+
+    #[test]
+    fn test_tokenize_bounded() {
+        assert_eq!(tokenize_bounded("-5"),
+            Ok(vec![ExpressionAtom::Start, ExpressionAtom::Negate, ExpressionAtom::Number(5), ExpressionAtom::End]));
+        assert_eq!(tokenize_bounded(""), Ok(vec![ExpressionAtom::Start, ExpressionAtom::End]));
+
+        let unary = |expression| tokenize_bounded(expression).unwrap().iter()
+            .filter(|&&token| token == ExpressionAtom::Negate).count();
+        assert_eq!(unary("2-3"), 0);
+        assert_eq!(unary("2*-3"), 1);
+        assert_eq!(unary("(-1)-(-2)"), 2);
+        assert_eq!(unary("clamp(-1, -2, 3)"), 2);
+        assert_eq!(unary("--4"), 2);
+
+        let rpn = to_rpn(&tokenize_bounded("10+-3*2").unwrap()).unwrap();
+        assert_eq!(evaluate_rpn_in::<i64>(&rpn), Ok(4));
+    }
+
+    #[test]
+    fn test_merge_numbers() {
+        let merged = |atoms: Vec<ExpressionAtom>| merge_numbers(atoms.into_iter()).collect::<Vec<ExpressionAtom>>();
+        assert_eq!(merged(vec![ExpressionAtom::Number(1), ExpressionAtom::Number(2),
+            ExpressionAtom::Operation(Operations::Add), ExpressionAtom::Number(3)]),
+            vec![ExpressionAtom::Number(12), ExpressionAtom::Operation(Operations::Add), ExpressionAtom::Number(3)]);
+        assert_eq!(merged(vec![ExpressionAtom::Number(12), ExpressionAtom::Number(0), ExpressionAtom::Number(34)]),
+            vec![ExpressionAtom::Number(12034)]);
+        assert_eq!(merged(vec![ExpressionAtom::Number(4294967), ExpressionAtom::Number(295), ExpressionAtom::Number(1)]),
+            vec![ExpressionAtom::Number(4294967295), ExpressionAtom::Number(1)]);
+        assert_eq!(merged(vec![]), vec![]);
+
+        let digits = "1+23*456".chars().map(|character| match character.to_digit(10) {
+            Some(digit) => ExpressionAtom::Number(digit),
+            None => ExpressionAtom::Operation(turn_into_operation(character).unwrap())
+        });
+        assert_eq!(merge_numbers(digits).collect::<Vec<ExpressionAtom>>(), OperationTokenTree::evaluate_tokens("1+23*456"));
+    }
+
+    #[test]
+    fn test_lex() {
+        assert_eq!(lex("1+  2×3"), Ok(("1 + 2 * 3".to_string(), vec![ExpressionAtom::Number(1),
+            ExpressionAtom::Operation(Operations::Add), ExpressionAtom::Number(2),
+            ExpressionAtom::Operation(Operations::Multiply), ExpressionAtom::Number(3)])));
+        assert_eq!(lex("12 34"), Ok(("1234".to_string(), vec![ExpressionAtom::Number(1234)])));
+        assert_eq!(lex(""), Ok((String::new(), vec![])));
+
+        for expression in &["  clamp( 15,0 , 10 )", "2(x)+ 0.5", "(1 +2) *[3- 4]", "max(1,2)y"] {
+            let (normalized, tokens) = lex(expression).unwrap();
+            let typed: Vec<ExpressionAtom> = tokens.iter().cloned()
+                .filter(|&token| token != ExpressionAtom::ImplicitMultiply).collect();
+            let words: Vec<&str> = normalized.split(' ').collect();
+            assert_eq!(words.len(), typed.len(), "{}", expression);
+            for (word, token) in words.iter().zip(typed) {
+                assert_eq!(lex(word).unwrap().1, vec![token], "{} in {}", word, expression);
+            }
+            assert_eq!(lex(&normalized).unwrap(), (normalized.clone(), tokens));
+        }
+    }
+
+    #[test]
+    fn test_number_from_digits() {
+        assert_eq!(number_from_digits(&[1, 2, 3]), Some(ExpressionAtom::Number(123)));
+        assert_eq!(number_from_digits(&[0, 0, 7]), Some(ExpressionAtom::Number(7)));
+        assert_eq!(number_from_digits(&[4, 2, 9, 4, 9, 6, 7, 2, 9, 5]), Some(ExpressionAtom::Number(MAX_LITERAL)));
+        assert_eq!(number_from_digits(&[4, 2, 9, 4, 9, 6, 7, 2, 9, 6]), None);
+        assert_eq!(number_from_digits(&[1; 11]), None);
+        assert_eq!(number_from_digits(&[1, 10]), None);
+        assert_eq!(number_from_digits(&[]), None);
+    }
+
+    #[test]
+    fn test_division_is_exact() {
+        assert!(division_is_exact(6, 2));
+        assert!(!division_is_exact(7, 2));
+        assert!(division_is_exact(0, 5));
+        assert!(!division_is_exact(6, 0));
+    }
+
+    #[test]
+    fn test_is_perfect_power() {
+        assert_eq!(is_perfect_power(8), Some((2, 3)));
+        assert_eq!(is_perfect_power(16), Some((2, 4)));
+        assert_eq!(is_perfect_power(7), None);
+        assert_eq!(is_perfect_power(1), Some((1, 2)));
+        assert_eq!(is_perfect_power(0), Some((0, 2)));
+        assert_eq!(is_perfect_power(36), Some((6, 2)));
+        assert_eq!(is_perfect_power(1 << 31), Some((2, 31)));
+        assert_eq!(is_perfect_power(65535 * 65535), Some((65535, 2)));
+        assert_eq!(is_perfect_power(u32::MAX), None);
+    }
+
+    #[test]
+    fn test_validate_parentheses() {
+        assert_eq!(validate_parentheses("(1+2)"), Ok(()));
+        assert_eq!(validate_parentheses("[1+{2*(3)}]"), Ok(()));
+        assert_eq!(validate_parentheses("[1+2)"), Err(EvalError::MismatchedBrackets { open: '[', close: ')', pos: 4 }));
+        assert_eq!(validate_parentheses("({1+2)}"), Err(EvalError::MismatchedBrackets { open: '{', close: ')', pos: 5 }));
+        assert_eq!(validate_parentheses("(1+2"), Err(EvalError::MismatchedParentheses));
+        assert_eq!(validate_parentheses("1+2]"), Err(EvalError::MismatchedParentheses));
+
+        assert_eq!(evaluate("[1+2]*{3}"), Ok(9));
+        assert_eq!(evaluate("[1+2)"), Err(EvalError::MismatchedBrackets { open: '[', close: ')', pos: 4 }));
+    }
+
+    #[test]
+    fn test_bracket_kinds_checked_when_tokenizing() {
+        let mismatched = EvalError::MismatchedBrackets { open: '[', close: ')', pos: 4 };
+        assert_eq!(OperationTokenTree::try_evaluate_tokens("[1+2)*3"),
+            Err(TokenizeError::MismatchedBrackets { open: '[', close: ')', pos: 4 }));
+        assert_eq!(Tokenizer::new("[1+2)*3").nth(4), Some(Err(TokenizeError::MismatchedBrackets { open: '[', close: ')', pos: 4 })));
+        assert_eq!(evaluate_value("[1+2)*3"), Err(mismatched));
+        assert_eq!(evaluate_with_options("[1+2)*3", &EvalOptions::default()), Err(mismatched));
+        assert_eq!(evaluate_saturating("[1+2)*3"), Err(mismatched));
+        assert_eq!(evaluate_float("[1+2)*3"), Err(mismatched));
+        assert_eq!(evaluate_float_with("[1+2)*3", FloatMode::default()), Err(mismatched));
+        assert_eq!(evaluate_banker_rounded("[1+2)*3", 2), Err(mismatched));
+        assert_eq!(evaluate_with_precedence_spec("[1+2)*3", "^ ; * / ; + -"), Err(mismatched));
+        assert!(evaluate_units("[1m+2m)*3").is_err());
+        assert_eq!(evaluate_tape("[1+2)"), Err(mismatched));
+        assert_eq!(normalize("[1+2)*3"), Err(mismatched));
+        assert_eq!(explicit_form("[1+2)*3"), Err(mismatched));
+        assert_eq!(to_latex("[1+2)*3"), Err(mismatched));
+        assert_eq!(Session::new().evaluate("[1+2)*3"), Err(mismatched));
+        assert_eq!(evaluate("{[1+2]*(3)}"), Ok(9));
+    }
+
+    #[test]
+    fn test_normalize_operators() {
+        assert_eq!(normalize_operators("2\u{00D7}3"), "2*3");
+        assert_eq!(normalize_operators("2**3\u{2212}1"), "2^3-1");
+        assert_eq!(OperationTokenTree::evaluate_tokens("2\u{00D7}3"), OperationTokenTree::evaluate_tokens("2*3"));
+        assert_eq!(OperationTokenTree::evaluate_tokens("2**3"), OperationTokenTree::evaluate_tokens("2^3"));
     }
 }
@@ -0,0 +1,231 @@
+use std::vec::Vec;
+
+use {Operations, EvalError, Field, Value, OperationTokenTree, evaluate, try_calculate, validate_parentheses};
+use evaluator::{to_rpn, reduce_rpn_with, Condition};
+
+/// The first of the letters standing in for matrix literals while the rest of an expression is
+/// tokenized. These are CJK ideographs, which nobody writes as a variable by accident.
+const PLACEHOLDER_BASE: u32 = 0x4E00;
+
+/// The cells of a matrix, row by row
+type Rows = Vec<Vec<u32>>;
+
+impl Condition for Value {
+    fn is_true(&self) -> bool {
+        match *self {
+            Value::Num(number) => number != 0,
+            Value::Bool(truth) => truth,
+            Value::List(ref values) => values.iter().any(|&value| value != 0),
+            Value::Matrix(ref rows) => rows.iter().flatten().any(|&cell| cell != 0)
+        }
+    }
+}
+
+/// Calculates two values where either side may be a matrix. Matrices of the same size add and
+/// subtract cell by cell, a matrix multiplies another with as many rows as it has columns, and a
+/// number multiplies every cell of a matrix. Any other mix of numbers and matrices is a
+/// DimensionMismatch.
+///     [[1,2],[3,4]] + [[5,6],[7,8]] is [[6,8],[10,12]]
+///     [[1,2],[3,4]] * [[5,6],[7,8]] is [[19,22],[43,50]]
+pub fn calculate_value(lval: Value, rval: Value, operation: Operations) -> Result<Value, EvalError> {
+    match (lval, rval, operation) {
+        (Value::Num(left), Value::Num(right), _) => try_calculate(left, right, operation).map(Value::Num),
+        (Value::Matrix(left), Value::Matrix(right), Operations::Add | Operations::Subtract) =>
+            cell_by_cell(&left, &right, operation).map(Value::Matrix),
+        (Value::Matrix(left), Value::Matrix(right), Operations::Multiply) =>
+            multiply(&left, &right).map(Value::Matrix),
+        (Value::Num(scale), Value::Matrix(rows), Operations::Multiply) |
+        (Value::Matrix(rows), Value::Num(scale), Operations::Multiply) =>
+            cell_by_cell(&rows, &vec![vec![scale; width(&rows)]; rows.len()], operation).map(Value::Matrix),
+        (Value::Matrix(_), _, Operations::Add | Operations::Subtract | Operations::Multiply) |
+        (_, Value::Matrix(_), Operations::Add | Operations::Subtract | Operations::Multiply) =>
+            Err(EvalError::DimensionMismatch),
+        _ => Err(EvalError::UnsupportedOperation(operation))
+    }
+}
+
+/// Evaluates an expression that may contain matrix literals, written as rows of comma separated
+/// cells in square brackets. Each cell can be an expression of its own. The result is a
+/// Value::Matrix, or a Value::Num when no matrix is left over.
+///     "[[1,2],[3,4]] + [[5,6],[7,8]]" evaluates to Matrix([[6, 8], [10, 12]])
+///     "[[1,2,3]] * [[1],[2],[3]]" evaluates to Matrix([[14]])
+///     "[[1,2],[3,4]] + [[1,2,3]]" is DimensionMismatch
+/// Brackets that do not hold a matrix, like the ones in [1+2]*3, still group.
+pub fn evaluate_matrix(expression: &str) -> Result<Value, EvalError> {
+    validate_parentheses(expression)?;
+    let (matrices, placeholder) = extract_matrices(expression)?;
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(&placeholder)?)?;
+
+    reduce_rpn_with(&rpn, |mantissa, scale| u32::from_decimal(mantissa, scale).map(Value::Num), calculate_value,
+        |function, arguments| {
+            let numbers = arguments.iter().map(|argument| match *argument {
+                Value::Num(number) => Ok(number),
+                _ => Err(EvalError::UnsupportedFunction(function))
+            }).collect::<Result<Vec<u32>, EvalError>>()?;
+            u32::call(function, &numbers).map(Value::Num)
+        },
+        |symbol, _, _| Err(EvalError::UnknownOperator(symbol)),
+        |name| (name as u32).checked_sub(PLACEHOLDER_BASE)
+            .and_then(|index| matrices.get(index as usize))
+            .map(|rows| Value::Matrix(rows.clone()))
+            .ok_or(EvalError::UnboundVariable(name)))
+}
+
+/// Pulls every matrix literal out of an expression, replacing each with a placeholder letter the
+/// tokenizer reads as a variable. A [ starts a matrix when the next thing inside it is another [.
+fn extract_matrices(expression: &str) -> Result<(Vec<Rows>, String), EvalError> {
+    let characters: Vec<char> = expression.chars().collect();
+    let mut matrices: Vec<Rows> = vec![];
+    let mut placeholder = String::new();
+    let mut index = 0;
+    while index < characters.len() {
+        let opens_matrix = characters[index] == '['
+            && characters[index + 1..].iter().find(|c| !c.is_whitespace()) == Some(&'[');
+        if !opens_matrix {
+            placeholder.push(characters[index]);
+            index += 1;
+            continue;
+        }
+
+        let end = matching_bracket(&characters, index).ok_or(EvalError::MismatchedParentheses)?;
+        let literal: String = characters[index + 1..end].iter().collect();
+        let name = std::char::from_u32(PLACEHOLDER_BASE + matrices.len() as u32)
+            .ok_or(EvalError::Overflow)?;
+        matrices.push(parse_rows(&literal)?);
+        // Spaces keep the placeholder from joining a word next to it, like the x in [[1]]x
+        placeholder.push(' ');
+        placeholder.push(name);
+        placeholder.push(' ');
+        index = end + 1;
+    }
+    Ok((matrices, placeholder))
+}
+
+/// The index of the ] closing the [ at start
+fn matching_bracket(characters: &[char], start: usize) -> Option<usize> {
+    let mut depth: usize = 0;
+    for (index, &character) in characters.iter().enumerate().skip(start) {
+        match character {
+            '[' => depth += 1,
+            ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            },
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Reads the inside of a matrix literal, like the [1,2],[3,4] of [[1,2],[3,4]]. Every row has to
+/// be in brackets and have the same number of cells.
+fn parse_rows(literal: &str) -> Result<Rows, EvalError> {
+    let rows = split_cells(literal).iter()
+        .map(|row| {
+            let cells = row.trim().strip_prefix('[').and_then(|row| row.strip_suffix(']'))
+                .ok_or(EvalError::DimensionMismatch)?;
+            split_cells(cells).iter().map(|cell| evaluate(cell)).collect::<Result<Vec<u32>, EvalError>>()
+        })
+        .collect::<Result<Rows, EvalError>>()?;
+
+    if rows.iter().any(|row| row.len() != width(&rows)) {
+        return Err(EvalError::DimensionMismatch);
+    }
+    Ok(rows)
+}
+
+/// Splits text at the commas outside of any brackets, so clamp(1,2,3) stays one cell
+fn split_cells(text: &str) -> Vec<&str> {
+    let mut cells = vec![];
+    let mut depth: usize = 0;
+    let mut start = 0;
+    for (index, character) in text.char_indices() {
+        match character {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                cells.push(&text[start..index]);
+                start = index + 1;
+            },
+            _ => {}
+        }
+    }
+    cells.push(&text[start..]);
+    cells
+}
+
+/// The number of columns in a matrix
+fn width(rows: &[Vec<u32>]) -> usize {
+    rows.first().map_or(0, Vec::len)
+}
+
+/// Calculates each cell of left with the cell in the same place of right
+fn cell_by_cell(left: &[Vec<u32>], right: &[Vec<u32>], operation: Operations) -> Result<Rows, EvalError> {
+    if left.len() != right.len() || width(left) != width(right) {
+        return Err(EvalError::DimensionMismatch);
+    }
+    left.iter().zip(right)
+        .map(|(left_row, right_row)| left_row.iter().zip(right_row)
+            .map(|(&left_cell, &right_cell)| try_calculate(left_cell, right_cell, operation))
+            .collect())
+        .collect()
+}
+
+/// The matrix product of left and right
+fn multiply(left: &[Vec<u32>], right: &[Vec<u32>]) -> Result<Rows, EvalError> {
+    if width(left) != right.len() {
+        return Err(EvalError::DimensionMismatch);
+    }
+    left.iter()
+        .map(|left_row| (0..width(right))
+            .map(|column| left_row.iter().zip(right)
+                .try_fold(0u32, |sum, (&cell, right_row)| {
+                    let product = cell.checked_mul(right_row[column]).ok_or(EvalError::Overflow)?;
+                    sum.checked_add(product).ok_or(EvalError::Overflow)
+                }))
+            .collect())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(rows: &[&[u32]]) -> Value {
+        Value::Matrix(rows.iter().map(|row| row.to_vec()).collect())
+    }
+
+    #[test]
+    fn test_matrix_addition() {
+        assert_eq!(evaluate_matrix("[[1,2],[3,4]] + [[5,6],[7,8]]"), Ok(matrix(&[&[6, 8], &[10, 12]])));
+        assert_eq!(evaluate_matrix("[[5,6],[7,8]] - [[1,2],[3,4]]"), Ok(matrix(&[&[4, 4], &[4, 4]])));
+        assert_eq!(evaluate_matrix("[[1,2],[3,4]] - [[5,6],[7,8]]"), Err(EvalError::Underflow));
+    }
+
+    #[test]
+    fn test_matrix_multiplication() {
+        assert_eq!(evaluate_matrix("[[1,2],[3,4]] * [[5,6],[7,8]]"), Ok(matrix(&[&[19, 22], &[43, 50]])));
+        assert_eq!(evaluate_matrix("[[1,2,3]] * [[1],[2],[3]]"), Ok(matrix(&[&[14]])));
+        assert_eq!(evaluate_matrix("2*[[1,2],[3,4]]"), Ok(matrix(&[&[2, 4], &[6, 8]])));
+        assert_eq!(evaluate_matrix("([[1,0],[0,1]] + [[1,1],[1,1]]) * [[2,0],[0,2]]"),
+            Ok(matrix(&[&[4, 2], &[2, 4]])));
+    }
+
+    #[test]
+    fn test_matrix_dimension_mismatch() {
+        assert_eq!(evaluate_matrix("[[1,2],[3,4]] + [[1,2,3]]"), Err(EvalError::DimensionMismatch));
+        assert_eq!(evaluate_matrix("[[1,2,3]] * [[1,2,3]]"), Err(EvalError::DimensionMismatch));
+        assert_eq!(evaluate_matrix("[[1,2],[3]]"), Err(EvalError::DimensionMismatch));
+        assert_eq!(evaluate_matrix("[[1,2],[3,4]] + 1"), Err(EvalError::DimensionMismatch));
+    }
+
+    #[test]
+    fn test_matrix_cells_and_grouping() {
+        assert_eq!(evaluate_matrix("[[1+1, clamp(9,0,3)]]"), Ok(matrix(&[&[2, 3]])));
+        assert_eq!(evaluate_matrix("[1+2]*3"), Ok(Value::Num(9)));
+        assert_eq!(evaluate_matrix("[[1,2],[3,4]] / [[1,2],[3,4]]"),
+            Err(EvalError::UnsupportedOperation(Operations::Divide)));
+    }
+}
@@ -0,0 +1,214 @@
+use std::convert::TryFrom;
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, OperationTokenTree, EvalError, TableError};
+use {precedence, is_right_associative, turn_into_operation};
+use evaluator::{to_rpn_with, evaluate_rpn};
+
+/// Which side a chain of the same operation groups from
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Associativity {
+
+    /// 8-3-2 is (8-3)-2
+    Left,
+
+    /// 2^3^2 is 2^(3^2)
+    Right
+}
+
+/// How tightly one operation binds, and which way it groups
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OperatorEntry {
+    pub operation: Operations,
+    pub precedence: u8,
+    pub associativity: Associativity
+}
+
+/// How tightly an operator registered at runtime binds, and which way it groups
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CustomOperatorEntry {
+    pub symbol: char,
+    pub precedence: u8,
+    pub associativity: Associativity
+}
+
+/// The precedence and associativity of every operation, so order of operations can be changed.
+/// OperatorTable::default() is the crate's usual order of operations.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OperatorTable {
+    entries: Vec<OperatorEntry>,
+    custom: Vec<CustomOperatorEntry>
+}
+
+impl OperatorTable {
+    /// Sets how tightly an operation binds, and which way it groups. Higher binds tighter.
+    pub fn set(&mut self, operation: Operations, precedence: u8, associativity: Associativity) {
+        self.entries.retain(|entry| entry.operation != operation);
+        self.entries.push(OperatorEntry { operation, precedence, associativity });
+    }
+
+    fn entry(&self, operation: Operations) -> Option<&OperatorEntry> {
+        self.entries.iter().find(|entry| entry.operation == operation)
+    }
+
+    /// How tightly an operation binds. Operations missing from the table bind loosest.
+    pub fn precedence(&self, operation: Operations) -> u8 {
+        self.entry(operation).map_or(0, |entry| entry.precedence)
+    }
+
+    pub fn is_right_associative(&self, operation: Operations) -> bool {
+        self.entry(operation).is_some_and(|entry| entry.associativity == Associativity::Right)
+    }
+
+    /// Adds an operator that is not one of the Operations, written as symbol. A symbol can only be
+    /// added once, and cannot be one of the Operations or a character expressions are already
+    /// made of, like a digit, a letter, a bracket, a comma, a point, or a space.
+    pub fn set_custom(&mut self, symbol: char, precedence: u8, associativity: Associativity) -> Result<(), TableError> {
+        if let Some(operation) = turn_into_operation(symbol) {
+            return Err(TableError::ShadowsOperation { symbol, operation });
+        }
+        if symbol.is_alphanumeric() || symbol.is_whitespace() || "()[]{},.".contains(symbol) {
+            return Err(TableError::ReservedSymbol(symbol));
+        }
+        if self.custom_entry(symbol).is_some() {
+            return Err(TableError::DuplicateSymbol(symbol));
+        }
+        self.custom.push(CustomOperatorEntry { symbol, precedence, associativity });
+        Ok(())
+    }
+
+    fn custom_entry(&self, symbol: char) -> Option<&CustomOperatorEntry> {
+        self.custom.iter().find(|entry| entry.symbol == symbol)
+    }
+
+    /// The symbols of every custom operator
+    pub fn custom_symbols(&self) -> Vec<char> {
+        self.custom.iter().map(|entry| entry.symbol).collect()
+    }
+
+    /// The precedence and associativity of an Operation or Custom atom. Anything missing from the
+    /// table binds loosest and groups left.
+    pub(crate) fn binding(&self, atom: ExpressionAtom) -> (u8, Associativity) {
+        match atom {
+            ExpressionAtom::Operation(operation) => self.entry(operation)
+                .map_or((0, Associativity::Left), |entry| (entry.precedence, entry.associativity)),
+            ExpressionAtom::Custom(symbol) => self.custom_entry(symbol)
+                .map_or((0, Associativity::Left), |entry| (entry.precedence, entry.associativity)),
+            _ => (0, Associativity::Left)
+        }
+    }
+
+    /// Checks the table for operators at the same precedence that group in different directions.
+    /// Custom symbols are checked as they are added, by set_custom.
+    pub fn validate(&self) -> Result<(), TableError> {
+        let levels: Vec<(u8, Associativity)> = self.entries.iter().map(|entry| (entry.precedence, entry.associativity))
+            .chain(self.custom.iter().map(|entry| (entry.precedence, entry.associativity)))
+            .collect();
+        for (index, &(precedence, associativity)) in levels.iter().enumerate() {
+            let conflicting = levels[..index].iter()
+                .any(|&(other, other_associativity)| other == precedence && other_associativity != associativity);
+            if conflicting {
+                return Err(TableError::MixedAssociativity { precedence });
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a table from groups of operation characters separated by ';', highest first.
+    ///     "^ ; * / ; + -" is the usual order of arithmetic
+    /// Operations not in the spec bind looser than all of them, in their usual order. Precedences
+    /// have to fit in a u8, so a spec of more than 155 groups is InvalidPrecedenceSpec.
+    pub fn from_spec(spec: &str) -> Result<OperatorTable, EvalError> {
+        let mut table = OperatorTable::default();
+        let groups: Vec<&str> = spec.split(';').collect();
+
+        for (index, group) in groups.iter().enumerate() {
+            for symbol in group.split_whitespace() {
+                let mut characters = symbol.chars();
+                let operation = match (characters.next().and_then(turn_into_operation), characters.next()) {
+                    (Some(operation), None) => operation,
+                    _ => return Err(EvalError::InvalidPrecedenceSpec)
+                };
+                let associativity = table.entry(operation).map_or(Associativity::Left, |entry| entry.associativity);
+                let precedence = u8::try_from(100 + groups.len() - index).map_err(|_| EvalError::InvalidPrecedenceSpec)?;
+                table.set(operation, precedence, associativity);
+            }
+        }
+        Ok(table)
+    }
+}
+
+impl Default for OperatorTable {
+    fn default() -> OperatorTable {
+        OperatorTable {
+            entries: Operations::all().iter().map(|&operation| OperatorEntry {
+                operation,
+                precedence: precedence(operation),
+                associativity: if is_right_associative(operation) { Associativity::Right } else { Associativity::Left }
+            }).collect(),
+            custom: vec![]
+        }
+    }
+}
+
+/// Evaluates an expression with the order of operations given as a spec, like "^ ; * / ; + -".
+/// See OperatorTable::from_spec.
+pub fn evaluate_with_precedence_spec(expression: &str, spec: &str) -> Result<u32, EvalError> {
+    let table = OperatorTable::from_spec(spec)?;
+    evaluate_rpn(&to_rpn_with(&OperationTokenTree::try_evaluate_tokens(expression)?, &table)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evaluate;
+
+    #[test]
+    fn test_default_spec() {
+        for expression in &["1+2*3", "2^3^2*2", "8-3-2", "1+2>2*1", "10/5*2"] {
+            assert_eq!(evaluate_with_precedence_spec(expression, "^ ; * / ; + -"), evaluate(expression));
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut table = OperatorTable::default();
+        assert_eq!(table.validate(), Ok(()));
+        assert_eq!(table.set_custom('@', precedence(Operations::Multiply), Associativity::Left), Ok(()));
+        assert_eq!(table.validate(), Ok(()));
+
+        let mut table = OperatorTable::default();
+        assert_eq!(table.set_custom('#', precedence(Operations::Add), Associativity::Right), Ok(()));
+        assert_eq!(table.validate(), Err(TableError::MixedAssociativity { precedence: precedence(Operations::Add) }));
+
+        assert_eq!(OperatorTable::from_spec("+ - ; * /").unwrap().validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_set_custom_rejects_conflicts() {
+        let mut table = OperatorTable::default();
+        assert_eq!(table.set_custom('@', 50, Associativity::Left), Ok(()));
+        assert_eq!(table.set_custom('@', 60, Associativity::Right), Err(TableError::DuplicateSymbol('@')));
+        assert_eq!(table.binding(ExpressionAtom::Custom('@')), (50, Associativity::Left));
+        assert_eq!(table.set_custom('+', 50, Associativity::Left),
+            Err(TableError::ShadowsOperation { symbol: '+', operation: Operations::Add }));
+        for &symbol in &['7', 'x', '(', ']', ',', '.', ' '] {
+            assert_eq!(table.set_custom(symbol, 50, Associativity::Left), Err(TableError::ReservedSymbol(symbol)));
+        }
+        assert_eq!(table.custom_symbols(), vec!['@']);
+        assert_eq!(table.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_custom_spec() {
+        assert_eq!(evaluate_with_precedence_spec("1+2*3", "+ - ; * /"), Ok(9));
+        assert_eq!(evaluate_with_precedence_spec("2*3^2", "* ; ^"), Ok(36));
+        assert_eq!(evaluate_with_precedence_spec("8-3-2", "+ - * / ^"), Ok(3));
+        assert_eq!(evaluate_with_precedence_spec("1+2", "+ ; $"), Err(EvalError::InvalidPrecedenceSpec));
+        assert_eq!(evaluate_with_precedence_spec("1+2", "+*"), Err(EvalError::InvalidPrecedenceSpec));
+
+        // Empty groups still count, and past 155 of them the precedences no longer fit
+        assert_eq!(evaluate_with_precedence_spec("1+2*3", &format!("* ;{} +", ";".repeat(153))), Ok(7));
+        assert_eq!(evaluate_with_precedence_spec("1+2*3", &format!("* ;{} +", ";".repeat(154))), Err(EvalError::InvalidPrecedenceSpec));
+    }
+}
@@ -0,0 +1,199 @@
+use {Operations, OperationTokenTree, EvalError, Field};
+use {try_calculate};
+use evaluator::{to_rpn, reduce_rpn};
+
+/// What to do when a subtraction would go below zero
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum SubtractionMode {
+
+    /// 2-5 is an Underflow error
+    #[default]
+    Error,
+
+    /// 2-5 is 0
+    Saturate,
+
+    /// 2-5 is 4294967293
+    Wrap
+}
+
+/// What to do when an addition, multiplication, or power is too large for a u32
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum OverflowMode {
+
+    /// 4294967295+1 is an Overflow error
+    #[default]
+    Error,
+
+    /// 4294967295+1 is 4294967295
+    Saturate
+}
+
+/// What to do with two numbers that have only spaces or unknown characters between them
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum NumberAdjacencyPolicy {
+
+    /// 12 34 is 1234
+    #[default]
+    Concatenate,
+
+    /// 12 34 is an AdjacentNumbers error
+    Error,
+
+    /// 12 34 is 408
+    ImplicitMultiply
+}
+
+/// What 0^0 is, since mathematicians do not agree on it
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Default)]
+pub enum ZeroPowZero {
+
+    /// 0^0 is 1, like u32::pow
+    #[default]
+    One,
+
+    /// 0^0 is 0
+    Zero,
+
+    /// 0^0 is an Indeterminate error
+    Error
+}
+
+/// Settings that change how an expression is evaluated
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+pub struct EvalOptions {
+    pub subtraction: SubtractionMode,
+    pub overflow: OverflowMode,
+
+    /// The largest number that may be written in the expression, and that an operation will accept
+    /// on either side, or None for no limit
+    pub max_operand: Option<u32>,
+
+    /// What two numbers with only spaces or unknown characters between them are, like 12 34
+    pub adjacent_numbers: NumberAdjacencyPolicy,
+
+    /// What 0^0 calculates to
+    pub zero_pow_zero: ZeroPowZero
+}
+
+/// Calculates two numbers like try_calculate, but following the options
+pub fn calculate_with_options(lval: u32, rval: u32, operation: Operations, options: &EvalOptions)
+    -> Result<u32, EvalError> {
+    if let Some(max) = options.max_operand {
+        if let Some(&operand) = [lval, rval].iter().find(|&&operand| operand > max) {
+            return Err(EvalError::OperandTooLarge(operand));
+        }
+    }
+
+    match (operation, options.subtraction) {
+        (Operations::Power, _) if lval == 0 && rval == 0 => match options.zero_pow_zero {
+            ZeroPowZero::One => Ok(1),
+            ZeroPowZero::Zero => Ok(0),
+            ZeroPowZero::Error => Err(EvalError::Indeterminate)
+        },
+        (Operations::Subtract, SubtractionMode::Saturate) => Ok(lval.saturating_sub(rval)),
+        (Operations::Subtract, SubtractionMode::Wrap) => Ok(lval.wrapping_sub(rval)),
+        (Operations::Add, _) if options.overflow == OverflowMode::Saturate => Ok(lval.saturating_add(rval)),
+        (Operations::Multiply, _) if options.overflow == OverflowMode::Saturate => Ok(lval.saturating_mul(rval)),
+        (Operations::Power, _) if options.overflow == OverflowMode::Saturate => Ok(lval.saturating_pow(rval)),
+        _ => try_calculate(lval, rval, operation)
+    }
+}
+
+/// Tokenizes and evaluates an expression following the options
+pub fn evaluate_with_options(expression: &str, options: &EvalOptions) -> Result<u32, EvalError> {
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens_with_policy(expression, options.adjacent_numbers)?)?;
+    let literal = |mantissa: u32, scale: u32| match u32::from_decimal(mantissa, scale)? {
+        value if options.max_operand.is_some_and(|max| value > max) => Err(EvalError::OperandTooLarge(value)),
+        value => Ok(value)
+    };
+    reduce_rpn(&rpn, literal, |left, right, op| calculate_with_options(left, right, op, options), u32::call)
+}
+
+/// Evaluates an expression where every step that would overflow or go below zero is clamped
+/// to the nearest u32 instead. Division by zero is still an error.
+///     "(4294967295+1)-5" is 4294967290
+pub fn evaluate_saturating(expression: &str) -> Result<u32, EvalError> {
+    evaluate_with_options(expression, &EvalOptions {
+        subtraction: SubtractionMode::Saturate,
+        overflow: OverflowMode::Saturate,
+        ..EvalOptions::default()
+    })
+}
+
+/// Evaluates an expression, refusing any number written in it or any operation with a value
+/// larger than max_operand on either side. Intermediate results count, so the limit also keeps
+/// results from growing past it.
+///     "2000000+1" and "2000000" with a limit of 1000000 are OperandTooLarge(2000000)
+pub fn evaluate_bounded(expression: &str, max_operand: u32) -> Result<u32, EvalError> {
+    evaluate_with_options(expression, &EvalOptions { max_operand: Some(max_operand), ..EvalOptions::default() })
+}
+
+/// Evaluates an expression where any step that would overflow or go below zero is an error
+pub fn evaluate_checked(expression: &str) -> Result<u32, EvalError> {
+    evaluate_with_options(expression, &EvalOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_subtraction(subtraction: SubtractionMode) -> EvalOptions {
+        EvalOptions { subtraction, ..EvalOptions::default() }
+    }
+
+    #[test]
+    fn test_subtraction_modes() {
+        assert_eq!(evaluate_with_options("2-5", &EvalOptions::default()), Err(EvalError::Underflow));
+        assert_eq!(evaluate_with_options("2-5", &with_subtraction(SubtractionMode::Saturate)), Ok(0));
+        assert_eq!(evaluate_with_options("2-5", &with_subtraction(SubtractionMode::Wrap)), Ok(u32::MAX - 2));
+        assert_eq!(evaluate_with_options("10-(2-5)", &with_subtraction(SubtractionMode::Wrap)), Ok(13));
+        assert_eq!(evaluate_with_options("5-2", &with_subtraction(SubtractionMode::Saturate)), Ok(3));
+    }
+
+    #[test]
+    fn test_number_adjacency() {
+        let with_adjacency = |adjacent_numbers| EvalOptions { adjacent_numbers, ..EvalOptions::default() };
+        assert_eq!(evaluate_with_options("12 34", &with_adjacency(NumberAdjacencyPolicy::Concatenate)), Ok(1234));
+        assert_eq!(evaluate_with_options("12 34", &with_adjacency(NumberAdjacencyPolicy::Error)), Err(EvalError::AdjacentNumbers { pos: 3 }));
+        assert_eq!(evaluate_with_options("12 34", &with_adjacency(NumberAdjacencyPolicy::ImplicitMultiply)), Ok(408));
+        assert_eq!(evaluate_with_options("1 2 3+4", &with_adjacency(NumberAdjacencyPolicy::ImplicitMultiply)), Ok(10));
+        assert_eq!(evaluate_with_options("12+34", &with_adjacency(NumberAdjacencyPolicy::Error)), Ok(46));
+    }
+
+    #[test]
+    fn test_zero_pow_zero() {
+        let with_zero_pow_zero = |zero_pow_zero| EvalOptions { zero_pow_zero, ..EvalOptions::default() };
+        assert_eq!(evaluate_with_options("0^0", &EvalOptions::default()), Ok(1));
+        assert_eq!(evaluate_with_options("0^0", &with_zero_pow_zero(ZeroPowZero::One)), Ok(1));
+        assert_eq!(evaluate_with_options("0^0", &with_zero_pow_zero(ZeroPowZero::Zero)), Ok(0));
+        assert_eq!(evaluate_with_options("0^0", &with_zero_pow_zero(ZeroPowZero::Error)), Err(EvalError::Indeterminate));
+        assert_eq!(evaluate_with_options("2+(1-1)^(3-3)", &with_zero_pow_zero(ZeroPowZero::Zero)), Ok(2));
+        assert_eq!(evaluate_with_options("0^2+3^0", &with_zero_pow_zero(ZeroPowZero::Error)), Ok(1));
+    }
+
+    #[test]
+    fn test_evaluate_bounded() {
+        assert_eq!(evaluate_bounded("2000000+1", 1000000), Err(EvalError::OperandTooLarge(2000000)));
+        assert_eq!(evaluate_bounded("1+2000000", 1000000), Err(EvalError::OperandTooLarge(2000000)));
+        assert_eq!(evaluate_bounded("1000000+1", 1000000), Ok(1000001));
+        assert_eq!(evaluate_bounded("(1000*1001)*2", 1000000), Err(EvalError::OperandTooLarge(1001000)));
+        assert_eq!(evaluate_bounded("2000000", 1000000), Err(EvalError::OperandTooLarge(2000000)));
+        assert_eq!(evaluate_bounded("clamp(2000000, 0, 5)", 1000000), Err(EvalError::OperandTooLarge(2000000)));
+        assert_eq!(evaluate_bounded("-(2000000)", 1000000), Err(EvalError::OperandTooLarge(2000000)));
+        assert_eq!(evaluate_bounded("1000000", 1000000), Ok(1000000));
+    }
+
+    #[test]
+    fn test_overflow_through_expression() {
+        let expression = "((65536*65536)+10)-(2-5)*3";
+        assert_eq!(evaluate_saturating(expression), Ok(u32::MAX));
+        assert_eq!(evaluate_checked(expression), Err(EvalError::Overflow));
+
+        assert_eq!(evaluate_saturating("(2^40-5)/2"), Ok((u32::MAX - 5) / 2));
+        assert_eq!(evaluate_saturating("1+(2-5)*3"), Ok(1));
+        assert_eq!(evaluate_checked("1+(2-5)*3"), Err(EvalError::Underflow));
+        assert_eq!(evaluate_saturating("1/0"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_checked("2+3*4"), Ok(14));
+    }
+}
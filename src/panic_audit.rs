@@ -0,0 +1,70 @@
+use std::panic::catch_unwind;
+
+use {evaluate, evaluate_float, evaluate_signed, evaluate_calc, debug_rpn, reduction_step_count};
+use {parse_expression, simplify_expression, explain, OperationTokenTree};
+
+/// How deeply the nested inputs go
+const DEPTH: usize = 500;
+
+fn adversarial_inputs() -> Vec<String> {
+    let mut inputs: Vec<String> = [
+        // Empty and blank
+        "", " ", "\t\n", "()", "(())", "( )",
+        // Unbalanced
+        "(", ")", ")(", "((1)", "1)", "(1+2))", "[1+2)", "{1]", "(1,2",
+        // Missing pieces
+        "+", "1+", "*1", "1**", "-", "--", "1 2", ",", "1,2", "(,)", "^", "1^", "&|",
+        // Too large
+        "4294967295+1", "4294967296", "99999999999999999999999", "4294967295*4294967295", "2^4294967295",
+        "4294967295^4294967295", "65536*65536", "0.99999999999", "1.4294967296", "sum(0, 4294967295)",
+        "product(1, 4294967295)",
+        // Below zero and undefined
+        "0-1", "1-4294967295", "1/0", "0/0", "0^0", "(1-2)^2", "-4294967295-1", "0-(0-1)",
+        // Malformed numbers
+        ".", "..", "1..2", "1.2.3", "1.", ".5.", "1e5", "0x10",
+        // Characters nobody expects
+        "$", "1$2", "€", "é+1", "\u{0}", "\u{feff}1", "1\u{200b}+2", "🙂", "x", "xyz", "inf-inf", "NaN",
+        // Functions used wrongly
+        "clamp(", "clamp()", "clamp(1,2)", "clamp(1,2,3,4)", "if(1,2)", "abs", "abs()", "rand(0)", "floor(,)",
+        "sum(1)", "product(,,)",
+        // Operators in odd places
+        "2^2^2^2^2^2", "1<2>3=4&5|6", "=", "1==2", "2(3)(4)", "(2)3", "-(-(-1))", "1--1", "1-+-1"
+    ].iter().map(|input| input.to_string()).collect();
+
+    // Deeply nested and very long
+    inputs.push(format!("{}1{}", "(".repeat(DEPTH), ")".repeat(DEPTH)));
+    inputs.push("(".repeat(DEPTH));
+    inputs.push(")".repeat(DEPTH));
+    inputs.push(format!("{}1", "-".repeat(DEPTH)));
+    inputs.push(format!("{}1", "1+".repeat(DEPTH)));
+    inputs.push(format!("{}2", "2^".repeat(DEPTH)));
+    inputs.push(format!("{}1{}", "abs(".repeat(DEPTH), ")".repeat(DEPTH)));
+    inputs.push("9".repeat(DEPTH));
+    inputs.push("1".repeat(DEPTH * 20));
+    inputs
+}
+
+/// Inputs picked to break an evaluator are run through every entry point that promises a Result.
+/// None of them may panic, whatever they return.
+#[test]
+fn test_no_panics() {
+    let inputs = adversarial_inputs();
+    assert!(inputs.len() >= 30);
+
+    for input in &inputs {
+        let input = input.as_str();
+        let outcome = catch_unwind(|| {
+            let _ = evaluate(input);
+            let _ = evaluate_float(input);
+            let _ = evaluate_signed(input);
+            let _ = evaluate_calc(input);
+            let _ = debug_rpn(input);
+            let _ = reduction_step_count(input);
+            let _ = parse_expression(input);
+            let _ = simplify_expression(input);
+            let _ = explain(input);
+            let _ = OperationTokenTree::try_evaluate_tokens(input);
+        });
+        assert!(outcome.is_ok(), "panicked on {:?}", input);
+    }
+}
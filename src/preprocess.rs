@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use std::vec::Vec;
+
+use {Operations, EvalError, evaluate, turn_into_character};
+
+/// Joins lines that end in a backslash onto the line after them.
+///     "1+\\\n2" becomes "1+2"
+pub fn join_continued_lines(input: &str) -> String {
+    input.replace("\\\r\n", "").replace("\\\n", "")
+}
+
+/// Evaluates every line of the input as its own expression, after joining continued lines.
+/// Blank lines are skipped.
+///     "1+\\\n2" is one expression, giving [Ok(3)]
+///     "1+\n2" is two, giving [Err(MissingOperand), Ok(2)]
+pub fn evaluate_lines(input: &str) -> Vec<Result<u32, EvalError>> {
+    join_continued_lines(input)
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(evaluate)
+        .collect()
+}
+
+/// Replaces each {N} placeholder with the Nth argument.
+///     "{0}+{1}*{0}" with [2, 3] becomes "2+3*2"
+pub fn fill_template(template: &str, args: &[u32]) -> Result<String, EvalError> {
+    let mut filled = String::new();
+    let mut rest = template;
+
+    while let Some(open) = rest.find('{') {
+        filled.push_str(&rest[..open]);
+        let close = rest[open..].find('}').ok_or(EvalError::InvalidPlaceholder)? + open;
+        let index = rest[open + 1..close].trim().parse::<usize>().map_err(|_| EvalError::InvalidPlaceholder)?;
+        let value = args.get(index).ok_or(EvalError::PlaceholderOutOfRange(index))?;
+        filled.push_str(&value.to_string());
+        rest = &rest[close + 1..];
+    }
+    filled.push_str(rest);
+    Ok(filled)
+}
+
+/// Fills in a template's {N} placeholders with the arguments, then evaluates it.
+///     "{0}+{1}*{0}" with [2, 3] evaluates 2+3*2, giving 8
+pub fn evaluate_template(template: &str, args: &[u32]) -> Result<u32, EvalError> {
+    evaluate(&fill_template(template, args)?)
+}
+
+/// Rewrites every alias in an expression as the usual character for its operation. Aliases are
+/// looked at before the usual meaning of a character, so they can replace it. A letter is only
+/// taken as an alias when it stands alone, so mapping x to Multiply leaves a word like max alone.
+///     "2x3" with x as Multiply becomes "2*3"
+pub fn apply_aliases(expression: &str, aliases: &HashMap<char, Operations>) -> String {
+    let characters: Vec<char> = expression.chars().collect();
+    let is_letter = |index: Option<usize>| index.and_then(|index| characters.get(index)).is_some_and(|c| c.is_alphabetic());
+    characters.iter().enumerate().map(|(index, character)| match aliases.get(character) {
+        Some(&op) if !character.is_alphabetic() || !(is_letter(index.checked_sub(1)) || is_letter(Some(index + 1))) =>
+            turn_into_character(op),
+        _ => *character
+    }).collect()
+}
+
+/// Evaluates an expression after rewriting its operator aliases, for localized or custom notations.
+///     "2x3" with x as Multiply evaluates to 6
+pub fn evaluate_with_aliases(expression: &str, aliases: &HashMap<char, Operations>) -> Result<u32, EvalError> {
+    evaluate(&apply_aliases(expression, aliases))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_continued_lines() {
+        assert_eq!(join_continued_lines("1+\\\n2"), "1+2");
+        assert_eq!(join_continued_lines("1+\\\r\n2*\\\n3"), "1+2*3");
+        assert_eq!(evaluate_lines("1+\\\n2"), vec![Ok(3)]);
+        assert_eq!(evaluate_lines("1+\n2"), vec![Err(EvalError::MissingOperand), Ok(2)]);
+        assert_eq!(evaluate_lines("1+1\n\n2*\\\n3\n"), vec![Ok(2), Ok(6)]);
+    }
+
+    #[test]
+    fn test_evaluate_template() {
+        assert_eq!(fill_template("{0}+{1}*{0}", &[2, 3]), Ok("2+3*2".to_string()));
+        assert_eq!(evaluate_template("{0}+{1}*{0}", &[2, 3]), Ok(8));
+        assert_eq!(evaluate_template("({0}+{0})*{0}", &[4]), Ok(32));
+        assert_eq!(evaluate_template("1+1", &[]), Ok(2));
+        assert_eq!(evaluate_template("{0}+{2}", &[1, 2]), Err(EvalError::PlaceholderOutOfRange(2)));
+        assert_eq!(evaluate_template("{0+1", &[1]), Err(EvalError::InvalidPlaceholder));
+        assert_eq!(evaluate_template("{a}", &[1]), Err(EvalError::InvalidPlaceholder));
+    }
+
+    #[test]
+    fn test_evaluate_with_aliases() {
+        let times: HashMap<char, Operations> = [('x', Operations::Multiply)].iter().cloned().collect();
+        assert_eq!(evaluate_with_aliases("2x3", &times), Ok(6));
+        assert_eq!(evaluate_with_aliases("1 + 2 x 3", &times), Ok(7));
+        assert_eq!(apply_aliases("max(2x3)", &times), "max(2*3)");
+        assert_eq!(evaluate("2x3"), Err(EvalError::UnboundVariable('x')));
+
+        let swapped: HashMap<char, Operations> =
+            [('+', Operations::Multiply), ('*', Operations::Add), ('\u{00B7}', Operations::Subtract)].iter().cloned().collect();
+        assert_eq!(apply_aliases("2+3*4", &swapped), "2*3+4");
+        assert_eq!(evaluate_with_aliases("2+3*4", &swapped), Ok(10));
+        assert_eq!(evaluate_with_aliases("5\u{00B7}3", &swapped), Ok(2));
+        assert_eq!(evaluate_with_aliases("2x3", &HashMap::new()), Err(EvalError::UnboundVariable('x')));
+    }
+}
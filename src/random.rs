@@ -0,0 +1,74 @@
+use {Function, EvalError, OperationTokenTree, Field, validate_parentheses};
+use evaluator::{to_rpn, reduce_rpn};
+
+/// A small, fast pseudo-random generator (SplitMix64). Not suitable for anything secret, but the
+/// same seed always gives the same numbers.
+struct SplitMix64 {
+    state: u64
+}
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut mixed = self.state;
+        mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        mixed ^ (mixed >> 31)
+    }
+
+    /// A number from lo to hi, inclusive. The bounds can be given in either order.
+    fn between(&mut self, lo: u32, hi: u32) -> u32 {
+        let (lo, hi) = if lo <= hi { (lo, hi) } else { (hi, lo) };
+        let span = u64::from(hi - lo) + 1;
+        lo + (self.next() % span) as u32
+    }
+}
+
+/// Evaluates an expression where rand(lo, hi) gives pseudo-random numbers drawn from seed, so the
+/// same seed always gives the same result. Each rand call draws the next number.
+///     "rand(1, 6)+rand(1, 6)" rolls two dice
+pub fn evaluate_seeded(expression: &str, seed: u64) -> Result<u32, EvalError> {
+    validate_parentheses(expression)?;
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    let mut generator = SplitMix64 { state: seed };
+    reduce_rpn(&rpn, u32::from_decimal, u32::calculate, |function, arguments| match function {
+        Function::Rand => Ok(generator.between(arguments[0], arguments[1])),
+        _ => u32::call(function, arguments)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use evaluate;
+
+    #[test]
+    fn test_same_seed_same_result() {
+        let expression = "rand(1, 1000)*1000+rand(1, 1000)";
+        assert_eq!(evaluate_seeded(expression, 42), evaluate_seeded(expression, 42));
+        let results: Vec<Result<u32, EvalError>> = (0..8).map(|seed| evaluate_seeded(expression, seed)).collect();
+        assert!(results.iter().any(|&result| result != results[0]));
+    }
+
+    #[test]
+    fn test_rand_bounds() {
+        for seed in 0..200 {
+            let roll = evaluate_seeded("rand(1, 6)", seed).unwrap();
+            assert!((1..=6).contains(&roll));
+            assert_eq!(evaluate_seeded("rand(6, 1)", seed), Ok(roll));
+        }
+        assert_eq!(evaluate_seeded("rand(3, 3)", 7), Ok(3));
+        assert!(evaluate_seeded("rand(0, 4294967295)", 7).is_ok());
+        assert_eq!(evaluate_seeded("1+2*3", 7), Ok(7));
+        assert_eq!(evaluate("rand(1, 6)"), Err(EvalError::UnsupportedFunction(Function::Rand)));
+    }
+
+    #[test]
+    fn test_untaken_branch_draws_nothing() {
+        for seed in 0..20 {
+            let roll = evaluate_seeded("rand(1, 1000)", seed);
+            assert_eq!(evaluate_seeded("if(0, rand(1, 1000), 0)+rand(1, 1000)", seed), roll);
+            assert_eq!(evaluate_seeded("if(1, rand(1, 1000), rand(1, 1000))", seed), roll);
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::convert::TryFrom;
+use std::cmp::Ordering;
+use std::fmt::{Display};
+
+use {EvalError, Field, Function};
+use function::clamp;
+use field::evaluate_in;
+use evaluator::Condition;
+use square_and_multiply;
+
+/// A fraction of two whole numbers, always kept in lowest terms. Like the crate's native numbers,
+/// it cannot go below zero.
+///     1/2+1/4 is exactly 3/4
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numerator: u64,
+    denominator: u64
+}
+
+impl Rational {
+    /// Builds the fraction numerator/denominator, reduced to lowest terms.
+    ///     Rational::new(6, 8) is 3/4
+    pub fn new(numerator: u64, denominator: u64) -> Result<Rational, EvalError> {
+        Rational::reduced(u128::from(numerator), u128::from(denominator))
+    }
+
+    pub fn numerator(&self) -> u64 {
+        self.numerator
+    }
+
+    /// The denominator, which is 1 for whole numbers
+    pub fn denominator(&self) -> u64 {
+        self.denominator
+    }
+
+    /// Divides both sides by their greatest common divisor, reporting fractions that still do not
+    /// fit in a u64 as Overflow
+    fn reduced(numerator: u128, denominator: u128) -> Result<Rational, EvalError> {
+        if denominator == 0 {
+            return Err(EvalError::DivideByZero);
+        }
+        let divisor = gcd(numerator, denominator);
+        match (u64::try_from(numerator / divisor), u64::try_from(denominator / divisor)) {
+            (Ok(numerator), Ok(denominator)) => Ok(Rational { numerator, denominator }),
+            _ => Err(EvalError::Overflow)
+        }
+    }
+
+    /// The largest whole number that is at most this fraction
+    fn floor(self) -> Rational {
+        Rational { numerator: self.numerator / self.denominator, denominator: 1 }
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let remainder = a % b;
+        a = b;
+        b = remainder;
+    }
+    a
+}
+
+/// Written as numerator/denominator, or just the numerator for whole numbers
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.denominator == 1 {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Rational) -> Ordering {
+        (u128::from(self.numerator) * u128::from(other.denominator))
+            .cmp(&(u128::from(other.numerator) * u128::from(self.denominator)))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Rational) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Every operation is exact. Powers need a whole exponent, and results that would go below zero
+/// are an Underflow.
+impl Field for Rational {
+    fn from_u32(value: u32) -> Self {
+        Rational { numerator: u64::from(value), denominator: 1 }
+    }
+
+    fn from_decimal(mantissa: u32, scale: u32) -> Result<Self, EvalError> {
+        Rational::reduced(u128::from(mantissa), 10u128.checked_pow(scale).ok_or(EvalError::Overflow)?)
+    }
+
+    fn add(self, rval: Self) -> Result<Self, EvalError> {
+        let left = u128::from(self.numerator) * u128::from(rval.denominator);
+        let right = u128::from(rval.numerator) * u128::from(self.denominator);
+        Rational::reduced(left.checked_add(right).ok_or(EvalError::Overflow)?,
+            u128::from(self.denominator) * u128::from(rval.denominator))
+    }
+
+    fn sub(self, rval: Self) -> Result<Self, EvalError> {
+        let left = u128::from(self.numerator) * u128::from(rval.denominator);
+        let right = u128::from(rval.numerator) * u128::from(self.denominator);
+        Rational::reduced(left.checked_sub(right).ok_or(EvalError::Underflow)?,
+            u128::from(self.denominator) * u128::from(rval.denominator))
+    }
+
+    fn mul(self, rval: Self) -> Result<Self, EvalError> {
+        Rational::reduced(u128::from(self.numerator) * u128::from(rval.numerator),
+            u128::from(self.denominator) * u128::from(rval.denominator))
+    }
+
+    fn div(self, rval: Self) -> Result<Self, EvalError> {
+        Rational::reduced(u128::from(self.numerator) * u128::from(rval.denominator),
+            u128::from(self.denominator) * u128::from(rval.numerator))
+    }
+
+    fn pow(self, rval: Self) -> Result<Self, EvalError> {
+        if rval.denominator != 1 {
+            return Err(EvalError::Inexact);
+        }
+        square_and_multiply(self, rval.numerator, Rational::from_u32(1), Rational::mul, None)
+    }
+
+    fn call(function: Function, arguments: &[Self]) -> Result<Self, EvalError> {
+        match function {
+            Function::Clamp => Ok(clamp(arguments[0], arguments[1], arguments[2])),
+            Function::Abs => Ok(arguments[0]),
+            Function::Floor => Ok(arguments[0].floor()),
+            Function::Ceil => Ok(arguments[0].add(Rational::new(arguments[0].denominator - 1, arguments[0].denominator)?)?.floor()),
+            // Halves round up
+            Function::Round => Ok(arguments[0].add(Rational::new(1, 2)?)?.floor()),
+            Function::If => Ok(if arguments[0].is_true() { arguments[1] } else { arguments[2] }),
+            Function::Rand | Function::Sum | Function::Product | Function::Pct(_) => Err(EvalError::UnsupportedFunction(function))
+        }
+    }
+}
+
+/// Evaluates an expression exactly, writing the result as a fraction in lowest terms.
+///     "1/2+1/4" is "3/4", "4/2" is "2"
+pub fn evaluate_fraction(expression: &str) -> Result<String, EvalError> {
+    evaluate_in::<Rational>(expression).map(|result| result.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_fraction() {
+        assert_eq!(evaluate_fraction("1/2+1/4"), Ok("3/4".to_string()));
+        assert_eq!(evaluate_fraction("4/2"), Ok("2".to_string()));
+        assert_eq!(evaluate_fraction("1/3*3"), Ok("1".to_string()));
+        assert_eq!(evaluate_fraction("0.5+1/3"), Ok("5/6".to_string()));
+        assert_eq!(evaluate_fraction("(2/3)^2"), Ok("4/9".to_string()));
+        assert_eq!(evaluate_fraction("(1/2)^(1/2)"), Err(EvalError::Inexact));
+        assert_eq!(evaluate_fraction("1/0"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_fraction("1/2-1"), Err(EvalError::Underflow));
+        assert_eq!(evaluate_fraction("2^64"), Err(EvalError::Overflow));
+    }
+
+    #[test]
+    fn test_rational_functions() {
+        assert_eq!(evaluate_fraction("floor(7/2)"), Ok("3".to_string()));
+        assert_eq!(evaluate_fraction("ceil(7/2)"), Ok("4".to_string()));
+        assert_eq!(evaluate_fraction("ceil(4/2)"), Ok("2".to_string()));
+        assert_eq!(evaluate_fraction("round(5/2)+round(1/3)"), Ok("3".to_string()));
+        assert_eq!(evaluate_fraction("clamp(1/3, 1/2, 1)"), Ok("1/2".to_string()));
+    }
+
+    #[test]
+    fn test_rational_new() {
+        let three_quarters = Rational::new(6, 8).unwrap();
+        assert_eq!((three_quarters.numerator(), three_quarters.denominator()), (3, 4));
+        assert_eq!(Rational::new(0, 5), Ok(Rational::from_u32(0)));
+        assert_eq!(Rational::new(1, 0), Err(EvalError::DivideByZero));
+        assert!(Rational::new(1, 3).unwrap() < Rational::new(1, 2).unwrap());
+    }
+}
@@ -0,0 +1,167 @@
+use std::vec::Vec;
+
+use {Expr, EvalError, parse_expression};
+
+/// A rewrite from one shape of tree to another. Variables in the pattern match any subexpression,
+/// and the same variable has to match the same subexpression everywhere it appears.
+///     x+x to 2*x turns (1+y)+(1+y) into 2*(1+y)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rule {
+    pub pattern: Expr,
+    pub replacement: Expr
+}
+
+impl Rule {
+    pub fn new(pattern: Expr, replacement: Expr) -> Rule {
+        Rule { pattern, replacement }
+    }
+
+    /// Reads a rule from two expressions, like "x*1" and "x"
+    pub fn parse(pattern: &str, replacement: &str) -> Result<Rule, EvalError> {
+        Ok(Rule::new(parse_expression(pattern)?, parse_expression(replacement)?))
+    }
+
+    /// Removes operations that do nothing, like x+0, x*1, and x^1, and collapses ones whose value
+    /// is known, like x*0 and x^0. Collapsing never hides an error, so (1/0)*0 is left alone.
+    pub fn identities() -> Vec<Rule> {
+        [("x+0", "x"), ("0+x", "x"), ("x-0", "x"), ("x*1", "x"), ("1*x", "x"), ("x/1", "x"), ("x^1", "x"),
+         ("x*0", "0"), ("0*x", "0"), ("x^0", "1"), ("1^x", "1"), ("-(-x)", "x")]
+            .iter()
+            .filter_map(|&(pattern, replacement)| Rule::parse(pattern, replacement).ok())
+            .collect()
+    }
+
+    /// The rewritten tree, if the pattern matches the whole of expr. A part the replacement drops,
+    /// like the x in x*0, has to have a variable or a value, or its error would be lost.
+    fn apply(&self, expr: &Expr) -> Option<Expr> {
+        let mut bindings: Vec<(char, Expr)> = vec![];
+        if matches(&self.pattern, expr, &mut bindings) && bindings.iter().all(|&(name, ref bound)|
+            has_variable(&self.replacement, &|used| used == name)
+                || has_variable(bound, &|_| true) || bound.evaluate_in::<u32>().is_ok()) {
+            Some(substitute(&self.replacement, &bindings))
+        } else {
+            None
+        }
+    }
+}
+
+impl Expr {
+    /// Applies the rules everywhere in the tree, over and over, until none of them match. The
+    /// children of a node are rewritten before the node itself. Rules that undo each other, like
+    /// x+y to y+x, never finish.
+    pub fn rewrite(&self, rules: &[Rule]) -> Expr {
+        let mut current = self.clone();
+        loop {
+            let next = rewrite_once(&current, rules);
+            if next == current {
+                return current;
+            }
+            current = next;
+        }
+    }
+}
+
+/// One pass over the tree, from the leaves up
+fn rewrite_once(expr: &Expr, rules: &[Rule]) -> Expr {
+    let rebuilt = match *expr {
+        Expr::Binary(op, ref left, ref right) => Expr::binary(op, rewrite_once(left, rules), rewrite_once(right, rules)),
+        Expr::Call(function, ref arguments) =>
+            Expr::Call(function, arguments.iter().map(|argument| rewrite_once(argument, rules)).collect()),
+        Expr::Neg(ref inner) => Expr::Neg(Box::new(rewrite_once(inner, rules))),
+        _ => expr.clone()
+    };
+    rules.iter().find_map(|rule| rule.apply(&rebuilt)).unwrap_or(rebuilt)
+}
+
+/// Whether expr has the pattern's shape, recording what each of the pattern's variables stands for
+fn matches(pattern: &Expr, expr: &Expr, bindings: &mut Vec<(char, Expr)>) -> bool {
+    match (pattern, expr) {
+        (&Expr::Var(name), _) => match bindings.iter().find(|&&(bound, _)| bound == name) {
+            Some((_, bound)) => bound == expr,
+            None => {
+                bindings.push((name, expr.clone()));
+                true
+            }
+        },
+        (&Expr::Binary(op, ref left, ref right), &Expr::Binary(other_op, ref other_left, ref other_right)) =>
+            op == other_op && matches(left, other_left, bindings) && matches(right, other_right, bindings),
+        (&Expr::Call(function, ref arguments), &Expr::Call(other_function, ref other_arguments)) =>
+            function == other_function && arguments.len() == other_arguments.len()
+                && arguments.iter().zip(other_arguments).all(|(argument, other)| matches(argument, other, bindings)),
+        (Expr::Neg(inner), Expr::Neg(other_inner)) => matches(inner, other_inner, bindings),
+        _ => pattern == expr
+    }
+}
+
+/// Whether any of expr's variables passes the test
+fn has_variable<P: Fn(char) -> bool>(expr: &Expr, test: &P) -> bool {
+    match *expr {
+        Expr::Var(name) => test(name),
+        Expr::Binary(_, ref left, ref right) => has_variable(left, test) || has_variable(right, test),
+        Expr::Call(_, ref arguments) => arguments.iter().any(|argument| has_variable(argument, test)),
+        Expr::Neg(ref inner) => has_variable(inner, test),
+        _ => false
+    }
+}
+
+/// The replacement with each variable swapped for what it matched
+fn substitute(replacement: &Expr, bindings: &[(char, Expr)]) -> Expr {
+    match *replacement {
+        Expr::Var(name) => bindings.iter().find(|&&(bound, _)| bound == name)
+            .map_or_else(|| replacement.clone(), |(_, bound)| bound.clone()),
+        Expr::Binary(op, ref left, ref right) => Expr::binary(op, substitute(left, bindings), substitute(right, bindings)),
+        Expr::Call(function, ref arguments) =>
+            Expr::Call(function, arguments.iter().map(|argument| substitute(argument, bindings)).collect()),
+        Expr::Neg(ref inner) => Expr::Neg(Box::new(substitute(inner, bindings))),
+        _ => replacement.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(expression: &str) -> Expr {
+        parse_expression(expression).unwrap()
+    }
+
+    #[test]
+    fn test_custom_rule() {
+        let double = [Rule::parse("x+x", "2*x").unwrap()];
+        assert_eq!(parsed("y+y").rewrite(&double), parsed("2*y"));
+        assert_eq!(parsed("(1+y)+(1+y)").rewrite(&double), parsed("2*(1+y)"));
+        assert_eq!(parsed("(3+3)*y").rewrite(&double), parsed("(2*3)*y"));
+        assert_eq!(parsed("y+z").rewrite(&double), parsed("y+z"));
+        assert_eq!(parsed("clamp(a+a, 0, 9)").rewrite(&double), parsed("clamp(2*a, 0, 9)"));
+    }
+
+    #[test]
+    fn test_identities() {
+        let rules = Rule::identities();
+        assert_eq!(parsed("(z*1)+0").rewrite(&rules), parsed("z"));
+        assert_eq!(parsed("(y+0)*0+5").rewrite(&rules), parsed("5"));
+        assert_eq!(parsed("(a^0)*b").rewrite(&rules), parsed("b"));
+        assert_eq!(parsed("-(-(c/1))").rewrite(&rules), parsed("c"));
+        assert_eq!(parsed("a*2").rewrite(&rules), parsed("a*2"));
+        assert_eq!(parsed("4*1+3*0").rewrite(&rules).evaluate_in::<u32>(), Ok(4));
+    }
+
+    #[test]
+    fn test_identities_keep_errors() {
+        let rules = Rule::identities();
+        assert_eq!(parsed("(1/0)*0").rewrite(&rules), parsed("(1/0)*0"));
+        assert_eq!(parsed("0*(1-2)").rewrite(&rules), parsed("0*(1-2)"));
+        assert_eq!(parsed("(1/0)^0").rewrite(&rules), parsed("(1/0)^0"));
+        assert_eq!(parsed("1^(1/0)").rewrite(&rules), parsed("1^(1/0)"));
+        assert_eq!(parsed("(2-1)*0").rewrite(&rules), parsed("0"));
+        assert_eq!(parsed("(a/0)*0").rewrite(&rules), parsed("0"));
+    }
+
+    #[test]
+    fn test_rule_variables_must_agree() {
+        let rules = [Rule::parse("x-x", "0").unwrap()];
+        assert_eq!(parsed("(a*b)-(a*b)").rewrite(&rules), parsed("0"));
+        assert_eq!(parsed("(a*b)-(b*a)").rewrite(&rules), parsed("(a*b)-(b*a)"));
+        assert_eq!(Rule::parse("x+", "x"), Err(EvalError::MissingOperand));
+    }
+}
@@ -0,0 +1,85 @@
+use {Expr, Operations, EvalError, evaluate, parse_expression, try_calculate};
+
+/// A run of calculations, like a calculator that remembers its display. Pressing = again repeats
+/// the last operation on the result.
+///     after "2+3" gives 5, repeat_last gives 8, then 11
+#[derive(Clone, Debug, Default)]
+pub struct Session {
+    result: Option<u32>,
+
+    // The operation at the root of the last expression, with the value of its right side
+    last: Option<(Operations, u32)>
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session { result: None, last: None }
+    }
+
+    /// Evaluates an expression, remembering its result and the operation calculated last. A failed
+    /// evaluation leaves the session as it was.
+    ///     in "1+2*3" the operation calculated last is the +, with 6 on its right
+    pub fn evaluate(&mut self, expression: &str) -> Result<u32, EvalError> {
+        let result = evaluate(expression)?;
+        self.last = match parse_expression(expression)? {
+            Expr::Binary(op, _, ref right) => Some((op, right.evaluate_in()?)),
+            _ => None
+        };
+        self.result = Some(result);
+        Ok(result)
+    }
+
+    /// Applies the last operation again, with the same right side, to the current result. An
+    /// expression with no operation at its root, like 5, leaves nothing to repeat, which is
+    /// EmptyExpression.
+    pub fn repeat_last(&mut self) -> Result<u32, EvalError> {
+        match (self.result, self.last) {
+            (Some(result), Some((op, operand))) => {
+                let repeated = try_calculate(result, operand, op)?;
+                self.result = Some(repeated);
+                Ok(repeated)
+            },
+            _ => Err(EvalError::EmptyExpression)
+        }
+    }
+
+    /// The result of the last calculation, if there has been one
+    pub fn result(&self) -> Option<u32> {
+        self.result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeat_last() {
+        let mut session = Session::new();
+        assert_eq!(session.evaluate("2+3"), Ok(5));
+        assert_eq!(session.repeat_last(), Ok(8));
+        assert_eq!(session.repeat_last(), Ok(11));
+        assert_eq!(session.result(), Some(11));
+
+        assert_eq!(session.evaluate("1+2*3"), Ok(7));
+        assert_eq!(session.repeat_last(), Ok(13));
+        assert_eq!(session.evaluate("(1+2)*3"), Ok(9));
+        assert_eq!(session.repeat_last(), Ok(27));
+    }
+
+    #[test]
+    fn test_repeat_last_errors() {
+        let mut session = Session::new();
+        assert_eq!(session.repeat_last(), Err(EvalError::EmptyExpression));
+
+        assert_eq!(session.evaluate("10-4"), Ok(6));
+        assert_eq!(session.evaluate("1/0"), Err(EvalError::DivideByZero));
+        assert_eq!(session.result(), Some(6));
+        assert_eq!(session.repeat_last(), Ok(2));
+        assert_eq!(session.repeat_last(), Err(EvalError::Underflow));
+        assert_eq!(session.result(), Some(2));
+
+        assert_eq!(session.evaluate("5"), Ok(5));
+        assert_eq!(session.repeat_last(), Err(EvalError::EmptyExpression));
+    }
+}
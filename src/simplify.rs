@@ -0,0 +1,200 @@
+use std::vec::Vec;
+
+use {Expr, Operations, EvalError, Field, Rule, parse_expression};
+use {try_calculate};
+use ast::Grouping;
+
+impl Expr {
+    /// Calculates every operation and function call whose operands are all numbers. Ones that
+    /// cannot be calculated, like 1/0, are left for evaluation to report.
+    ///     2*3+x becomes 6+x
+    pub fn fold_constants(&self) -> Expr {
+        match *self {
+            Expr::Binary(op, ref left, ref right) => match (left.fold_constants(), right.fold_constants()) {
+                (Expr::Number(lval), Expr::Number(rval)) => try_calculate(lval, rval, op)
+                    .map_or_else(|_| Expr::binary(op, Expr::Number(lval), Expr::Number(rval)), Expr::Number),
+                (left, right) => Expr::binary(op, left, right)
+            },
+            Expr::Call(function, ref arguments) => {
+                let arguments: Vec<Expr> = arguments.iter().map(Expr::fold_constants).collect();
+                let values: Option<Vec<u32>> = arguments.iter().map(|argument| match *argument {
+                    Expr::Number(value) => Some(value),
+                    _ => None
+                }).collect();
+                values.and_then(|values| u32::call(function, &values).ok())
+                    .map_or_else(|| Expr::Call(function, arguments), Expr::Number)
+            },
+            Expr::Neg(ref inner) => Expr::Neg(Box::new(inner.fold_constants())),
+            _ => self.clone()
+        }
+    }
+
+    /// Gathers the numbers spread through a chain of additions or multiplications, which can be
+    /// calculated in any order, into one number at the front of the chain. A chain with fewer than
+    /// two numbers, or whose numbers cannot be calculated, is left as it is.
+    ///     2+x+3 becomes 5+x
+    ///     2*y*3 becomes 6*y
+    pub fn fold_chains(&self) -> Expr {
+        match *self {
+            Expr::Binary(op, ref left, ref right) => {
+                let rebuilt = Expr::binary(op, left.fold_chains(), right.fold_chains());
+                if op != Operations::Add && op != Operations::Multiply {
+                    return rebuilt;
+                }
+
+                let mut operands: Vec<Expr> = vec![];
+                rebuilt.flatten_into(op, &mut operands);
+                let numbers: Vec<u32> = operands.iter().filter_map(|operand| match *operand {
+                    Expr::Number(value) => Some(value),
+                    _ => None
+                }).collect();
+                if numbers.len() < 2 {
+                    return rebuilt;
+                }
+                match numbers[1..].iter().try_fold(numbers[0], |total, &value| try_calculate(total, value, op)) {
+                    Ok(total) => operands.into_iter()
+                        .filter(|operand| !matches!(*operand, Expr::Number(_)))
+                        .fold(Expr::Number(total), |chain, operand| Expr::binary(op, chain, operand)),
+                    Err(_) => rebuilt
+                }
+            },
+            Expr::Call(function, ref arguments) => Expr::Call(function, arguments.iter().map(Expr::fold_chains).collect()),
+            Expr::Neg(ref inner) => Expr::Neg(Box::new(inner.fold_chains())),
+            _ => self.clone()
+        }
+    }
+
+    /// Collects the operands of a chain of one operation, however it is grouped
+    ///     (2+x)+(3+y) gives 2, x, 3 and y
+    fn flatten_into(&self, operation: Operations, operands: &mut Vec<Expr>) {
+        match *self {
+            Expr::Binary(op, ref left, ref right) if op == operation => {
+                left.flatten_into(operation, operands);
+                right.flatten_into(operation, operands);
+            },
+            _ => operands.push(self.clone())
+        }
+    }
+
+    /// Folds constants and chains and applies the identity rules until none of them changes anything
+    ///     2*3+x*1 becomes 6+x
+    pub fn simplify(&self) -> Expr {
+        let identities = Rule::identities();
+        let mut current = self.clone();
+        loop {
+            let next = current.fold_constants().fold_chains().rewrite(&identities);
+            if next == current {
+                return current;
+            }
+            current = next;
+        }
+    }
+}
+
+/// Parses and simplifies an expression, then writes it back out with only the parentheses it needs.
+///     "2*3+x*1" is "6+x"
+///     "(x+0)*(2+3)" is "x*5"
+pub fn simplify_expression(expression: &str) -> Result<String, EvalError> {
+    Ok(parse_expression(expression)?.simplify().to_text(Grouping::Needed))
+}
+
+/// Parses an expression and calculates every part of it that does not depend on a variable, once,
+/// so a template can be evaluated with Expr::evaluate_with for many values without redoing them.
+///     "2*3+x" is the tree of 6+x
+/// Parts that cannot be calculated, like 1/0, are kept for evaluation to report.
+pub fn precompute(expression: &str) -> Result<Expr, EvalError> {
+    Ok(parse_expression(expression)?.fold_constants())
+}
+
+/// Rewrites an expression in one canonical form, without calculating anything: no whitespace, ASCII
+/// operators, every multiplication written out, and only the parentheses that are needed.
+///     " 2 (3 + 4) ", "2*(3+4)", and "((2))×(3+4)" are all "2*(3+4)"
+pub fn normalize(expression: &str) -> Result<String, EvalError> {
+    Ok(parse_expression(expression)?.to_text(Grouping::Needed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_fold_constants() {
+        assert_eq!(parse_expression("2*3+x").unwrap().fold_constants(), parse_expression("6+x").unwrap());
+        assert_eq!(parse_expression("clamp(9, 0, 4)*x").unwrap().fold_constants(), parse_expression("4*x").unwrap());
+        assert_eq!(parse_expression("(1/0)+2").unwrap().fold_constants(), parse_expression("(1/0)+2").unwrap());
+    }
+
+    #[test]
+    fn test_precompute() {
+        let template = precompute("2*3+x").unwrap();
+        assert_eq!(Ok(template.clone()), parse_expression("6+x"));
+        for x in 0..5u32 {
+            let variables: HashMap<char, u32> = vec![('x', x)].into_iter().collect();
+            assert_eq!(template.evaluate_with(&variables), Ok(6 + x));
+        }
+
+        assert_eq!(precompute("clamp(2^5, 0, 10)*y-(4-1)"), parse_expression("10*y-3"));
+        assert_eq!(template.evaluate_with::<u32>(&HashMap::new()), Err(EvalError::UnboundVariable('x')));
+        let variables: HashMap<char, u32> = vec![('x', 1)].into_iter().collect();
+        assert_eq!(precompute("x+1/0").unwrap().evaluate_with(&variables), Err(EvalError::DivideByZero));
+        assert_eq!(precompute("1+"), Err(EvalError::MissingOperand));
+    }
+
+    #[test]
+    fn test_normalize() {
+        for equivalent in &[" 2 (3 + 4) ", "2*(3+4)", "((2))×(3+4)", "2·[3+4]", "(2)(3+4)"] {
+            assert_eq!(normalize(equivalent), Ok("2*(3+4)".to_string()));
+        }
+        for equivalent in &["1+2*3", "1 + (2*3)", "((1+(2*3)))", "1+2×3", "(1)+(2)(3)"] {
+            assert_eq!(normalize(equivalent), Ok("1+2*3".to_string()));
+        }
+        for equivalent in &["2**3**2", "2^(3^2)", "2 ^ 3 ** 2"] {
+            assert_eq!(normalize(equivalent), Ok("2^3^2".to_string()));
+        }
+
+        assert_eq!(normalize("(1-2)-3"), Ok("1-2-3".to_string()));
+        assert_eq!(normalize("1-(2-3)"), Ok("1-(2-3)".to_string()));
+        assert_eq!(normalize("clamp( 9,0 , 4 )x"), Ok("clamp(9, 0, 4)*x".to_string()));
+        assert_eq!(normalize("2*x*1"), Ok("2*x*1".to_string()));
+        assert_eq!(normalize("(1"), Err(EvalError::MismatchedParentheses));
+    }
+
+    #[test]
+    fn test_simplify_constant_expressions() {
+        assert_eq!(simplify_expression("2*3+4"), Ok("10".to_string()));
+        assert_eq!(simplify_expression("(1+2)^2"), Ok("9".to_string()));
+        assert_eq!(simplify_expression("1/0"), Ok("1/0".to_string()));
+        assert_eq!(simplify_expression("1+"), Err(EvalError::MissingOperand));
+        assert_eq!(simplify_expression("(1/0)*0"), Ok("1/0*0".to_string()));
+        assert_eq!(simplify_expression("(1-2)*0"), Ok("(1-2)*0".to_string()));
+        assert_eq!(simplify_expression("(1/0)^0"), Ok("(1/0)^0".to_string()));
+        assert_eq!(simplify_expression("1^(1/0)"), Ok("1^(1/0)".to_string()));
+        assert!(::evaluate(&simplify_expression("(1/0)*0").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_simplify_mixed_expressions() {
+        assert_eq!(simplify_expression("2*3+x*1"), Ok("6+x".to_string()));
+        assert_eq!(simplify_expression("(x+0)*(2+3)"), Ok("x*5".to_string()));
+        assert_eq!(simplify_expression("(1+2)*(x-4*0)"), Ok("3*x".to_string()));
+        assert_eq!(simplify_expression("y^(2-1)+z*(3-3)"), Ok("y".to_string()));
+        assert_eq!(simplify_expression("x*(y+1)"), Ok("x*(y+1)".to_string()));
+        assert_eq!(simplify_expression("x-(y-z)"), Ok("x-(y-z)".to_string()));
+        assert_eq!(simplify_expression("(x-y)-z"), Ok("x-y-z".to_string()));
+        assert_eq!(simplify_expression("(x^y)^z"), Ok("(x^y)^z".to_string()));
+        assert_eq!(simplify_expression("-(-x)+1*2"), Ok("x+2".to_string()));
+    }
+
+    #[test]
+    fn test_simplify_chains() {
+        assert_eq!(simplify_expression("2+x+3"), Ok("5+x".to_string()));
+        assert_eq!(simplify_expression("2*y*3"), Ok("6*y".to_string()));
+        assert_eq!(simplify_expression("1+x+2+y+3"), Ok("6+x+y".to_string()));
+        assert_eq!(simplify_expression("(2+x)+(y+3)"), Ok("5+x+y".to_string()));
+        assert_eq!(simplify_expression("2*(x*3)+1"), Ok("6*x+1".to_string()));
+        assert_eq!(simplify_expression("x+2"), Ok("x+2".to_string()));
+        assert_eq!(simplify_expression("2-x-3"), Ok("2-x-3".to_string()));
+        assert_eq!(simplify_expression("4000000000+x+4000000000"), Ok("4000000000+x+4000000000".to_string()));
+    }
+}
@@ -0,0 +1,92 @@
+use std::vec::Vec;
+
+use {Expr, ExpressionAtom, EvalError};
+use ast::{push_rpn, finish_rpn};
+use evaluator::ShuntingYard;
+use operator_table::OperatorTable;
+
+/// Builds a tree from tokens handed over one at a time, as they are typed. Each token goes straight
+/// through the shunting-yard, so mistakes like a missing operand are reported as soon as they can be.
+///     feeding 1, +, 2, *, 3 and finishing gives Binary(Add, 1, Binary(Multiply, 2, 3))
+#[derive(Clone, Debug)]
+pub struct StreamParser {
+    table: OperatorTable,
+    yard: ShuntingYard,
+
+    // Subexpressions that have been built so far, waiting for the operations that use them
+    stack: Vec<Expr>
+}
+
+impl StreamParser {
+    pub fn new() -> StreamParser {
+        StreamParser { table: OperatorTable::default(), yard: ShuntingYard::new(), stack: vec![] }
+    }
+
+    /// Takes the next infix token. Once an error is reported, the parser should be thrown away.
+    pub fn feed(&mut self, atom: ExpressionAtom) -> Result<(), EvalError> {
+        let mut ready: Vec<ExpressionAtom> = vec![];
+        self.yard.push(atom, &self.table, &mut ready)?;
+        ready.into_iter().try_for_each(|token| push_rpn(&mut self.stack, token))
+    }
+
+    /// The tree of everything fed so far
+    pub fn finish(mut self) -> Result<Expr, EvalError> {
+        let mut ready: Vec<ExpressionAtom> = vec![];
+        self.yard.finish(&mut ready)?;
+        for token in ready {
+            push_rpn(&mut self.stack, token)?;
+        }
+        finish_rpn(self.stack)
+    }
+}
+
+impl Default for StreamParser {
+    fn default() -> StreamParser {
+        StreamParser::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Operations, OperationTokenTree, parse_expression};
+
+    fn feed_all(expression: &str) -> Result<Expr, EvalError> {
+        let mut parser = StreamParser::new();
+        for atom in OperationTokenTree::evaluate_tokens(expression) {
+            parser.feed(atom)?;
+        }
+        parser.finish()
+    }
+
+    #[test]
+    fn test_stream_parser() {
+        let mut parser = StreamParser::new();
+        parser.feed(ExpressionAtom::Number(1)).unwrap();
+        parser.feed(ExpressionAtom::Operation(Operations::Add)).unwrap();
+        parser.feed(ExpressionAtom::Number(2)).unwrap();
+        parser.feed(ExpressionAtom::Operation(Operations::Multiply)).unwrap();
+        parser.feed(ExpressionAtom::Number(3)).unwrap();
+        assert_eq!(parser.finish(), Ok(Expr::binary(Operations::Add, Expr::num(1),
+            Expr::binary(Operations::Multiply, Expr::num(2), Expr::num(3)))));
+
+        for expression in &["(1+2)*3", "2^3^2", "-4+clamp(1, 2, 3)", "2(0.5)", "1-2-3"] {
+            assert_eq!(feed_all(expression), parse_expression(expression));
+        }
+    }
+
+    #[test]
+    fn test_stream_parser_errors() {
+        let mut parser = StreamParser::new();
+        parser.feed(ExpressionAtom::Operation(Operations::Multiply)).unwrap();
+        parser.feed(ExpressionAtom::Number(2)).unwrap();
+        assert_eq!(parser.feed(ExpressionAtom::Operation(Operations::Add)), Err(EvalError::MissingOperand));
+
+        let mut parser = StreamParser::new();
+        assert_eq!(parser.feed(ExpressionAtom::RightParenthesis), Err(EvalError::MismatchedParentheses));
+
+        assert_eq!(feed_all("(1+2"), Err(EvalError::MismatchedParentheses));
+        assert_eq!(feed_all(""), Err(EvalError::EmptyExpression));
+        assert_eq!(StreamParser::default().finish(), Err(EvalError::EmptyExpression));
+    }
+}
@@ -0,0 +1,100 @@
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, StrictError, ParseError, TokenizeError, Tokenizer, NumberAdjacencyPolicy, validate_parentheses};
+use evaluator::{to_rpn, evaluate_rpn};
+
+/// Evaluates an expression, rejecting everything the usual tokenizer lets slide: implied
+/// multiplication, numbers with leading zeros, numbers with no operation between them, and
+/// characters it does not understand.
+///     "2*(3)" is 6, but "2(3)", "007", and "12 34" are errors
+pub fn evaluate_strict(expression: &str) -> Result<u32, StrictError> {
+    validate_parentheses(expression).map_err(StrictError::Eval)?;
+
+    let mut tokens: Vec<ExpressionAtom> = vec![];
+    let mut tokenizer = Tokenizer::with_policy(expression, NumberAdjacencyPolicy::Error);
+    while let Some(spanned) = tokenizer.next_spanned() {
+        let spanned = spanned.map_err(|error| match error {
+            TokenizeError::AdjacentNumbers { pos } => StrictError::MissingOperator { pos },
+            _ => StrictError::Tokenize(error)
+        })?;
+        match spanned.atom {
+            ExpressionAtom::ImplicitMultiply => return Err(StrictError::ImplicitMultiplication { pos: spanned.start }),
+            ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. }
+                if spanned.text.len() > 1 && spanned.text.starts_with('0') && !spanned.text.starts_with("0.") =>
+                return Err(StrictError::LeadingZero { pos: spanned.start }),
+            _ => {}
+        }
+        tokens.push(spanned.atom);
+    }
+
+    to_rpn(&tokens).and_then(|rpn| evaluate_rpn(&rpn)).map_err(StrictError::Eval)
+}
+
+/// Checks that an expression only uses the allowed operations, for contexts that restrict what can
+/// be calculated. Implied multiplication, like 2(3), counts as a multiplication. Only the
+/// operations are checked; anything else wrong with the expression is left to evaluation.
+///     "1+2-3" is allowed with only + and -, but "1*2" is Disallowed { op: Multiply, pos: 1 }
+pub fn validate_allowed(expression: &str, allowed: &[Operations]) -> Result<(), ParseError> {
+    let mut tokenizer = Tokenizer::new(expression);
+    while let Some(spanned) = tokenizer.next_spanned() {
+        let spanned = spanned.map_err(ParseError::Tokenize)?;
+        match spanned.atom.as_operation() {
+            Some(op) if !allowed.contains(&op) => return Err(ParseError::Disallowed { op, pos: spanned.start }),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {evaluate, EvalError, TokenizeError};
+
+    const ADDITIVE: &[Operations] = &[Operations::Add, Operations::Subtract];
+
+    #[test]
+    fn test_strict_accepts_explicit_input() {
+        for expression in &["1+2*3", "2*(3+4)", "0", "0.5*4", "10-0", "clamp(15, 0, 10)", "[1+2]*{3}"] {
+            assert_eq!(evaluate_strict(expression).ok(), evaluate(expression).ok());
+        }
+    }
+
+    #[test]
+    fn test_strict_rejects_what_evaluate_tolerates() {
+        for expression in &["2(3)", "1+007", "12 34", "1+2$"] {
+            assert!(evaluate(expression).is_ok());
+            assert!(evaluate_strict(expression).is_err());
+        }
+    }
+
+    #[test]
+    fn test_strict_rejects_lenient_input() {
+        let cases = [
+            ("2(3)", StrictError::ImplicitMultiplication { pos: 1 }),
+            ("(1)(2)", StrictError::ImplicitMultiplication { pos: 3 }),
+            ("1+007", StrictError::LeadingZero { pos: 2 }),
+            ("00.5", StrictError::LeadingZero { pos: 0 }),
+            ("12 34", StrictError::MissingOperator { pos: 3 }),
+            ("1+2$", StrictError::Tokenize(TokenizeError::UnexpectedChar { c: '$', pos: 3 })),
+            ("1/0", StrictError::Eval(EvalError::DivideByZero)),
+            ("(1+2", StrictError::Eval(EvalError::MismatchedParentheses))
+        ];
+        for &(expression, ref error) in &cases {
+            assert_eq!(evaluate_strict(expression).as_ref(), Err(error));
+        }
+    }
+
+    #[test]
+    fn test_validate_allowed() {
+        assert_eq!(validate_allowed("1+2-3", ADDITIVE), Ok(()));
+        assert_eq!(validate_allowed("(10 - 4) + [2]", ADDITIVE), Ok(()));
+        assert_eq!(validate_allowed("", ADDITIVE), Ok(()));
+        assert_eq!(validate_allowed("1*2", ADDITIVE), Err(ParseError::Disallowed { op: Operations::Multiply, pos: 1 }));
+        assert_eq!(validate_allowed("1 + 2 ^ 3", ADDITIVE), Err(ParseError::Disallowed { op: Operations::Power, pos: 6 }));
+        assert_eq!(validate_allowed("1+2(3)", ADDITIVE), Err(ParseError::Disallowed { op: Operations::Multiply, pos: 3 }));
+        assert_eq!(validate_allowed("1×2", ADDITIVE), Err(ParseError::Disallowed { op: Operations::Multiply, pos: 1 }));
+        assert_eq!(validate_allowed("1-2", &[Operations::Add]), Err(ParseError::Disallowed { op: Operations::Subtract, pos: 1 }));
+        assert_eq!(validate_allowed("1+2$", ADDITIVE), Err(ParseError::Tokenize(TokenizeError::UnexpectedChar { c: '$', pos: 3 })));
+    }
+}
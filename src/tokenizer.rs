@@ -0,0 +1,379 @@
+use std::iter::Peekable;
+use std::str::Chars;
+
+use {ExpressionAtom, TokenizeError, NumberAdjacencyPolicy, OPERATOR_ALIASES};
+use {turn_into_operation, turn_into_function, track_bracket, append_digit};
+
+/// Reads every digit at the front of chars as one number, leaving the character after them unread.
+/// Positions in the errors count from the start of the run, so callers add where the run began.
+///     "123+4" reads 123 and leaves "+4"
+pub(crate) fn parse_number_run(chars: &mut Peekable<Chars>) -> Result<u32, TokenizeError> {
+    let mut text = String::new();
+    let mut value: Option<u32> = Some(0);
+    while let Some(digit) = chars.peek().filter(|c| c.is_ascii_digit()).and_then(|c| c.to_digit(10)) {
+        text.extend(chars.next());
+        value = value.and_then(|v| append_digit(v, digit));
+    }
+
+    if text.is_empty() {
+        return Err(match chars.peek() {
+            Some(&c) => TokenizeError::UnexpectedChar { c, pos: 0 },
+            None => TokenizeError::UnexpectedEnd { pos: 0 }
+        });
+    }
+    value.ok_or(TokenizeError::NumberOverflow { text, pos: 0 })
+}
+
+/// Reads tokens out of an expression one at a time, keeping track of where it is. Unlike
+/// OperationTokenTree::evaluate_tokens, it reports characters it does not understand. A bracket
+/// closed by the wrong kind, like the ) in [1+2), is a MismatchedBrackets error.
+///     Tokenizer::new("12+3") yields Number(12), Operation(Add), Number(3)
+pub struct Tokenizer<'a> {
+    input: &'a str,
+    position: usize,
+    previous: Option<ExpressionAtom>,
+    adjacency: NumberAdjacencyPolicy,
+    /// The brackets open so far, so each closing one can be checked against its kind
+    brackets: Vec<char>
+}
+
+/// A token along with the exact text it was read from, for tools like syntax highlighters that
+/// care which spelling was typed.
+///     "2×3" has an Operation(Multiply) whose text is "×"
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SpannedToken<'a> {
+    pub atom: ExpressionAtom,
+    pub text: &'a str,
+    /// The byte offset of the text in the input
+    pub start: usize
+}
+
+impl<'a> SpannedToken<'a> {
+    /// The character typed for this token, if it was a single character
+    pub fn raw_char(&self) -> Option<char> {
+        let mut characters = self.text.chars();
+        match (characters.next(), characters.next()) {
+            (Some(character), None) => Some(character),
+            _ => None
+        }
+    }
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(input: &'a str) -> Tokenizer<'a> {
+        Tokenizer::with_policy(input, NumberAdjacencyPolicy::default())
+    }
+
+    /// A tokenizer that treats numbers with only whitespace between them, like 12 34, the way the
+    /// policy says
+    ///     with NumberAdjacencyPolicy::ImplicitMultiply, "12 34" yields Number(12), ImplicitMultiply, Number(34)
+    pub fn with_policy(input: &'a str, adjacency: NumberAdjacencyPolicy) -> Tokenizer<'a> {
+        Tokenizer { input, position: 0, previous: None, adjacency, brackets: vec![] }
+    }
+
+    /// Starts over on new input, as if the tokenizer had just been made for it. Nothing about the
+    /// old input carries over, like the number that would make a ( after it a multiplication. The
+    /// adjacency policy is kept.
+    pub fn reset(&mut self, new_input: &'a str) {
+        self.input = new_input;
+        self.position = 0;
+        self.previous = None;
+        self.brackets.clear();
+    }
+
+    /// The byte offset into the input of the next character to be read
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The part of the input that has not been read yet
+    pub fn remaining(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    /// The word of letters coming next, after any whitespace, without reading it
+    ///     in "3 km+1", after the 3 the next word is "km"
+    pub fn peek_word(&self) -> Option<&'a str> {
+        let rest = self.remaining().trim_start();
+        let word = &rest[..rest.find(|c: char| !c.is_alphabetic()).unwrap_or(rest.len())];
+        if word.is_empty() { None } else { Some(word) }
+    }
+
+    /// Moves past the next word without making a token of it, for callers that give words a meaning
+    /// of their own, like the m in 3m. What came before the word still counts, so the ( in 3m(2)
+    /// is still a multiplication, and positions still count from the start of the input.
+    pub fn skip_word(&mut self) -> Option<&'a str> {
+        let word = self.peek_word()?;
+        self.skip_whitespace();
+        self.position += word.len();
+        Some(word)
+    }
+
+    /// Reads the next token along with the text it came from. Inferred tokens, like the
+    /// ImplicitMultiply in 2(3), have empty text.
+    pub fn next_spanned(&mut self) -> Option<Result<SpannedToken<'a>, TokenizeError>> {
+        let before = self.position;
+        let atom = match self.next()? {
+            Ok(atom) => atom,
+            Err(error) => return Some(Err(error))
+        };
+        let text = self.input[before..self.position].trim_start();
+        Some(Ok(SpannedToken { atom, text, start: self.position - text.len() }))
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.remaining();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    /// Reads the next token, or None at the end of the input
+    fn read_token(&mut self) -> Option<Result<ExpressionAtom, TokenizeError>> {
+        self.skip_whitespace();
+        let rest = self.remaining();
+        let start = self.position;
+        let character = rest.chars().next()?;
+
+        if character.is_ascii_digit() || character == '.' {
+            // How much of rest the number takes, its digits without the point (None once they are too
+            // large), and how many of them come after the point. Whitespace only joins digits when
+            // they are concatenated.
+            let mut length = 0;
+            let mut has_digits = false;
+            let mut mantissa: Option<u32> = Some(0);
+            let mut scale: Option<u32> = None;
+            for (index, c) in rest.char_indices() {
+                if let Some(digit) = Some(c).filter(char::is_ascii_digit).and_then(|c| c.to_digit(10)) {
+                    has_digits = true;
+                    mantissa = mantissa.and_then(|mantissa| append_digit(mantissa, digit));
+                    scale = scale.map(|scale| scale + 1);
+                    length = index + 1;
+                } else if c == '.' && index == length {
+                    if scale.is_some() {
+                        self.position += index + 1;
+                        return Some(Err(TokenizeError::UnexpectedChar { c: '.', pos: start + index }));
+                    }
+                    scale = Some(0);
+                    length = index + 1;
+                } else if !(c.is_whitespace() && self.adjacency == NumberAdjacencyPolicy::Concatenate) {
+                    break;
+                }
+            }
+            let text = &rest[..length];
+            self.position += length;
+
+            if !has_digits {
+                return Some(Err(TokenizeError::UnexpectedChar { c: '.', pos: start }));
+            }
+            return Some(mantissa
+                .map(|mantissa| match scale {
+                    Some(scale) => ExpressionAtom::Decimal { mantissa, scale },
+                    None => ExpressionAtom::Number(mantissa)
+                })
+                .ok_or_else(|| TokenizeError::NumberOverflow { text: text.to_string(), pos: start }));
+        }
+
+        if character.is_alphabetic() {
+            let word: &str = &rest[..rest.find(|c: char| !c.is_alphabetic()).unwrap_or(rest.len())];
+            self.position += word.len();
+            if word.chars().count() == 1 && turn_into_function(word).is_none() {
+                return Some(Ok(ExpressionAtom::Variable(character)));
+            }
+            return Some(turn_into_function(word)
+                .map(ExpressionAtom::Function)
+                .ok_or(TokenizeError::UnexpectedChar { c: character, pos: start }));
+        }
+
+        for &(alias, canonical) in OPERATOR_ALIASES {
+            if rest.starts_with(alias) {
+                self.position += alias.len();
+                let operation = canonical.chars().next().and_then(turn_into_operation);
+                return operation.map(|op| Ok(ExpressionAtom::Operation(op)));
+            }
+        }
+
+        self.position += character.len_utf8();
+        Some(match character {
+            '(' | '[' | '{' => Ok(ExpressionAtom::LeftParenthesis),
+            ')' | ']' | '}' => Ok(ExpressionAtom::RightParenthesis),
+            ',' => Ok(ExpressionAtom::Comma),
+            _ => turn_into_operation(character)
+                .map(ExpressionAtom::Operation)
+                .ok_or(TokenizeError::UnexpectedChar { c: character, pos: start })
+        })
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<ExpressionAtom, TokenizeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let before = self.position;
+        let token = match self.read_token()? {
+            Ok(token) => token,
+            Err(error) => return Some(Err(error))
+        };
+
+        // A number straight after a number is only possible with whitespace between them. When it
+        // starts with a digit it follows the adjacency policy; one like .5 is always multiplied.
+        let after_number = matches!(self.previous, Some(ExpressionAtom::Number(_)) | Some(ExpressionAtom::Decimal { .. }));
+        let skipped = &self.input[before..];
+        let start = before + skipped.len() - skipped.trim_start().len();
+        if after_number && self.adjacency == NumberAdjacencyPolicy::Error
+            && self.input[start..].starts_with(|c: char| c.is_ascii_digit()) {
+            return Some(Err(TokenizeError::AdjacentNumbers { pos: start }));
+        }
+
+        // A value directly after a value is multiplied, like 2(3). The token is read again next time.
+        let ends_value = after_number
+            || matches!(self.previous, Some(ExpressionAtom::Variable(_)) | Some(ExpressionAtom::RightParenthesis));
+        let starts_value = match token {
+            ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. } => after_number || matches!(self.previous,
+                Some(ExpressionAtom::RightParenthesis) | Some(ExpressionAtom::Variable(_))),
+            ExpressionAtom::LeftParenthesis | ExpressionAtom::Function(_) | ExpressionAtom::Variable(_) => true,
+            _ => false
+        };
+        if ends_value && starts_value {
+            self.position = before;
+            self.previous = Some(ExpressionAtom::ImplicitMultiply);
+            return Some(Ok(ExpressionAtom::ImplicitMultiply));
+        }
+
+        if matches!(token, ExpressionAtom::LeftParenthesis | ExpressionAtom::RightParenthesis) {
+            let bracket = self.input[start..].chars().next().unwrap_or_default();
+            if let Err(error) = track_bracket(&mut self.brackets, bracket, start) {
+                return Some(Err(error));
+            }
+        }
+        self.previous = Some(token);
+        Some(Ok(token))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Operations, OperationTokenTree};
+
+    #[test]
+    fn test_tokenizer_position() {
+        let mut tokenizer = Tokenizer::new("12 + 345");
+        assert_eq!(tokenizer.position(), 0);
+        assert_eq!(tokenizer.next(), Some(Ok(ExpressionAtom::Number(12))));
+        assert_eq!(tokenizer.position(), 2);
+        assert_eq!(tokenizer.remaining(), " + 345");
+        assert_eq!(tokenizer.next(), Some(Ok(ExpressionAtom::Operation(Operations::Add))));
+        assert_eq!(tokenizer.position(), 4);
+        assert_eq!(tokenizer.next(), Some(Ok(ExpressionAtom::Number(345))));
+        assert_eq!(tokenizer.position(), 8);
+        assert_eq!(tokenizer.remaining(), "");
+        assert_eq!(tokenizer.next(), None);
+    }
+
+    #[test]
+    fn test_tokenizer_reset() {
+        let mut tokenizer = Tokenizer::new("2*(3");
+        assert_eq!(tokenizer.by_ref().collect::<Result<Vec<ExpressionAtom>, TokenizeError>>(),
+            Ok(OperationTokenTree::evaluate_tokens("2*(3")));
+
+        tokenizer.reset("(4)+15");
+        assert_eq!(tokenizer.position(), 0);
+        assert_eq!(tokenizer.by_ref().collect::<Result<Vec<ExpressionAtom>, TokenizeError>>(),
+            Ok(OperationTokenTree::evaluate_tokens("(4)+15")));
+
+        tokenizer.reset("7+8");
+        assert_eq!(tokenizer.next(), Some(Ok(ExpressionAtom::Number(7))));
+        tokenizer.reset("1$");
+        assert_eq!(tokenizer.nth(1), Some(Err(TokenizeError::UnexpectedChar { c: '$', pos: 1 })));
+    }
+
+    #[test]
+    fn test_tokenizer_adjacency_policy() {
+        let policies = [NumberAdjacencyPolicy::Concatenate, NumberAdjacencyPolicy::Error, NumberAdjacencyPolicy::ImplicitMultiply];
+        for expression in &["12 34", "1 2 3", "1.5 2", "12 3.4", "1 2.5 3", "1. 5", "12 34x", "1\t2", "2 (3) 4", "x 3", "1.5 .5", "12+34"] {
+            for &policy in &policies {
+                let tokens: Result<Vec<ExpressionAtom>, TokenizeError> = Tokenizer::with_policy(expression, policy).collect();
+                assert_eq!(tokens, OperationTokenTree::try_evaluate_tokens_with_policy(expression, policy), "{:?} {:?}", expression, policy);
+            }
+        }
+
+        let mut tokenizer = Tokenizer::with_policy("12 34", NumberAdjacencyPolicy::ImplicitMultiply);
+        assert_eq!(tokenizer.next_spanned().map(|spanned| spanned.map(|spanned| spanned.text)), Some(Ok("12")));
+        tokenizer.reset("1 2");
+        assert_eq!(tokenizer.nth(1), Some(Ok(ExpressionAtom::ImplicitMultiply)));
+        assert_eq!(Tokenizer::new("12 34").next_spanned().map(|spanned| spanned.map(|spanned| spanned.text)), Some(Ok("12 34")));
+    }
+
+    #[test]
+    fn test_tokenizer_skip_word() {
+        let mut tokenizer = Tokenizer::new("3 km(2) + $");
+        assert_eq!(tokenizer.next(), Some(Ok(ExpressionAtom::Number(3))));
+        assert_eq!(tokenizer.peek_word(), Some("km"));
+        assert_eq!(tokenizer.skip_word(), Some("km"));
+        assert_eq!(tokenizer.position(), 4);
+        assert_eq!(tokenizer.peek_word(), None);
+        assert_eq!(tokenizer.skip_word(), None);
+        assert_eq!(tokenizer.next(), Some(Ok(ExpressionAtom::ImplicitMultiply)));
+        assert_eq!(tokenizer.nth(3), Some(Ok(ExpressionAtom::Operation(Operations::Add))));
+        assert_eq!(tokenizer.next(), Some(Err(TokenizeError::UnexpectedChar { c: '$', pos: 10 })));
+    }
+
+    #[test]
+    fn test_tokenizer_stuck() {
+        let mut tokenizer = Tokenizer::new("1+$2");
+        tokenizer.next();
+        tokenizer.next();
+        assert_eq!(tokenizer.next(), Some(Err(TokenizeError::UnexpectedChar { c: '$', pos: 2 })));
+        assert_eq!(tokenizer.remaining(), "2");
+
+        let mut overflow = Tokenizer::new("1+4294967296");
+        assert_eq!(overflow.nth(2),
+            Some(Err(TokenizeError::NumberOverflow { text: "4294967296".to_string(), pos: 2 })));
+
+        let mut points = Tokenizer::new("1.2.3");
+        assert_eq!(points.next(), Some(Err(TokenizeError::UnexpectedChar { c: '.', pos: 3 })));
+        assert_eq!(points.remaining(), "3");
+    }
+
+    #[test]
+    fn test_spanned_tokens() {
+        let mut tokenizer = Tokenizer::new("2 \u{00D7} 3");
+        tokenizer.next_spanned();
+        let times = tokenizer.next_spanned().unwrap().unwrap();
+        assert_eq!(times.atom, ExpressionAtom::Operation(Operations::Multiply));
+        assert_eq!(times.raw_char(), Some('\u{00D7}'));
+        assert_eq!(times.start, 2);
+        assert_eq!(::evaluate("2\u{00D7}3"), Ok(6));
+
+        let mut power = Tokenizer::new("2**3(1)");
+        power.next_spanned();
+        let stars = power.next_spanned().unwrap().unwrap();
+        assert_eq!((stars.text, stars.raw_char()), ("**", None));
+        power.next_spanned();
+        let implicit = power.next_spanned().unwrap().unwrap();
+        assert_eq!(implicit, SpannedToken { atom: ExpressionAtom::ImplicitMultiply, text: "", start: 4 });
+    }
+
+    #[test]
+    fn test_parse_number_run() {
+        let mut chars = "12345+6".chars().peekable();
+        assert_eq!(parse_number_run(&mut chars), Ok(12345));
+        assert_eq!(chars.collect::<String>(), "+6");
+
+        let mut overflow = "99999999999*2".chars().peekable();
+        assert_eq!(parse_number_run(&mut overflow),
+            Err(TokenizeError::NumberOverflow { text: "99999999999".to_string(), pos: 0 }));
+        assert_eq!(overflow.next(), Some('*'));
+
+        assert_eq!(parse_number_run(&mut "+1".chars().peekable()), Err(TokenizeError::UnexpectedChar { c: '+', pos: 0 }));
+        assert_eq!(parse_number_run(&mut "".chars().peekable()), Err(TokenizeError::UnexpectedEnd { pos: 0 }));
+    }
+
+    #[test]
+    fn test_tokenizer_matches_evaluate_tokens() {
+        for expression in &["1+(1+(2+4+5666))", "2(3)", "clamp(1, 2, 3)", "2\u{00D7}3**2", "(1)(2)3", "0.25+.5*2.(3)",
+                            "2x+y(3)", "x y", "x2"] {
+            let tokens: Result<Vec<ExpressionAtom>, TokenizeError> = Tokenizer::new(expression).collect();
+            assert_eq!(tokens, Ok(OperationTokenTree::evaluate_tokens(expression)));
+        }
+    }
+}
@@ -0,0 +1,651 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, EvalError, Field, OperationTokenTree, Function};
+use {precedence, is_right_associative, turn_into_character, format_decimal, try_calculate};
+use evaluator::evaluate;
+
+/// Removes pairs of parentheses that wrap the whole expression.
+///     ((1+2)) becomes 1+2
+///     (1)+(2) is left alone, since its first and last parentheses are not a pair
+pub fn strip_outer_parens(tokens: &[ExpressionAtom]) -> &[ExpressionAtom] {
+    let mut stripped = tokens;
+    while stripped.len() >= 2 && encloses_everything(stripped) {
+        stripped = &stripped[1..stripped.len() - 1];
+    }
+    stripped
+}
+
+/// The tokens each pair of parentheses covers, the parentheses included, ordered by where they
+/// open, so a group comes before the groups inside it. Parentheses without a partner are left out.
+///     1+(2*(3+4)) is [2..11, 5..10]
+pub fn paren_groups(tokens: &[ExpressionAtom]) -> Vec<Range<usize>> {
+    let mut groups: Vec<Range<usize>> = vec![];
+    let mut opened: Vec<usize> = vec![];
+    for (index, token) in tokens.iter().enumerate() {
+        match *token {
+            ExpressionAtom::LeftParenthesis => opened.push(index),
+            ExpressionAtom::RightParenthesis => groups.extend(opened.pop().map(|start| start..index + 1)),
+            _ => {}
+        }
+    }
+    groups.sort_by_key(|group| group.start);
+    groups
+}
+
+/// Whether the first token is a parenthesis that is only closed by the last token
+fn encloses_everything(tokens: &[ExpressionAtom]) -> bool {
+    if tokens.first() != Some(&ExpressionAtom::LeftParenthesis)
+        || tokens.last() != Some(&ExpressionAtom::RightParenthesis) {
+        return false;
+    }
+
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        match *token {
+            ExpressionAtom::LeftParenthesis => depth += 1,
+            ExpressionAtom::RightParenthesis => {
+                depth -= 1;
+                if depth == 0 {
+                    return index == tokens.len() - 1;
+                }
+            },
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Splits a token stream at each use of op outside of parentheses. Splitting on the loosest
+/// operation in the expression gives terms that can be evaluated on their own.
+///     1+2*3+4 split on Add is [1], [2*3], [4]
+///     (1+2)*3 split on Add is not split
+/// Inferred multiplications count as Multiply, and a - that negates is not a Subtract.
+pub fn split_top_level(tokens: &[ExpressionAtom], op: Operations) -> Vec<Vec<ExpressionAtom>> {
+    let mut pieces: Vec<Vec<ExpressionAtom>> = vec![vec![]];
+    let mut depth: usize = 0;
+    let mut follows_value = false;
+    for &token in tokens {
+        match token {
+            ExpressionAtom::LeftParenthesis => depth += 1,
+            ExpressionAtom::RightParenthesis => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+
+        if depth == 0 && follows_value && token.as_operation() == Some(op) {
+            pieces.push(vec![]);
+        } else if let Some(piece) = pieces.last_mut() {
+            piece.push(token);
+        }
+        follows_value = matches!(token, ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. }
+            | ExpressionAtom::Variable(_) | ExpressionAtom::RightParenthesis);
+    }
+    pieces
+}
+
+/// Lists every position where two token streams differ, with the token each stream has there.
+/// When one stream is longer, the shorter one has None at the extra positions.
+///     1+2 against 1*2 differs only at 1, with Add against Multiply
+pub fn token_diff(a: &[ExpressionAtom], b: &[ExpressionAtom])
+    -> Vec<(usize, Option<ExpressionAtom>, Option<ExpressionAtom>)> {
+    (0..a.len().max(b.len()))
+        .map(|index| (index, a.get(index).cloned(), b.get(index).cloned()))
+        .filter(|&(_, left, right)| left != right)
+        .collect()
+}
+
+/// How many of each kind of atom a token stream has
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AtomCounts {
+    /// Whole numbers and decimals
+    pub numbers: usize,
+    /// Operations, including inferred multiplications and negations
+    pub operators: usize,
+    pub left_parens: usize,
+    pub right_parens: usize
+}
+
+/// Counts the atoms in a token stream by kind. Functions, commas, variables, and the Start and End
+/// sentinels are not counted.
+///     1+(2*3) has 3 numbers, 2 operators, and 1 of each parenthesis
+pub fn atom_histogram(tokens: &[ExpressionAtom]) -> AtomCounts {
+    let mut counts = AtomCounts::default();
+    for token in tokens {
+        match *token {
+            ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. } => counts.numbers += 1,
+            ExpressionAtom::Operation(_) | ExpressionAtom::ImplicitMultiply | ExpressionAtom::Negate
+            | ExpressionAtom::Custom(_) => counts.operators += 1,
+            ExpressionAtom::LeftParenthesis => counts.left_parens += 1,
+            ExpressionAtom::RightParenthesis => counts.right_parens += 1,
+            ExpressionAtom::Function(_) | ExpressionAtom::Comma | ExpressionAtom::Variable(_)
+            | ExpressionAtom::Start | ExpressionAtom::End => {}
+        }
+    }
+    counts
+}
+
+/// Measures of how complicated an expression is
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExprStats {
+    /// How many times each operation appears. Inferred multiplications count as Multiply, and
+    /// operations that do not appear are left out.
+    pub operations: HashMap<Operations, usize>,
+    /// Whole numbers and decimals
+    pub literals: usize,
+    /// The most parentheses open at once, counting the ones around function arguments
+    pub max_depth: usize
+}
+
+/// Counts the operations and literals in a token stream, and how deeply its parentheses nest.
+///     1+2*(3+4) has 2 Adds, 1 Multiply, 4 literals, and a depth of 1
+pub fn expression_stats(tokens: &[ExpressionAtom]) -> ExprStats {
+    let mut stats = ExprStats::default();
+    let mut depth: usize = 0;
+    for token in tokens {
+        match *token {
+            ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. } => stats.literals += 1,
+            ExpressionAtom::LeftParenthesis => {
+                depth += 1;
+                stats.max_depth = stats.max_depth.max(depth);
+            },
+            ExpressionAtom::RightParenthesis => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        if let Some(op) = token.as_operation() {
+            *stats.operations.entry(op).or_insert(0) += 1;
+        }
+    }
+    stats
+}
+
+/// Whether a token stream has a variable in it, stopping at the first one. Streams without any
+/// can be evaluated straight away.
+///     2*x+1 does, 2*3+1 does not
+pub fn contains_variable(tokens: &[ExpressionAtom]) -> bool {
+    tokens.iter().any(|token| matches!(*token, ExpressionAtom::Variable(_)))
+}
+
+/// Every whole number in a token stream, in the order they appear. Decimals are left out.
+///     12+3*45 has the literals 12, 3, and 45
+pub fn literals(tokens: &[ExpressionAtom]) -> Vec<u32> {
+    tokens.iter().filter_map(|token| match *token {
+        ExpressionAtom::Number(value) => Some(value),
+        _ => None
+    }).collect()
+}
+
+/// The Shannon entropy, in bits, of the kinds of atom in a token stream. All numbers are one kind,
+/// while each operation and function is a kind of its own, so expressions that mix many operations
+/// score higher than long chains of one.
+///     1+1+1+1 scores about 0.99, 2*(3-4)^5 about 2.28
+pub fn expression_entropy(tokens: &[ExpressionAtom]) -> f64 {
+    let mut kinds: Vec<(ExpressionAtom, usize)> = vec![];
+    for token in tokens {
+        let kind = match *token {
+            ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. } => ExpressionAtom::zero(),
+            other => other
+        };
+        match kinds.iter_mut().find(|&&mut (seen, _)| seen == kind) {
+            Some(&mut (_, ref mut count)) => *count += 1,
+            None => kinds.push((kind, 1))
+        }
+    }
+
+    let total = tokens.len() as f64;
+    kinds.iter().map(|&(_, count)| {
+        let share = count as f64 / total;
+        -share * share.log2()
+    }).sum()
+}
+
+/// The index of the first division by a literal zero, so it can be warned about before evaluating.
+/// The zero may be wrapped in parentheses. Only literals are found: 1/(2-2) divides by zero too,
+/// but telling takes evaluating, so it gives None.
+///     1+4/0 gives Some(3), 1/((0)) gives Some(1), and 1/0^0 gives None, since 0^0 is 1
+pub fn find_divide_by_zero(tokens: &[ExpressionAtom]) -> Option<usize> {
+    (0..tokens.len())
+        .filter(|&index| tokens[index] == ExpressionAtom::Operation(Operations::Divide))
+        .find(|&index| is_zero_operand(&tokens[index + 1..]))
+}
+
+/// Whether the operand at the start of tokens is a zero literal, alone or in parentheses
+fn is_zero_operand(tokens: &[ExpressionAtom]) -> bool {
+    let depth = tokens.iter().take_while(|&&token| token == ExpressionAtom::LeftParenthesis).count();
+    let after_zero = match tokens.get(depth) {
+        Some(&ExpressionAtom::Number(0)) | Some(&ExpressionAtom::Decimal { mantissa: 0, .. }) => &tokens[depth + 1..],
+        _ => return false
+    };
+    after_zero.len() >= depth
+        && after_zero[..depth].iter().all(|&token| token == ExpressionAtom::RightParenthesis)
+        // A power after it binds first, and gives the division a different operand
+        && after_zero.get(depth) != Some(&ExpressionAtom::Operation(Operations::Power))
+}
+
+/// Writes a token stream back out as an expression, leaving out the multiplications the tokenizer
+/// inferred, so the result reads the way it was typed.
+///     the tokens of 2(3) render as "2(3)", while those of 2*(3) keep their "*"
+/// A negation straight after an operation is wrapped in parentheses with its operand.
+///     the tokens of 3*-2 render as "3*(-2)"
+pub fn render_tokens_preserving_implicit(tokens: &[ExpressionAtom]) -> String {
+    let mut rendered = String::new();
+    let mut index = 0;
+    while index < tokens.len() {
+        let negation = matches!(tokens[index], ExpressionAtom::Negate | ExpressionAtom::Operation(Operations::Subtract));
+        if negation && index > 0 && matches!(tokens[index - 1],
+            ExpressionAtom::Operation(_) | ExpressionAtom::Negate | ExpressionAtom::Custom(_)) {
+            let end = operand_end(tokens, index + 1);
+            rendered.push_str(&format!("(-{})", render_tokens_preserving_implicit(&tokens[index + 1..end])));
+            index = end;
+            continue;
+        }
+
+        match tokens[index] {
+            ExpressionAtom::Number(value) => rendered.push_str(&value.to_string()),
+            ExpressionAtom::Decimal { mantissa, scale } => rendered.push_str(&format_decimal(mantissa, scale)),
+            ExpressionAtom::Operation(op) => rendered.push(turn_into_character(op)),
+            ExpressionAtom::ImplicitMultiply | ExpressionAtom::Start | ExpressionAtom::End => {},
+            ExpressionAtom::Negate => rendered.push('-'),
+            ExpressionAtom::Custom(symbol) | ExpressionAtom::Variable(symbol) => rendered.push(symbol),
+            ExpressionAtom::LeftParenthesis => rendered.push('('),
+            ExpressionAtom::RightParenthesis => rendered.push(')'),
+            ExpressionAtom::Function(function) => rendered.push_str(function.name()),
+            ExpressionAtom::Comma => rendered.push_str(", ")
+        }
+        index += 1;
+    }
+    rendered
+}
+
+/// The index just past the operand that starts at start. Powers on it are part of it, since they
+/// bind tighter than a negation does.
+///     in -2^2*3, the operand after the - ends before the *
+fn operand_end(tokens: &[ExpressionAtom], start: usize) -> usize {
+    let mut end = match tokens.get(start) {
+        Some(&ExpressionAtom::Negate) | Some(&ExpressionAtom::Operation(Operations::Subtract)) =>
+            return operand_end(tokens, start + 1),
+        Some(&ExpressionAtom::Function(_)) => past_closing_parenthesis(tokens, start + 1),
+        Some(&ExpressionAtom::LeftParenthesis) => past_closing_parenthesis(tokens, start),
+        Some(_) => start + 1,
+        None => return start
+    };
+    while tokens.get(end) == Some(&ExpressionAtom::Operation(Operations::Power)) {
+        end = operand_end(tokens, end + 1);
+    }
+    end
+}
+
+/// The index just past the parenthesis that closes the one at open
+fn past_closing_parenthesis(tokens: &[ExpressionAtom], open: usize) -> usize {
+    let mut depth: usize = 0;
+    for (index, token) in tokens.iter().enumerate().skip(open) {
+        match *token {
+            ExpressionAtom::LeftParenthesis => depth += 1,
+            ExpressionAtom::RightParenthesis if depth <= 1 => return index + 1,
+            ExpressionAtom::RightParenthesis => depth -= 1,
+            _ => {}
+        }
+    }
+    tokens.len()
+}
+
+/// Finds the operation to calculate next when reducing infix tokens one step at a time. It has to
+/// sit directly between two numbers, without a neighbouring operation that claims one of them first.
+/// Of those, the highest precedence wins, then the leftmost.
+///     1+2*3 picks the * at 3
+///     (1+2)*3 picks the + at 2, since the * has a parenthesis for an operand
+pub fn next_reducible(tokens: &[ExpressionAtom]) -> Option<usize> {
+    (1..tokens.len().saturating_sub(1))
+        .filter_map(|index| tokens[index].as_operation().map(|op| (index, op)))
+        .filter(|&(index, _)| is_value(&tokens[index - 1]) && is_value(&tokens[index + 1]))
+        .filter(|&(index, op)| {
+            let left_claimed = index >= 2 && operation_at(tokens, index - 2).is_some_and(|left|
+                precedence(left) > precedence(op) || (precedence(left) == precedence(op) && !is_right_associative(op)));
+            let right_claimed = operation_at(tokens, index + 2).is_some_and(|right|
+                precedence(right) > precedence(op) || (precedence(right) == precedence(op) && is_right_associative(op)));
+            !left_claimed && !right_claimed
+        })
+        .max_by_key(|&(index, op)| (precedence(op), Reverse(index)))
+        .map(|(index, _)| index)
+}
+
+/// The operation with the highest precedence anywhere in the tokens, whatever parentheses it is
+/// in. Of operations with the same precedence, the leftmost is given.
+///     1+2*3 is Some(Multiply), (1+2)*3 is Some(Multiply), and 7 is None
+pub fn highest_precedence_present(tokens: &[ExpressionAtom]) -> Option<Operations> {
+    tokens.iter()
+        .filter_map(ExpressionAtom::as_operation)
+        .enumerate()
+        .max_by_key(|&(index, op)| (precedence(op), Reverse(index)))
+        .map(|(_, op)| op)
+}
+
+/// Carries out the next step of calculating infix tokens: the operation next_reducible picks, or
+/// else a function call whose arguments are all numbers, or else the parentheses around a lone
+/// number. Parentheses left around a result go in the same step. None when there is nothing to do.
+///     1+2*3 becomes 1+6, (1+2)*3 becomes 3*3, and clamp(15, 0, 10) becomes 10
+pub fn reduce_once(tokens: &[ExpressionAtom]) -> Result<Option<Vec<ExpressionAtom>>, EvalError> {
+    let mut reduced = tokens.to_vec();
+    let mut at = if let Some(index) = next_reducible(tokens) {
+        let op = tokens[index].as_operation().ok_or(EvalError::MissingOperator)?;
+        let value = try_calculate(value_of(&tokens[index - 1])?, value_of(&tokens[index + 1])?, op)?;
+        reduced.splice(index - 1..index + 2, Some(ExpressionAtom::Number(value)));
+        index - 1
+    } else if let Some((start, end, function)) = next_call(tokens) {
+        let arguments = tokens[start + 2..end].iter().step_by(2).map(value_of).collect::<Result<Vec<u32>, EvalError>>()?;
+        let function = function.with_arguments(arguments.len());
+        if arguments.len() != function.arity() {
+            return Err(EvalError::WrongArgumentCount { function, expected: function.arity(), found: arguments.len() });
+        }
+        reduced.splice(start..end + 1, Some(ExpressionAtom::Number(u32::call(function, &arguments)?)));
+        start
+    } else if let Some(index) = (1..tokens.len()).find(|&index| is_lone_value(tokens, index)) {
+        index
+    } else {
+        return Ok(None);
+    };
+
+    while is_lone_value(&reduced, at) {
+        reduced.remove(at + 1);
+        reduced.remove(at - 1);
+        at -= 1;
+        // Without its parentheses the number would run into its neighbour, like the 3 in 2(3)
+        for neighbour in [at.checked_sub(1), Some(at + 1)].iter().flatten() {
+            if reduced.get(*neighbour) == Some(&ExpressionAtom::ImplicitMultiply) {
+                reduced[*neighbour] = ExpressionAtom::Operation(Operations::Multiply);
+            }
+        }
+    }
+    Ok(Some(reduced))
+}
+
+/// Every step of calculating an expression, rendered as it would be typed, from the expression
+/// itself down to its result. Each step is one reduce_once.
+///     "1+2*3" is ["1+2*3", "1+6", "7"]
+pub fn animation_frames(expression: &str) -> Result<Vec<String>, EvalError> {
+    // Evaluating first reports errors the same way everywhere else does
+    let result = evaluate(expression)?;
+    let mut tokens = OperationTokenTree::try_evaluate_tokens(expression)?;
+    let mut frames = vec![render_tokens_preserving_implicit(&tokens)];
+    while let Some(reduced) = reduce_once(&tokens)? {
+        tokens = reduced;
+        frames.push(render_tokens_preserving_implicit(&tokens));
+    }
+    // Steps stop short of the result where one of the tokens is not understood on its own, like
+    // a - that negates
+    if frames.last() != Some(&result.to_string()) {
+        frames.push(result.to_string());
+    }
+    Ok(frames)
+}
+
+/// The first function call whose arguments are all numbers, as the index of the function, the
+/// index of its closing parenthesis, and the function
+fn next_call(tokens: &[ExpressionAtom]) -> Option<(usize, usize, Function)> {
+    tokens.iter().enumerate().find_map(|(start, token)| {
+        let function = match *token {
+            ExpressionAtom::Function(function) if tokens.get(start + 1) == Some(&ExpressionAtom::LeftParenthesis) => function,
+            _ => return None
+        };
+        let mut index = start + 2;
+        while is_value(tokens.get(index)?) {
+            match *tokens.get(index + 1)? {
+                ExpressionAtom::Comma => index += 2,
+                ExpressionAtom::RightParenthesis => return Some((start, index + 1, function)),
+                _ => return None
+            }
+        }
+        None
+    })
+}
+
+/// Whether the token at index is a number alone in parentheses that do not belong to a function
+fn is_lone_value(tokens: &[ExpressionAtom], index: usize) -> bool {
+    index >= 1 && tokens.get(index - 1) == Some(&ExpressionAtom::LeftParenthesis)
+        && tokens.get(index).is_some_and(is_value)
+        && tokens.get(index + 1) == Some(&ExpressionAtom::RightParenthesis)
+        && !(index >= 2 && matches!(tokens[index - 2], ExpressionAtom::Function(_)))
+}
+
+/// The whole number a value token holds
+fn value_of(token: &ExpressionAtom) -> Result<u32, EvalError> {
+    match *token {
+        ExpressionAtom::Number(value) => Ok(value),
+        ExpressionAtom::Decimal { mantissa, scale } => u32::from_decimal(mantissa, scale),
+        _ => Err(EvalError::MissingOperand)
+    }
+}
+
+fn is_value(token: &ExpressionAtom) -> bool {
+    matches!(*token, ExpressionAtom::Number(_) | ExpressionAtom::Decimal { .. })
+}
+
+/// The binary operation at index, if there is one. A - with nothing before it is a negation.
+fn operation_at(tokens: &[ExpressionAtom], index: usize) -> Option<Operations> {
+    let op = tokens.get(index)?.as_operation()?;
+    let after_value = matches!(tokens[index.saturating_sub(1)],
+        ExpressionAtom::Variable(_) | ExpressionAtom::RightParenthesis) || is_value(&tokens[index.saturating_sub(1)]);
+    if index == 0 || !after_value {
+        return None;
+    }
+    Some(op)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {OperationTokenTree, Operations, evaluate, evaluate_rpn, evaluate_signed, to_rpn, tokenize_bounded};
+
+    #[test]
+    fn test_strip_outer_parens() {
+        let wrapped = OperationTokenTree::evaluate_tokens("((1+2))");
+        assert_eq!(strip_outer_parens(&wrapped), &OperationTokenTree::evaluate_tokens("1+2")[..]);
+        assert_eq!(evaluate("((1+2))"), Ok(3));
+
+        let separate = OperationTokenTree::evaluate_tokens("(1)+(2)");
+        assert_eq!(strip_outer_parens(&separate), &separate[..]);
+        assert_eq!(evaluate("(1)+(2)"), Ok(3));
+    }
+
+    #[test]
+    fn test_split_top_level() {
+        let tokens = |expression| OperationTokenTree::evaluate_tokens(expression);
+        assert_eq!(split_top_level(&tokens("1+2*3+4"), Operations::Add), vec![tokens("1"), tokens("2*3"), tokens("4")]);
+        assert_eq!(split_top_level(&tokens("1+2*3+4"), Operations::Multiply), vec![tokens("1+2"), tokens("3+4")]);
+        assert_eq!(split_top_level(&tokens("(1+2)*3"), Operations::Add), vec![tokens("(1+2)*3")]);
+        assert_eq!(split_top_level(&tokens("clamp(1+2, 0, 9)+4"), Operations::Add),
+            vec![tokens("clamp(1+2, 0, 9)"), tokens("4")]);
+        assert_eq!(split_top_level(&tokens("-3-4"), Operations::Subtract), vec![tokens("-3"), tokens("4")]);
+        assert_eq!(split_top_level(&tokens("2(3)"), Operations::Multiply), vec![tokens("2"), tokens("(3)")]);
+        assert_eq!(split_top_level(&tokens("5"), Operations::Add), vec![tokens("5")]);
+
+        let terms = split_top_level(&tokens("1+2*3+4"), Operations::Add);
+        let sum: u32 = terms.iter().map(|term| evaluate_rpn(&to_rpn(term).unwrap()).unwrap()).sum();
+        assert_eq!(Ok(sum), evaluate("1+2*3+4"));
+    }
+
+    #[test]
+    fn test_token_diff() {
+        let sum = OperationTokenTree::evaluate_tokens("1+2");
+        let product = OperationTokenTree::evaluate_tokens("1*2");
+        assert_eq!(token_diff(&sum, &product), vec![(1,
+            Some(ExpressionAtom::Operation(Operations::Add)),
+            Some(ExpressionAtom::Operation(Operations::Multiply)))]);
+        assert_eq!(token_diff(&sum, &sum), vec![]);
+
+        let longer = OperationTokenTree::evaluate_tokens("1+2+3");
+        assert_eq!(token_diff(&sum, &longer), vec![
+            (3, None, Some(ExpressionAtom::Operation(Operations::Add))),
+            (4, None, Some(ExpressionAtom::Number(3)))]);
+    }
+
+    #[test]
+    fn test_atom_histogram() {
+        assert_eq!(atom_histogram(&OperationTokenTree::evaluate_tokens("1+(2*3)")),
+            AtomCounts { numbers: 3, operators: 2, left_parens: 1, right_parens: 1 });
+        assert_eq!(atom_histogram(&OperationTokenTree::evaluate_tokens("2(0.5)")),
+            AtomCounts { numbers: 2, operators: 1, left_parens: 1, right_parens: 1 });
+        assert_eq!(atom_histogram(&[]), AtomCounts::default());
+    }
+
+    #[test]
+    fn test_expression_stats() {
+        let stats = expression_stats(&OperationTokenTree::evaluate_tokens("2(3)-clamp((1), 0, 4)"));
+        assert_eq!(stats.operations, vec![(Operations::Multiply, 1), (Operations::Subtract, 1)].into_iter().collect());
+        assert_eq!(stats.literals, 5);
+        assert_eq!(stats.max_depth, 2);
+        assert_eq!(expression_stats(&[]), ExprStats::default());
+    }
+
+    #[test]
+    fn test_contains_variable() {
+        let tokens = |expression| OperationTokenTree::evaluate_tokens(expression);
+        assert!(contains_variable(&tokens("2*x+1")));
+        assert!(contains_variable(&tokens("x")));
+        assert!(contains_variable(&tokens("clamp(y, 0, 10)")));
+        assert!(!contains_variable(&tokens("2*3+1")));
+        assert!(!contains_variable(&tokens("abs(5)")));
+        assert!(!contains_variable(&[]));
+    }
+
+    #[test]
+    fn test_find_divide_by_zero() {
+        let tokens = |expression| OperationTokenTree::evaluate_tokens(expression);
+        assert_eq!(find_divide_by_zero(&tokens("1/0")), Some(1));
+        assert_eq!(find_divide_by_zero(&tokens("2*3+4/0")), Some(5));
+        assert_eq!(find_divide_by_zero(&tokens("1/((0))+2")), Some(1));
+        assert_eq!(find_divide_by_zero(&tokens("8/2+1/0.0")), Some(5));
+        assert_eq!(find_divide_by_zero(&tokens("1/0(2)")), Some(1));
+
+        // Not literal zeros, so they are only found by evaluating
+        assert_eq!(find_divide_by_zero(&tokens("1/(2-2)")), None);
+        assert_eq!(find_divide_by_zero(&tokens("1/(0+1)")), None);
+        assert_eq!(find_divide_by_zero(&tokens("1/0^0")), None);
+        assert_eq!(find_divide_by_zero(&tokens("1/10")), None);
+        assert_eq!(find_divide_by_zero(&tokens("0/1")), None);
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(literals(&OperationTokenTree::evaluate_tokens("12+3*45")), vec![12, 3, 45]);
+        assert_eq!(literals(&OperationTokenTree::evaluate_tokens("2(0.5)+7")), vec![2, 7]);
+        assert_eq!(literals(&[]), vec![]);
+    }
+
+    #[test]
+    fn test_render_tokens_preserving_implicit() {
+        let render = |expression| render_tokens_preserving_implicit(&OperationTokenTree::evaluate_tokens(expression));
+        assert_eq!(render("2(3)"), "2(3)");
+        assert_eq!(render("2*3"), "2*3");
+        assert_eq!(render("2*(3)"), "2*(3)");
+        assert_eq!(render("(1+2)(0.5)"), "(1+2)(0.5)");
+        assert_eq!(render("3clamp(1,2,3)"), "3clamp(1, 2, 3)");
+        assert_eq!(evaluate(&render("2(3+4)")), evaluate("2(3+4)"));
+    }
+
+    #[test]
+    fn test_render_negatives() {
+        let render = |expression| render_tokens_preserving_implicit(&OperationTokenTree::evaluate_tokens(expression));
+        assert_eq!(render("3*-2"), "3*(-2)");
+        assert_eq!(evaluate_signed(&render("3*-2")), Ok(-6));
+        assert_eq!(render("-2*3"), "-2*3");
+        assert_eq!(render("1--2"), "1-(-2)");
+        assert_eq!(render("--4"), "-(-4)");
+        assert_eq!(render("2*-3^2+1"), "2*(-3^2)+1");
+        assert_eq!(render("2^-(1+1)"), "2^(-(1+1))");
+        assert_eq!(render("4*-abs(0-2)"), "4*(-abs(0-2))");
+        assert_eq!(render("(-5)"), "(-5)");
+
+        for expression in &["3*-2", "1--2", "2*-3^2+1", "5+--5", "2*-3x"] {
+            let rendered = render(expression);
+            let signed = |expression: &str| evaluate_signed(&expression.replace('x', "(7)"));
+            assert_eq!(signed(&rendered), signed(expression), "{} as {}", expression, rendered);
+        }
+
+        let bounded = tokenize_bounded("3*-2").unwrap();
+        assert_eq!(render_tokens_preserving_implicit(&bounded), "3*(-2)");
+    }
+
+    #[test]
+    fn test_expression_entropy() {
+        let entropy = |expression| expression_entropy(&OperationTokenTree::evaluate_tokens(expression));
+        let chain = entropy("1+1+1+1");
+        assert!((chain - 0.985).abs() < 1e-3);
+        assert!(entropy("2*(3-4)^5/clamp(1, 2.5, 3)") > chain);
+        assert!(entropy("1+2+3+4") == chain);
+        assert_eq!(entropy("7"), 0.0);
+        assert_eq!(expression_entropy(&[]), 0.0);
+    }
+
+    #[test]
+    fn test_next_reducible() {
+        let pick = |expression| next_reducible(&OperationTokenTree::evaluate_tokens(expression));
+        assert_eq!(pick("1+2*3"), Some(3));
+        assert_eq!(pick("2*3+4"), Some(1));
+        assert_eq!(pick("1-2-3"), Some(1));
+        assert_eq!(pick("2^3^2"), Some(3));
+        assert_eq!(pick("(1+2)*3"), Some(2));
+        assert_eq!(pick("1*2^3"), Some(3));
+        assert_eq!(pick("-2*3"), Some(2));
+        assert_eq!(pick("(7)"), None);
+        assert_eq!(pick(""), None);
+    }
+
+    #[test]
+    fn test_paren_groups() {
+        let groups = |expression| paren_groups(&OperationTokenTree::evaluate_tokens(expression));
+        assert_eq!(groups("1+(2*(3+4))"), vec![2..11, 5..10]);
+        assert_eq!(groups("(1)+(2)"), vec![0..3, 4..7]);
+        assert_eq!(groups("((1+2)*(3))"), vec![0..11, 1..6, 7..10]);
+        assert_eq!(groups("clamp(1, 2, 3)"), vec![1..8]);
+        assert_eq!(groups("1+2"), vec![]);
+        assert_eq!(groups("(1+(2"), vec![]);
+        assert_eq!(groups("1)+(2)"), vec![3..6]);
+
+        let tokens = OperationTokenTree::evaluate_tokens("2*(3+4)");
+        let inner = paren_groups(&tokens)[0].clone();
+        assert_eq!(evaluate_rpn(&to_rpn(&tokens[inner]).unwrap()), Ok(7));
+    }
+
+    #[test]
+    fn test_highest_precedence_present() {
+        let highest = |expression| highest_precedence_present(&OperationTokenTree::evaluate_tokens(expression));
+        assert_eq!(highest("1+2*3"), Some(Operations::Multiply));
+        assert_eq!(highest("(1+2)*3"), Some(Operations::Multiply));
+        assert_eq!(highest("1+(2^3)"), Some(Operations::Power));
+        assert_eq!(highest("8/2*3"), Some(Operations::Divide));
+        assert_eq!(highest("2(3)+1"), Some(Operations::Multiply));
+        assert_eq!(highest("1>2 & 3"), Some(Operations::Greater));
+        assert_eq!(highest("42"), None);
+        assert_eq!(highest("(7)"), None);
+        assert_eq!(highest(""), None);
+    }
+
+    #[test]
+    fn test_reduce_once() {
+        let step = |expression| reduce_once(&OperationTokenTree::evaluate_tokens(expression))
+            .map(|reduced| reduced.map(|tokens| render_tokens_preserving_implicit(&tokens)));
+        assert_eq!(step("1+2*3"), Ok(Some("1+6".to_string())));
+        assert_eq!(step("(1+2)*3"), Ok(Some("3*3".to_string())));
+        assert_eq!(step("((7))"), Ok(Some("7".to_string())));
+        assert_eq!(step("2(3)"), Ok(Some("2*3".to_string())));
+        assert_eq!(step("clamp(15, 0, 10)"), Ok(Some("10".to_string())));
+        assert_eq!(step("clamp(1, 2)"),
+            Err(EvalError::WrongArgumentCount { function: Function::Clamp, expected: 3, found: 2 }));
+        assert_eq!(step("4/0"), Err(EvalError::DivideByZero));
+        assert_eq!(step("7"), Ok(None));
+    }
+
+    #[test]
+    fn test_animation_frames() {
+        let frames = |expression| animation_frames(expression).map(|frames| frames.join(" | "));
+        assert_eq!(animation_frames("1+2*3"), Ok(vec!["1+2*3".to_string(), "1+6".to_string(), "7".to_string()]));
+        assert_eq!(frames("(1+2)*(3+4)"), Ok("(1+2)*(3+4) | 3*(3+4) | 3*7 | 21".to_string()));
+        assert_eq!(frames("2^3^2"), Ok("2^3^2 | 2^9 | 512".to_string()));
+        assert_eq!(frames("clamp(2*8, 1, 10)+1"), Ok("clamp(2*8, 1, 10)+1 | clamp(16, 1, 10)+1 | 10+1 | 11".to_string()));
+        assert_eq!(frames("2(3)"), Ok("2(3) | 2*3 | 6".to_string()));
+        assert_eq!(frames("5"), Ok("5".to_string()));
+        assert_eq!(frames("1/0"), Err(EvalError::DivideByZero));
+    }
+}
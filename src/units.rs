@@ -0,0 +1,165 @@
+use std::fmt::{Display};
+use std::vec::Vec;
+
+use {ExpressionAtom, Operations, EvalError, Field, Tokenizer, turn_into_function};
+use evaluator::{to_rpn, reduce_rpn_with, Condition};
+
+/// The first of the characters standing in for numbers in the tokens, so each can be looked up with
+/// its unit. These are in the Private Use Area, which no expression is written with.
+const PLACEHOLDER_BASE: u32 = 0xE000;
+
+/// A number with the unit it was written with, like the 3m in 3m+2m. Numbers written without a
+/// unit have none.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: Option<String>
+}
+
+impl Quantity {
+    /// Calculates two quantities, checking that their units fit the operation. Adding, subtracting,
+    /// and comparing need the same unit on both sides. Multiplying needs at least one side without a
+    /// unit, and dividing needs a right side without a unit or with the left side's unit, which
+    /// cancels. Powers cannot have units at all. A 0 without a unit fits any unit, since no amount of
+    /// anything is the same; it is also how negating, as 0-value, keeps the value's unit.
+    ///     3m+2m is 5m, 3m*2 is 6m, 6m/2m is 3, 3m+0 is 3m, and 3m+2s is IncompatibleUnits
+    pub fn calculate(&self, rval: &Quantity, operation: Operations) -> Result<Quantity, EvalError> {
+        let unit = match (operation, &self.unit, &rval.unit) {
+            (Operations::Multiply, left, None) => left.clone(),
+            (Operations::Multiply, None, right) => right.clone(),
+            (Operations::Divide, left, None) => left.clone(),
+            (Operations::Divide, Some(left), Some(right)) if left == right => None,
+            (Operations::Power, None, None) => None,
+            (Operations::Multiply, _, _) | (Operations::Divide, _, _) | (Operations::Power, _, _) =>
+                return Err(EvalError::IncompatibleUnits),
+            (_, left, right) if left == right => left.clone(),
+            (_, left, None) if rval.value == 0.0 => left.clone(),
+            (_, None, right) if self.value == 0.0 => right.clone(),
+            _ => return Err(EvalError::IncompatibleUnits)
+        };
+        if operation == Operations::Divide && rval.value == 0.0 {
+            return Err(EvalError::DivideByZero);
+        }
+        Ok(Quantity { value: self.value.calculate(rval.value, operation)?, unit })
+    }
+}
+
+/// Written as the value followed directly by the unit, like 5m
+impl Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.unit {
+            Some(ref unit) => write!(f, "{}{}", self.value, unit),
+            None => write!(f, "{}", self.value)
+        }
+    }
+}
+
+impl Condition for Quantity {
+    fn is_true(&self) -> bool {
+        self.value != 0.0
+    }
+}
+
+/// Evaluates an expression where numbers can be followed by a unit, keeping track of the unit of
+/// every value. A unit is any word after a number that is not a function name.
+///     "3m + 2m" is 5m, "1.5km*2" is 3km, and "3m + 2s" is IncompatibleUnits
+/// No function takes units, so any function call but if is UnsupportedFunction.
+pub fn evaluate_units(expression: &str) -> Result<Quantity, EvalError> {
+    let (tokens, quantities) = read_quantities(expression)?;
+    reduce_rpn_with(&to_rpn(&tokens)?,
+        |mantissa, scale| Ok(Quantity { value: f64::from_decimal(mantissa, scale)?, unit: None }),
+        |left, right, op| left.calculate(&right, op),
+        |function, _| Err(EvalError::UnsupportedFunction(function)),
+        |symbol, _, _| Err(EvalError::UnknownOperator(symbol)),
+        |name| (name as u32).checked_sub(PLACEHOLDER_BASE)
+            .and_then(|index| quantities.get(index as usize))
+            .cloned()
+            .ok_or(EvalError::UnboundVariable(name)))
+}
+
+/// Tokenizes an expression, taking the word after each number as its unit. Each number is read into
+/// a Quantity, and left in the tokens as a placeholder variable for its index, so it is found again
+/// by what it is rather than by when it is reached.
+fn read_quantities(expression: &str) -> Result<(Vec<ExpressionAtom>, Vec<Quantity>), EvalError> {
+    let mut tokens: Vec<ExpressionAtom> = vec![];
+    let mut quantities: Vec<Quantity> = vec![];
+    let mut tokenizer = Tokenizer::new(expression);
+    while let Some(token) = tokenizer.next() {
+        let value = match token? {
+            ExpressionAtom::Number(value) => f64::from(value),
+            ExpressionAtom::Decimal { mantissa, scale } => f64::from_decimal(mantissa, scale)?,
+            token => {
+                tokens.push(token);
+                continue;
+            }
+        };
+
+        let unit = match tokenizer.peek_word() {
+            Some(word) if turn_into_function(word).is_none() => tokenizer.skip_word().map(str::to_string),
+            _ => None
+        };
+        let name = std::char::from_u32(PLACEHOLDER_BASE + quantities.len() as u32).ok_or(EvalError::Overflow)?;
+        tokens.push(ExpressionAtom::Variable(name));
+        quantities.push(Quantity { value, unit });
+    }
+    Ok((tokens, quantities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Function;
+
+    fn evaluated(expression: &str) -> Result<String, EvalError> {
+        evaluate_units(expression).map(|quantity| quantity.to_string())
+    }
+
+    #[test]
+    fn test_same_unit_addition() {
+        assert_eq!(evaluated("3m + 2m"), Ok("5m".to_string()));
+        assert_eq!(evaluated("1.5km+0.5km-1km"), Ok("1km".to_string()));
+        assert_eq!(evaluated("-3m+5m"), Ok("2m".to_string()));
+        assert_eq!(evaluated("(2kg+3kg)*2"), Ok("10kg".to_string()));
+        assert_eq!(evaluated("3+2"), Ok("5".to_string()));
+    }
+
+    #[test]
+    fn test_scalar_multiplication() {
+        assert_eq!(evaluated("3m*2"), Ok("6m".to_string()));
+        assert_eq!(evaluated("2*3m"), Ok("6m".to_string()));
+        assert_eq!(evaluated("6m/4"), Ok("1.5m".to_string()));
+        assert_eq!(evaluated("6m/2m"), Ok("3".to_string()));
+        assert_eq!(evaluated("6m/0"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluated("3m(2)"), Ok("6m".to_string()));
+        assert_eq!(evaluated("2 (3m)"), Ok("6m".to_string()));
+    }
+
+    #[test]
+    fn test_if_keeps_units_with_their_numbers() {
+        assert_eq!(evaluated("if(1, 2m, 3s)"), Ok("2m".to_string()));
+        assert_eq!(evaluated("if(0, 2m, 3s)"), Ok("3s".to_string()));
+        assert_eq!(evaluated("if(0, 2m, 3s)+4s"), Ok("7s".to_string()));
+        assert_eq!(evaluated("if(2m-2m, 1kg, 5s)-1s"), Ok("4s".to_string()));
+    }
+
+    #[test]
+    fn test_negation_and_zero() {
+        assert_eq!(evaluated("-3m"), Ok("-3m".to_string()));
+        assert_eq!(evaluated("2m*-3"), Ok("-6m".to_string()));
+        assert_eq!(evaluated("-(1m+2m)+5m"), Ok("2m".to_string()));
+        assert_eq!(evaluated("3m+0"), Ok("3m".to_string()));
+        assert_eq!(evaluated("0-3s"), Ok("-3s".to_string()));
+    }
+
+    #[test]
+    fn test_incompatible_units() {
+        assert_eq!(evaluated("3m + 2s"), Err(EvalError::IncompatibleUnits));
+        assert_eq!(evaluated("3m + 2"), Err(EvalError::IncompatibleUnits));
+        assert_eq!(evaluated("3m*2s"), Err(EvalError::IncompatibleUnits));
+        assert_eq!(evaluated("6/2m"), Err(EvalError::IncompatibleUnits));
+        assert_eq!(evaluated("2m^2"), Err(EvalError::IncompatibleUnits));
+        assert_eq!(evaluated("abs(3m)"), Err(EvalError::UnsupportedFunction(Function::Abs)));
+        assert_eq!(evaluated("clamp(1m, 2m, 3m)"), Err(EvalError::UnsupportedFunction(Function::Clamp)));
+        assert_eq!(evaluated("3m + x"), Err(EvalError::UnboundVariable('x')));
+    }
+}
@@ -0,0 +1,121 @@
+use std::vec::Vec;
+
+use {EvalError, Field, Function, OperationTokenTree};
+use {is_boolean_operation, try_calculate};
+use evaluator::{to_rpn, reduce_rpn};
+
+/// The result of an expression, keeping truth values apart from numbers
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+
+    /// e.g: 1+1
+    Num(u32),
+
+    /// e.g: 3>2
+    Bool(bool),
+
+    /// e.g: pct(30, 20, 50)
+    List(Vec<u32>),
+
+    /// e.g: [[1,2],[3,4]]
+    Matrix(Vec<Vec<u32>>)
+}
+
+/// Evaluates an expression, returning a Value::Bool when the last operation calculated is a
+/// comparison or logical operation and a Value::Num otherwise.
+///     "3>2" evaluates to Bool(true)
+///     "1+1" evaluates to Num(2)
+/// pct(...) gives each argument as a whole percentage of their total, as a Value::List. The
+/// percentages are rounded so they always add up to 100. A list cannot be calculated with.
+///     "pct(30, 20, 50)" evaluates to List([30, 20, 50])
+///     "pct(1, 2)+1" is UnsupportedOperation(Add)
+pub fn evaluate_value(expression: &str) -> Result<Value, EvalError> {
+    let rpn = to_rpn(&OperationTokenTree::try_evaluate_tokens(expression)?)?;
+    reduce_rpn(&rpn, |mantissa, scale| u32::from_decimal(mantissa, scale).map(Value::Num),
+        |lval, rval, operation| {
+            let result = try_calculate(number(&lval, || EvalError::UnsupportedOperation(operation))?,
+                                       number(&rval, || EvalError::UnsupportedOperation(operation))?, operation)?;
+            Ok(if is_boolean_operation(operation) { Value::Bool(result != 0) } else { Value::Num(result) })
+        },
+        |function, arguments| {
+            let numbers = arguments.iter().map(|argument| number(argument, || EvalError::UnsupportedFunction(function)))
+                .collect::<Result<Vec<u32>, EvalError>>()?;
+            match function {
+                Function::Pct(_) => percent_of_total(&numbers).map(Value::List),
+                _ => u32::call(function, &numbers).map(Value::Num)
+            }
+        })
+}
+
+/// A value as a number, where true is 1 and false is 0. Lists and matrices give the error.
+fn number<E: FnOnce() -> EvalError>(value: &Value, error: E) -> Result<u32, EvalError> {
+    match *value {
+        Value::Num(number) => Ok(number),
+        Value::Bool(truth) => Ok(truth as u32),
+        Value::List(_) | Value::Matrix(_) => Err(error())
+    }
+}
+
+/// Each value as a whole percentage of the total. Rounding goes to the values that lost the most
+/// to it, so the percentages add up to exactly 100.
+///     [1, 1, 1] is [34, 33, 33]
+fn percent_of_total(values: &[u32]) -> Result<Vec<u32>, EvalError> {
+    let total: u64 = values.iter().map(|&value| u64::from(value)).sum();
+    if total == 0 {
+        return Err(EvalError::DivideByZero);
+    }
+
+    let mut percentages: Vec<u32> = values.iter().map(|&value| (u64::from(value) * 100 / total) as u32).collect();
+    let mut by_remainder: Vec<usize> = (0..values.len()).collect();
+    by_remainder.sort_by_key(|&index| std::cmp::Reverse(u64::from(values[index]) * 100 % total));
+    let missing = 100 - percentages.iter().sum::<u32>();
+    for &index in by_remainder.iter().take(missing as usize) {
+        percentages[index] += 1;
+    }
+    Ok(percentages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_value() {
+        assert_eq!(evaluate_value("3>2"), Ok(Value::Bool(true)));
+        assert_eq!(evaluate_value("1+1"), Ok(Value::Num(2)));
+        assert_eq!(evaluate_value("1+1=3"), Ok(Value::Bool(false)));
+        assert_eq!(evaluate_value("(2>1)+1"), Ok(Value::Num(2)));
+    }
+
+    #[test]
+    fn test_pct() {
+        assert_eq!(evaluate_value("pct(30, 20, 50)"), Ok(Value::List(vec![30, 20, 50])));
+        assert_eq!(evaluate_value("pct(3, 2, 5)"), Ok(Value::List(vec![30, 20, 50])));
+        assert_eq!(evaluate_value("pct(1, 1, 1)"), Ok(Value::List(vec![34, 33, 33])));
+        assert_eq!(evaluate_value("pct(2*3, clamp(9, 0, 2), 0)"), Ok(Value::List(vec![75, 25, 0])));
+        assert_eq!(evaluate_value("pct(7)"), Ok(Value::List(vec![100])));
+
+        for expression in &["pct(1, 1, 1)", "pct(1, 2, 3, 4, 5, 6)", "pct(4294967295, 4294967295, 1)"] {
+            match evaluate_value(expression) {
+                Ok(Value::List(percentages)) => assert_eq!(percentages.iter().sum::<u32>(), 100),
+                other => panic!("{:?}", other)
+            }
+        }
+
+        assert_eq!(evaluate_value("pct(0, 0)"), Err(EvalError::DivideByZero));
+        assert_eq!(evaluate_value("pct(1, )"), Err(EvalError::MissingOperand));
+        assert_eq!(evaluate_value("pct(1, 1/0)"), Err(EvalError::DivideByZero));
+    }
+
+    #[test]
+    fn test_pct_is_a_function() {
+        assert_eq!(::turn_into_function("pct"), Some(Function::Pct(1)));
+        assert!(Function::all().iter().any(|function| function.name() == "pct"));
+        assert_eq!(evaluate_value(" pct ( 1 , 3 ) "), Ok(Value::List(vec![25, 75])));
+        assert_eq!(evaluate_value("pct(if(1, 2, 3), 2)"), Ok(Value::List(vec![50, 50])));
+        assert_eq!(evaluate_value("pct(1, 2)+1"), Err(EvalError::UnsupportedOperation(::Operations::Add)));
+        assert_eq!(evaluate_value("clamp(pct(1), 0, 1)"), Err(EvalError::UnsupportedFunction(Function::Clamp)));
+        assert_eq!(evaluate_value("pct(1)pct(2)"), Err(EvalError::UnsupportedOperation(::Operations::Multiply)));
+        assert_eq!(::evaluate("pct(1, 2)"), Err(EvalError::UnsupportedFunction(Function::Pct(2))));
+    }
+}